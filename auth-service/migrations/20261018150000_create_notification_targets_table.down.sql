@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS notification_targets;
+
+UPDATE users SET two_fa_method = 'email' WHERE two_fa_method IN ('sms', 'push');
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_two_fa_method_check;
+ALTER TABLE users ADD CONSTRAINT users_two_fa_method_check
+    CHECK (two_fa_method IN ('none', 'email', 'totp'));
\ No newline at end of file
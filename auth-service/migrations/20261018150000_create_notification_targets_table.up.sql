@@ -0,0 +1,11 @@
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_two_fa_method_check;
+ALTER TABLE users ADD CONSTRAINT users_two_fa_method_check
+    CHECK (two_fa_method IN ('none', 'email', 'sms', 'push', 'totp'));
+
+CREATE TABLE IF NOT EXISTS notification_targets(
+   email TEXT NOT NULL PRIMARY KEY REFERENCES users(email) ON DELETE CASCADE,
+   two_fa_method TEXT CHECK (two_fa_method IN ('sms', 'push')),
+   target TEXT,
+   pending_two_fa_method TEXT CHECK (pending_two_fa_method IN ('sms', 'push')),
+   pending_target TEXT
+);
\ No newline at end of file
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS email_outbox(
+   id TEXT NOT NULL PRIMARY KEY,
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   html_body TEXT NOT NULL,
+   text_body TEXT NOT NULL,
+   status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'dead')),
+   attempts INTEGER NOT NULL DEFAULT 0,
+   next_attempt_at BIGINT NOT NULL,
+   last_error TEXT,
+   created_at BIGINT NOT NULL,
+   sent_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS email_outbox_due_idx ON email_outbox(next_attempt_at) WHERE status = 'pending';
\ No newline at end of file
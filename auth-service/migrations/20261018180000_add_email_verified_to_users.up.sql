@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+-- Accounts created before verification was required keep working
+UPDATE users SET email_verified = TRUE;
\ No newline at end of file
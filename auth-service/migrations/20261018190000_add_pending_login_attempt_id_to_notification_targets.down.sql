@@ -0,0 +1 @@
+ALTER TABLE notification_targets DROP COLUMN IF EXISTS pending_login_attempt_id;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- The login attempt the code confirming the pending target was sent with
+ALTER TABLE notification_targets ADD COLUMN IF NOT EXISTS pending_login_attempt_id TEXT;
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- Random handle authenticators store in place of the email address. Passkeys
+-- registered before it existed were given the SHA-256 of the email, which they
+-- keep so the authenticators holding them still match.
+ALTER TABLE webauthn_credentials ADD COLUMN IF NOT EXISTS user_handle TEXT;
+
+UPDATE webauthn_credentials
+SET user_handle = rtrim(translate(encode(sha256(convert_to(email, 'UTF8')), 'base64'), '+/', '-_'), '=')
+WHERE user_handle IS NULL;
+
+ALTER TABLE webauthn_credentials ALTER COLUMN user_handle SET NOT NULL;
\ No newline at end of file
@@ -2,33 +2,92 @@ use std::sync::Arc;
 
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::domain::{
+    BannedTokenStore, EmailClient, EmailOutboxStore, NotificationChannel, NotificationTargetStore,
+    PasswordResetTokenStore, RecoveryCodeStore, RefreshTokenStore, ServiceClients, SessionStore,
+    TotpSecretStore, TrustedDeviceStore, TwoFACodeStore, UserStore, VerificationEmailStore,
+    WebAuthnChallengeStore, WebAuthnCredentialStore,
+};
 
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
+pub type TotpSecretStoreType = Arc<RwLock<dyn TotpSecretStore + Send + Sync>>;
+pub type RecoveryCodeStoreType = Arc<RwLock<dyn RecoveryCodeStore + Send + Sync>>;
+pub type TrustedDeviceStoreType = Arc<RwLock<dyn TrustedDeviceStore + Send + Sync>>;
+pub type NotificationTargetStoreType = Arc<RwLock<dyn NotificationTargetStore + Send + Sync>>;
+pub type WebAuthnChallengeStoreType = Arc<RwLock<dyn WebAuthnChallengeStore + Send + Sync>>;
+pub type WebAuthnCredentialStoreType = Arc<RwLock<dyn WebAuthnCredentialStore + Send + Sync>>;
+pub type PasswordResetTokenStoreType = Arc<RwLock<dyn PasswordResetTokenStore + Send + Sync>>;
+pub type VerificationEmailStoreType = Arc<RwLock<dyn VerificationEmailStore + Send + Sync>>;
+pub type EmailOutboxStoreType = Arc<RwLock<dyn EmailOutboxStore + Send + Sync>>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type NotificationChannelType = Arc<dyn NotificationChannel + Send + Sync>;
+pub type ServiceClientsType = Arc<ServiceClients>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
-    pub email_client: EmailClientType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub totp_secret_store: TotpSecretStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub trusted_device_store: TrustedDeviceStoreType,
+    pub notification_target_store: NotificationTargetStoreType,
+    pub webauthn_challenge_store: WebAuthnChallengeStoreType,
+    pub webauthn_credential_store: WebAuthnCredentialStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub verification_email_store: VerificationEmailStoreType,
+    pub email_outbox: EmailOutboxStoreType,
+    // None when the deployment can't send SMS or push notifications
+    pub sms_channel: Option<NotificationChannelType>,
+    pub push_channel: Option<NotificationChannelType>,
+    pub service_clients: ServiceClientsType,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
-        email_client: EmailClientType,
+        refresh_token_store: RefreshTokenStoreType,
+        session_store: SessionStoreType,
+        totp_secret_store: TotpSecretStoreType,
+        recovery_code_store: RecoveryCodeStoreType,
+        trusted_device_store: TrustedDeviceStoreType,
+        notification_target_store: NotificationTargetStoreType,
+        webauthn_challenge_store: WebAuthnChallengeStoreType,
+        webauthn_credential_store: WebAuthnCredentialStoreType,
+        password_reset_token_store: PasswordResetTokenStoreType,
+        verification_email_store: VerificationEmailStoreType,
+        email_outbox: EmailOutboxStoreType,
+        sms_channel: Option<NotificationChannelType>,
+        push_channel: Option<NotificationChannelType>,
+        service_clients: ServiceClientsType,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
-            email_client,
+            refresh_token_store,
+            session_store,
+            totp_secret_store,
+            recovery_code_store,
+            trusted_device_store,
+            notification_target_store,
+            webauthn_challenge_store,
+            webauthn_credential_store,
+            password_reset_token_store,
+            verification_email_store,
+            email_outbox,
+            sms_channel,
+            push_channel,
+            service_clients,
         }
     }
 }
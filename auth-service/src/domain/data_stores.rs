@@ -1,8 +1,14 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use mockall::automock;
 use rand::Rng;
+use ring::hmac;
 use uuid::Uuid;
 
-use crate::domain::{Email, Password, User};
+use crate::domain::{
+    Email, NotificationTarget, OutboxEmail, OutboxEmailStatus, Password, PasswordReset,
+    PasswordResetToken, PendingWebAuthnCeremony, RecoveryCode, Session, TotpSecret, TrustedDevice,
+    TwoFAMethod, User, WebAuthnCredential,
+};
 
 #[async_trait::async_trait]
 #[automock]
@@ -11,6 +17,21 @@ pub trait UserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
     async fn delete_user(&mut self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+    // Every JWT carries the generation that was current when it was issued.
+    // Incrementing it invalidates all tokens issued to the user so far.
+    async fn get_token_generation(&self, email: &Email) -> Result<i32, UserStoreError>;
+    async fn increment_token_generation(&mut self, email: &Email) -> Result<i32, UserStoreError>;
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError>;
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -21,10 +42,276 @@ pub enum UserStoreError {
     UnexpectedError,
 }
 
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, session_id: &str) -> Result<Session, SessionStoreError>;
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    async fn refresh_session(&mut self, session_id: &str) -> Result<(), SessionStoreError>;
+    async fn remove_session(&mut self, session_id: &str) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait TrustedDeviceStore {
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError>;
+    async fn get_device(&self, device_id: &str) -> Result<TrustedDevice, TrustedDeviceStoreError>;
+    async fn get_devices(&self, email: &Email) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError>;
+    async fn remove_device(&mut self, device_id: &str) -> Result<(), TrustedDeviceStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TrustedDeviceStoreError {
+    DeviceNotFound,
+    UnexpectedError,
+}
+
+// A TOTP secret stays pending until the user proves their authenticator app
+// produces valid codes for it. Only the active secret is used to verify logins.
+#[async_trait::async_trait]
+pub trait TotpSecretStore {
+    async fn set_pending_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError>;
+    async fn get_pending_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError>;
+    // Replaces the active secret with the pending one, marking `step` as used
+    async fn activate_pending_secret(
+        &mut self,
+        email: &Email,
+        step: u64,
+    ) -> Result<(), TotpSecretStoreError>;
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError>;
+    // Fails with `CodeAlreadyUsed` unless `step` is later than the last used step
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TotpSecretStoreError {
+    SecretNotFound,
+    CodeAlreadyUsed,
+    UnexpectedError,
+}
+
+// Where SMS and push 2FA codes are sent. Like TOTP secrets, a new target stays
+// pending until a code delivered to it has been confirmed.
+#[async_trait::async_trait]
+pub trait NotificationTargetStore {
+    // The pending target belongs to the login attempt its confirmation code was sent with
+    async fn set_pending_target(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+        target: NotificationTarget,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), NotificationTargetStoreError>;
+    async fn get_pending_target(
+        &self,
+        email: &Email,
+    ) -> Result<(TwoFAMethod, NotificationTarget, LoginAttemptId), NotificationTargetStoreError>;
+    // Replaces the active target with the pending one, if it belongs to the login attempt
+    async fn activate_pending_target(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), NotificationTargetStoreError>;
+    async fn get_target(&self, email: &Email)
+        -> Result<NotificationTarget, NotificationTargetStoreError>;
+    // Forgets both the active and the pending target
+    async fn remove_targets(&mut self, email: &Email) -> Result<(), NotificationTargetStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NotificationTargetStoreError {
+    TargetNotFound,
+    UnexpectedError,
+}
+
+// Challenges of WebAuthn ceremonies that were started but not finished yet
+#[async_trait::async_trait]
+pub trait WebAuthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        ceremony_id: &str,
+        pending: PendingWebAuthnCeremony,
+    ) -> Result<(), WebAuthnChallengeStoreError>;
+    // Removes the challenge, so each one can only be answered once
+    async fn take_challenge(
+        &mut self,
+        ceremony_id: &str,
+    ) -> Result<PendingWebAuthnCeremony, WebAuthnChallengeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnChallengeStoreError {
+    ChallengeNotFound,
+    UnexpectedError,
+}
+
+// Password reset links that were emailed but not used yet
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        token: &PasswordResetToken,
+        reset: PasswordReset,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    // Removes the token, so each link can only be used once
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<PasswordReset, PasswordResetTokenStoreError>;
+    // Counts a reset email sent to the address, whether or not it has an account.
+    // Fails if the last one was sent less than `cooldown_secs` ago or `max_emails`
+    // were already sent in the current window.
+    async fn record_reset_email(
+        &mut self,
+        email: &Email,
+        cooldown_secs: u64,
+        max_emails: u32,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordResetTokenStoreError {
+    TokenNotFound,
+    // Seconds left until another email can be sent
+    ResetEmailCooldown(u64),
+    TooManyResetEmails,
+    UnexpectedError,
+}
+
+// Period the limit on password reset emails applies to
+pub const PASSWORD_RESET_EMAIL_WINDOW_SECONDS: u64 = 86400; // 1 day
+
+// Period the limit on resent verification emails applies to
+pub const VERIFICATION_EMAIL_RESEND_WINDOW_SECONDS: u64 = 86400; // 1 day
+
+// Keeps track of the verification emails resent to each address
+#[async_trait::async_trait]
+pub trait VerificationEmailStore {
+    // Counts another resend. Fails with `ResendCooldown` until `cooldown_secs`
+    // passed since the last one, and with `TooManyResends` once `max_resends`
+    // were sent within the window.
+    async fn record_resend(
+        &mut self,
+        email: &Email,
+        cooldown_secs: u64,
+        max_resends: u32,
+    ) -> Result<(), VerificationEmailStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerificationEmailStoreError {
+    // Seconds left until another email can be sent
+    ResendCooldown(u64),
+    TooManyResends,
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait WebAuthnCredentialStore {
+    async fn add_credential(
+        &mut self,
+        credential: WebAuthnCredential,
+    ) -> Result<(), WebAuthnCredentialStoreError>;
+    async fn get_credential(
+        &self,
+        credential_id: &str,
+    ) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError>;
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebAuthnCredential>, WebAuthnCredentialStoreError>;
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &str,
+        sign_count: u32,
+    ) -> Result<(), WebAuthnCredentialStoreError>;
+    async fn remove_credential(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<(), WebAuthnCredentialStoreError>;
+    async fn remove_credentials(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), WebAuthnCredentialStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnCredentialStoreError {
+    CredentialAlreadyExists,
+    CredentialNotFound,
+    UnexpectedError,
+}
+
+// Emails handlers queue up for the outbox worker to deliver. Times are unix
+// timestamps in milliseconds.
+#[async_trait::async_trait]
+pub trait EmailOutboxStore {
+    async fn enqueue(&mut self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError>;
+    // Takes up to `limit` pending emails that are due and counts an attempt for each.
+    // Other workers won't see them before `lease_until`, so an email whose worker
+    // dies halfway through is picked up again after that.
+    async fn claim_due(
+        &mut self,
+        now: i64,
+        lease_until: i64,
+        limit: u32,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError>;
+    async fn mark_sent(&mut self, id: &str, now: i64) -> Result<(), EmailOutboxStoreError>;
+    async fn retry_later(
+        &mut self,
+        id: &str,
+        retry_at: i64,
+        error: &str,
+    ) -> Result<(), EmailOutboxStoreError>;
+    async fn dead_letter(&mut self, id: &str, error: &str) -> Result<(), EmailOutboxStoreError>;
+    async fn get_status(&self, id: &str) -> Result<OutboxEmailStatus, EmailOutboxStoreError>;
+    // Deletes sent and dead-lettered emails queued before `before`, returning how many
+    async fn delete_finished(&mut self, before: i64) -> Result<u64, EmailOutboxStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmailOutboxStoreError {
+    EmailNotFound,
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore {
+    // Replaces all recovery codes of the user
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), RecoveryCodeStoreError>;
+    // Removes the code, so every code can be used only once
+    async fn use_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RecoveryCodeStoreError {
+    CodeNotFound,
+    UnexpectedError,
+}
+
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>;
-    async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
+    // Tokens are banned by their `jti` claim, never by the raw token string
+    async fn add_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError>;
+    async fn contains_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -33,6 +320,33 @@ pub enum BannedTokenStoreError {
     UnexpectedError
 }
 
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn set_current_token(
+        &mut self,
+        family: &str,
+        token_id: &str,
+    ) -> Result<(), RefreshTokenStoreError>;
+    async fn get_current_token(&self, family: &str) -> Result<String, RefreshTokenStoreError>;
+    async fn revoke_family(&mut self, family: &str) -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefreshTokenStoreError {
+    FamilyNotFound,
+    UnexpectedError,
+}
+
+// Number of login attempts a user can have waiting for their 2FA code at the same
+// time, e.g. one per device. Adding another one drops the oldest.
+pub const MAX_PENDING_LOGIN_ATTEMPTS: usize = 5;
+
+// Codes expire after ten minutes, so by the time requests for codes haven't been
+// made for this long, none of the codes they were counted for are pending anymore
+pub const TWO_FA_CODE_REQUEST_WINDOW_SECONDS: u64 = 600;
+
+// Codes are kept per login attempt, so logins from several devices don't replace
+// each other's codes. Stores only keep keyed hashes of the codes.
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
     async fn add_code(
@@ -41,16 +355,90 @@ pub trait TwoFACodeStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
-    async fn get_code(
+    // Records a login attempt answered with a code the user generates themselves,
+    // such as from a TOTP authenticator app. No code is stored, so `consume_code`
+    // always fails for it, but wrong guesses are counted the same way.
+    async fn add_login_attempt(
+        &mut self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
+    async fn contains_code(
         &self,
         email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<bool, TwoFACodeStoreError>;
+    // Checks the code and removes it in one step, so a code can't be used by two
+    // requests at the same time. A wrong code fails with `IncorrectCode` and is kept.
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError>;
+    // Counts a wrong code against the login attempt. Once `max_attempts` wrong codes
+    // were entered the code is removed and `TooManyAttempts` is returned.
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError>;
+    // Swaps in a new code for a login attempt whose email got lost. Wrong guesses
+    // against the old code still count. Fails with `ResendCooldown` until
+    // `cooldown_secs` passed since the last code was sent, and with `TooManyResends`
+    // once `max_resends` new codes were sent.
+    async fn replace_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        cooldown_secs: u64,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError>;
+    // Counts a wrong guess at a factor that doesn't change between login attempts,
+    // such as a TOTP or recovery code, against the user. The count starts over
+    // `lockout_secs` after the first failure. Fails with `TooManyAttempts` once
+    // `max_failures` were counted.
+    async fn record_user_failure(
+        &mut self,
+        email: &Email,
+        max_failures: u32,
+        lockout_secs: u64,
+    ) -> Result<(), TwoFACodeStoreError>;
+    async fn is_user_locked_out(
+        &self,
+        email: &Email,
+        max_failures: u32,
+    ) -> Result<bool, TwoFACodeStoreError>;
+    async fn clear_user_failures(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    // Counts a code sent outside of a login, e.g. to confirm a phone number, against
+    // `key`, which names the user or target the code was sent for. Fails with
+    // `ResendCooldown` until `cooldown_secs` passed since the last counted request,
+    // and with `TooManyResends` once `max_requests` were counted. The count starts
+    // over once no request was counted for `TWO_FA_CODE_REQUEST_WINDOW_SECONDS`.
+    async fn record_code_request(
+        &mut self,
+        key: &str,
+        cooldown_secs: u64,
+        max_requests: u32,
+    ) -> Result<(), TwoFACodeStoreError>;
+    async fn clear_code_requests(&mut self, key: &str) -> Result<(), TwoFACodeStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
 pub enum TwoFACodeStoreError {
     LoginAttemptIdNotFound,
+    IncorrectCode,
+    TooManyAttempts,
+    // Seconds left until a new code can be sent
+    ResendCooldown(u64),
+    TooManyResends,
     UnexpectedError,
 }
 
@@ -89,6 +477,20 @@ impl TwoFACode {
             Err(err) => Err(err.to_string()),
         }
     }
+
+    // There are only a million codes, so a plain hash could be reversed by hashing
+    // all of them. The key keeps stored hashes useless without the secret.
+    pub fn hash(&self, key: &hmac::Key) -> String {
+        URL_SAFE_NO_PAD.encode(hmac::sign(key, self.0.as_bytes()))
+    }
+
+    // Compares in constant time
+    pub fn matches_hash(&self, key: &hmac::Key, hash: &str) -> bool {
+        match URL_SAFE_NO_PAD.decode(hash) {
+            Ok(tag) => hmac::verify(key, self.0.as_bytes(), &tag).is_ok(),
+            Err(_) => false,
+        }
+    }
 }
 
 impl Default for TwoFACode {
@@ -5,7 +5,25 @@ pub trait EmailClient {
     async fn send_email(
         &self,
         recipient: &Email,
-        subject: &str,
-        content: &str,
-    ) -> Result<(), String>;
-}
\ No newline at end of file
+        content: &EmailContent,
+    ) -> Result<(), EmailClientError>;
+}
+
+// An email ready to be sent, with a plain text alternative for clients that don't
+// show HTML
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmailContent {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmailClientError {
+    // The email couldn't be put together, e.g. because of an invalid address
+    InvalidEmail(String),
+    // The provider refused the email, sending it again won't help
+    Rejected(String),
+    // The provider couldn't be reached or kept failing
+    Unavailable(String),
+}
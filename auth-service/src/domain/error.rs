@@ -1,8 +1,36 @@
+#[derive(Debug)]
 pub enum AuthAPIError {
     UserAlreadyExists,
     InvalidCredentials,
     IncorrectCredentials,
     MissingToken,
     InvalidToken,
+    SessionNotFound,
+    TrustedDeviceNotFound,
+    InvalidClientCredentials,
+    TotpEnrollmentNotFound,
+    NotificationTargetNotFound,
+    PasskeyNotRegistered,
+    PasskeyNotFound,
+    PasskeyAlreadyRegistered,
+    TwoFANotEnabled,
+    TwoFAAlreadyEnabled,
+    TooMany2FAAttempts,
+    TwoFALockedOut,
+    TwoFACodeNotResendable,
+    // SMS or push 2FA without a channel configured to send the codes
+    TwoFAMethodUnavailable,
+    // Seconds the client has to wait before asking for another 2FA code
+    ResendCooldown(u64),
+    TooMany2FAResends,
+    TooMany2FACodeRequests,
+    // Seconds the client has to wait before asking for another verification email
+    VerificationEmailCooldown(u64),
+    TooManyVerificationEmails,
+    PasswordResetEmailCooldown(u64),
+    TooManyPasswordResetEmails,
+    EmailNotVerified,
+    InvalidVerificationLink,
+    InvalidResetLink,
     UnexpectedError,
 }
\ No newline at end of file
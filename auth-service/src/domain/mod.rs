@@ -1,13 +1,31 @@
 pub mod user;
+pub mod session;
+pub mod trusted_device;
 pub mod error;
 pub mod data_stores;
 pub mod email;
 pub mod password;
 pub mod email_client;
+pub mod outbox_email;
+pub mod notification_channel;
+pub mod service_clients;
+pub mod totp;
+pub mod recovery_code;
+pub mod webauthn;
+pub mod password_reset;
 
 pub use user::*;
+pub use session::*;
+pub use trusted_device::*;
 pub use error::*;
 pub use data_stores::*;
 pub use email::*;
 pub use password::*;
-pub use email_client::*;
\ No newline at end of file
+pub use email_client::*;
+pub use outbox_email::*;
+pub use notification_channel::*;
+pub use service_clients::*;
+pub use totp::*;
+pub use recovery_code::*;
+pub use webauthn::*;
+pub use password_reset::*;
\ No newline at end of file
@@ -0,0 +1,90 @@
+use crate::domain::TwoFAMethod;
+
+// Delivers 2FA codes through something other than email, like an SMS gateway or
+// a push notification provider
+#[async_trait::async_trait]
+pub trait NotificationChannel {
+    async fn send_notification(
+        &self,
+        recipient: &NotificationTarget,
+        content: &str,
+    ) -> Result<(), String>;
+}
+
+// Where a notification channel delivers to: a phone number for SMS, or the device
+// token the push provider handed out when the app registered
+#[derive(Clone, Debug, PartialEq)]
+pub struct NotificationTarget(String);
+
+const MAX_PUSH_TOKEN_LENGTH: usize = 512;
+
+impl NotificationTarget {
+    pub fn parse(two_fa_method: TwoFAMethod, target: String) -> Result<Self, String> {
+        match two_fa_method {
+            TwoFAMethod::Sms => {
+                // Phone numbers in E.164 format, e.g. +15551234567
+                let valid = target.len() >= 8
+                    && target.len() <= 16
+                    && target.starts_with('+')
+                    && !target[1..].starts_with('0')
+                    && target[1..].chars().all(|c| c.is_ascii_digit());
+                match valid {
+                    true => Ok(Self(target)),
+                    false => Err(format!("{} is not a valid phone number", target)),
+                }
+            }
+            TwoFAMethod::Push => {
+                let valid = !target.is_empty()
+                    && target.len() <= MAX_PUSH_TOKEN_LENGTH
+                    && target.chars().all(|c| c.is_ascii_graphic());
+                match valid {
+                    true => Ok(Self(target)),
+                    false => Err("Invalid push device token".to_owned()),
+                }
+            }
+            _ => Err(format!(
+                "2FA method {} has no notification target",
+                two_fa_method.as_ref()
+            )),
+        }
+    }
+}
+
+impl AsRef<str> for NotificationTarget {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_phone_number() {
+        assert!(NotificationTarget::parse(TwoFAMethod::Sms, "+15551234567".to_owned()).is_ok());
+
+        for number in ["15551234567", "+0551234567", "+1555-123-4567", "+1", ""] {
+            assert!(
+                NotificationTarget::parse(TwoFAMethod::Sms, number.to_owned()).is_err(),
+                "{} should be rejected",
+                number
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_push_token() {
+        assert!(NotificationTarget::parse(TwoFAMethod::Push, "device-token_123".to_owned()).is_ok());
+        assert!(NotificationTarget::parse(TwoFAMethod::Push, "".to_owned()).is_err());
+        assert!(NotificationTarget::parse(TwoFAMethod::Push, "a token".to_owned()).is_err());
+        assert!(NotificationTarget::parse(TwoFAMethod::Push, "a".repeat(513)).is_err());
+    }
+
+    #[test]
+    fn test_parse_without_notification_channel() {
+        for method in [TwoFAMethod::None, TwoFAMethod::Email, TwoFAMethod::Totp] {
+            assert!(NotificationTarget::parse(method, "+15551234567".to_owned()).is_err());
+        }
+    }
+}
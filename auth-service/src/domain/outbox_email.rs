@@ -0,0 +1,54 @@
+use uuid::Uuid;
+
+use super::{Email, EmailContent};
+
+// An email waiting in the outbox to be delivered in the background
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboxEmail {
+    // Queuing an email with an id that is already in the outbox does nothing
+    pub id: String,
+    pub recipient: Email,
+    pub content: EmailContent,
+    // Delivery attempts so far, including the one in progress
+    pub attempts: u32,
+}
+
+impl OutboxEmail {
+    pub fn new(recipient: Email, content: EmailContent) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            recipient,
+            content,
+            attempts: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutboxEmailStatus {
+    Pending,
+    Sent,
+    // Given up on, kept so failed deliveries can be looked into
+    Dead,
+}
+
+impl OutboxEmailStatus {
+    pub fn parse(status: &str) -> Result<Self, String> {
+        match status {
+            "pending" => Ok(Self::Pending),
+            "sent" => Ok(Self::Sent),
+            "dead" => Ok(Self::Dead),
+            _ => Err(format!("Unknown outbox email status: {}", status)),
+        }
+    }
+}
+
+impl AsRef<str> for OutboxEmailStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Pending => "pending",
+            Self::Sent => "sent",
+            Self::Dead => "dead",
+        }
+    }
+}
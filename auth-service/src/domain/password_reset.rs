@@ -0,0 +1,78 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use ring::digest;
+
+use super::Email;
+
+// This value determines how long a password reset link can be used
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 900; // 15 minutes
+
+const PASSWORD_RESET_TOKEN_LENGTH: usize = 32;
+
+// Secret in the password reset link, base64url encoded to fit in a URL
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordResetToken(String);
+
+impl PasswordResetToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        match URL_SAFE_NO_PAD.decode(&token) {
+            Ok(bytes) if bytes.len() == PASSWORD_RESET_TOKEN_LENGTH => Ok(Self(token)),
+            _ => Err("Invalid password reset token".to_owned()),
+        }
+    }
+
+    // Only the hash is stored, so the tokens can't be read out of the store
+    pub fn hash(&self) -> String {
+        URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, self.0.as_bytes()))
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        let mut bytes = [0u8; PASSWORD_RESET_TOKEN_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for PasswordResetToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Whose password a reset link is for
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordReset {
+    pub email: Email,
+    // The user's token generation when the link was sent. Resetting the password
+    // or logging out everywhere bumps it, which invalidates every older link.
+    pub token_generation: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let token = PasswordResetToken::default();
+        assert_eq!(
+            PasswordResetToken::parse(token.as_ref().to_owned()),
+            Ok(token.clone())
+        );
+
+        assert!(PasswordResetToken::parse("".to_owned()).is_err());
+        assert!(PasswordResetToken::parse("invalid".to_owned()).is_err());
+        assert!(PasswordResetToken::parse(format!("{}x", token.as_ref())).is_err());
+        assert!(PasswordResetToken::parse(URL_SAFE_NO_PAD.encode([0u8; 16])).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let token = PasswordResetToken::default();
+        assert_eq!(token.hash(), token.clone().hash());
+        assert_ne!(token.hash(), token.as_ref());
+        assert_ne!(token.hash(), PasswordResetToken::default().hash());
+    }
+}
@@ -0,0 +1,111 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::Rng;
+use ring::digest;
+
+// Number of recovery codes a user gets at a time
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// Lowercase letters and digits, leaving out the easily confused 0, o, 1, l and i
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+const RECOVERY_CODE_GROUPS: usize = 3;
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+
+// One-time code that stands in for the second factor when a user lost access to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    pub fn parse(code: String) -> Result<Self, String> {
+        let code = code.trim().to_ascii_lowercase();
+        let groups: Vec<&str> = code.split('-').collect();
+
+        let valid = groups.len() == RECOVERY_CODE_GROUPS
+            && groups.iter().all(|group| {
+                group.len() == RECOVERY_CODE_GROUP_LENGTH
+                    && group.bytes().all(|c| RECOVERY_CODE_ALPHABET.contains(&c))
+            });
+
+        match valid {
+            true => Ok(Self(code)),
+            false => Err("Invalid recovery code".to_owned()),
+        }
+    }
+
+    pub fn generate_set() -> Vec<Self> {
+        (0..RECOVERY_CODE_COUNT).map(|_| Self::default()).collect()
+    }
+
+    // Codes carry about 74 bits of randomness, so unlike passwords they don't
+    // need a slow hash to be safe at rest
+    pub fn hash(&self) -> String {
+        URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, self.0.as_bytes()))
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let groups: Vec<String> = (0..RECOVERY_CODE_GROUPS)
+            .map(|_| {
+                (0..RECOVERY_CODE_GROUP_LENGTH)
+                    .map(|_| {
+                        RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())]
+                            as char
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self(groups.join("-"))
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert!(RecoveryCode::parse("abcde-fghjk-mnpqr".to_owned()).is_ok());
+        assert_eq!(
+            RecoveryCode::parse(" ABCDE-FGHJK-MNPQR ".to_owned()),
+            Ok(RecoveryCode("abcde-fghjk-mnpqr".to_owned()))
+        );
+
+        assert!(RecoveryCode::parse("".to_owned()).is_err());
+        assert!(RecoveryCode::parse("123456".to_owned()).is_err());
+        assert!(RecoveryCode::parse("abcde-fghjk".to_owned()).is_err());
+        assert!(RecoveryCode::parse("abcde-fghjk-mnpq".to_owned()).is_err());
+        assert!(RecoveryCode::parse("abcde-fghjk-mnpq0".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_generate_set() {
+        let codes = RecoveryCode::generate_set();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+
+        for code in &codes {
+            assert_eq!(RecoveryCode::parse(code.as_ref().to_owned()), Ok(code.clone()));
+        }
+
+        let mut hashes: Vec<String> = codes.iter().map(RecoveryCode::hash).collect();
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), RECOVERY_CODE_COUNT);
+    }
+
+    #[test]
+    fn test_hash_ignores_formatting() {
+        let code = RecoveryCode::parse("abcde-fghjk-mnpqr".to_owned()).unwrap();
+        let same_code = RecoveryCode::parse("ABCDE-FGHJK-MNPQR".to_owned()).unwrap();
+
+        assert_eq!(code.hash(), same_code.hash());
+        assert_ne!(code.hash(), RecoveryCode::default().hash());
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use subtle::ConstantTimeEq;
+
+// Credentials a backend service authenticates itself with
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+// Backend services registered to call service-to-service endpoints such as token introspection
+#[derive(Debug, Clone, Default)]
+pub struct ServiceClients(HashMap<String, String>);
+
+impl ServiceClients {
+    // Parses a comma separated list of `client_id:client_secret` pairs
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut clients = HashMap::new();
+
+        for client in s.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+            match client.split_once(':') {
+                Some((id, secret)) if !id.is_empty() && !secret.is_empty() => {
+                    clients.insert(id.to_owned(), secret.to_owned());
+                }
+                _ => return Err(format!("{} is not a valid client_id:client_secret pair.", client)),
+            }
+        }
+
+        Ok(Self(clients))
+    }
+
+    pub fn authenticate(&self, credentials: &ClientCredentials) -> bool {
+        match self.0.get(&credentials.client_id) {
+            Some(secret) => secret
+                .as_bytes()
+                .ct_eq(credentials.client_secret.as_bytes())
+                .into(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(client_id: &str, client_secret: &str) -> ClientCredentials {
+        ClientCredentials {
+            client_id: client_id.to_owned(),
+            client_secret: client_secret.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let clients = ServiceClients::parse("app-service:secret, other-service:other").unwrap();
+        assert!(clients.authenticate(&credentials("app-service", "secret")));
+        assert!(clients.authenticate(&credentials("other-service", "other")));
+
+        let clients = ServiceClients::parse("").unwrap();
+        assert!(!clients.authenticate(&credentials("app-service", "secret")));
+    }
+
+    #[test]
+    fn test_parse_invalid_pairs() {
+        for s in ["app-service", "app-service:", ":secret"] {
+            assert!(ServiceClients::parse(s).is_err(), "Failed for input: {}", s);
+        }
+    }
+
+    #[test]
+    fn test_authenticate_with_wrong_credentials() {
+        let clients = ServiceClients::parse("app-service:secret").unwrap();
+        assert!(!clients.authenticate(&credentials("app-service", "wrong")));
+        assert!(!clients.authenticate(&credentials("app-service", "secret2")));
+        assert!(!clients.authenticate(&credentials("unknown", "secret")));
+    }
+}
@@ -0,0 +1,25 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::Email;
+
+// A logged-in device or browser. The session id is embedded in every JWT issued
+// for the session and doubles as the id of its refresh token family.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub email: Email,
+    pub created_at: i64,
+    pub user_agent: Option<String>,
+}
+
+impl Session {
+    pub fn new(email: Email, user_agent: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            email,
+            created_at: Utc::now().timestamp(),
+            user_agent,
+        }
+    }
+}
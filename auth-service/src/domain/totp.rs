@@ -0,0 +1,212 @@
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::Rng;
+use ring::hmac;
+use subtle::ConstantTimeEq;
+
+use crate::domain::{Email, TwoFACode};
+
+const TOTP_STEP_SECONDS: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+// Codes of the neighbouring time steps are accepted to allow for clock drift
+const TOTP_ALLOWED_DRIFT_STEPS: u64 = 1;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// Shared secret of an authenticator app (RFC 6238), kept base32 encoded as apps expect it
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpSecret(String);
+
+impl TotpSecret {
+    pub fn parse(secret: String) -> Result<Self, String> {
+        let secret = secret.to_ascii_uppercase();
+        match base32_decode(&secret) {
+            Some(key) if !key.is_empty() => Ok(Self(secret)),
+            _ => Err("TOTP secret must be base32 encoded".to_owned()),
+        }
+    }
+
+    // URI rendered as a QR code to set up authenticator apps
+    pub fn otpauth_uri(&self, issuer: &str, email: &Email) -> String {
+        let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC);
+        let account = utf8_percent_encode(email.as_ref(), NON_ALPHANUMERIC);
+
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            issuer, account, self.0, issuer, TOTP_DIGITS, TOTP_STEP_SECONDS
+        )
+    }
+
+    pub fn generate_code(&self, unix_time: u64) -> TwoFACode {
+        TwoFACode::parse(self.code_for_step(unix_time / TOTP_STEP_SECONDS))
+            .expect("TOTP codes are numeric")
+    }
+
+    // Returns the time step the code belongs to, so callers can refuse to accept it twice
+    pub fn verify(&self, code: &TwoFACode, unix_time: u64) -> Option<u64> {
+        let current_step = unix_time / TOTP_STEP_SECONDS;
+
+        (current_step.saturating_sub(TOTP_ALLOWED_DRIFT_STEPS)
+            ..=current_step + TOTP_ALLOWED_DRIFT_STEPS)
+            .find(|&step| {
+                self.code_for_step(step)
+                    .as_bytes()
+                    .ct_eq(code.as_ref().as_bytes())
+                    .into()
+            })
+    }
+
+    // HOTP (RFC 4226) with the time step as counter
+    fn code_for_step(&self, step: u64) -> String {
+        let key = base32_decode(&self.0).expect("TOTP secret is valid base32");
+        let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &key);
+        let digest = hmac::sign(&key, &step.to_be_bytes());
+        let digest = digest.as_ref();
+
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+
+        format!(
+            "{:0width$}",
+            binary % 10u32.pow(TOTP_DIGITS),
+            width = TOTP_DIGITS as usize
+        )
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        // 160 bits, the key length recommended for HMAC-SHA1
+        let key: [u8; 20] = rand::thread_rng().gen();
+        Self(base32_encode(&key))
+    }
+}
+
+impl AsRef<str> for TotpSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in s.trim_end_matches('=').bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The SHA1 secret "12345678901234567890" of the RFC 6238 test vectors
+    fn rfc_secret() -> TotpSecret {
+        TotpSecret::parse(base32_encode(b"12345678901234567890")).unwrap()
+    }
+
+    fn code(code: &str) -> TwoFACode {
+        TwoFACode::parse(code.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn test_generate_code_matches_rfc_6238_test_vectors() {
+        let secret = rfc_secret();
+
+        // The RFC lists 8 digit codes; 6 digit codes are their last 6 digits
+        assert_eq!(secret.generate_code(59), code("287082"));
+        assert_eq!(secret.generate_code(1111111109), code("081804"));
+        assert_eq!(secret.generate_code(1234567890), code("005924"));
+        assert_eq!(secret.generate_code(2000000000), code("279037"));
+    }
+
+    #[test]
+    fn test_verify_accepts_codes_of_adjacent_steps() {
+        let secret = rfc_secret();
+        let now = 1111111109;
+        let step = now / TOTP_STEP_SECONDS;
+
+        assert_eq!(secret.verify(&secret.generate_code(now), now), Some(step));
+        assert_eq!(
+            secret.verify(&secret.generate_code(now - TOTP_STEP_SECONDS), now),
+            Some(step - 1)
+        );
+        assert_eq!(
+            secret.verify(&secret.generate_code(now + TOTP_STEP_SECONDS), now),
+            Some(step + 1)
+        );
+        assert_eq!(
+            secret.verify(&secret.generate_code(now - 2 * TOTP_STEP_SECONDS), now),
+            None
+        );
+        assert_eq!(
+            secret.verify(&secret.generate_code(now + 2 * TOTP_STEP_SECONDS), now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_secrets() {
+        assert!(TotpSecret::parse("".to_owned()).is_err());
+        assert!(TotpSecret::parse("not base32!".to_owned()).is_err());
+        assert_eq!(
+            TotpSecret::parse("gezdgnbv".to_owned()),
+            Ok(TotpSecret("GEZDGNBV".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI======"), Some(b"foobar".to_vec()));
+
+        let secret = TotpSecret::default();
+        assert_eq!(base32_decode(secret.as_ref()).map(|key| key.len()), Some(20));
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let secret = TotpSecret::parse("GEZDGNBV".to_owned()).unwrap();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_owned()).unwrap();
+
+        assert_eq!(
+            secret.otpauth_uri("auth-service", &email),
+            "otpauth://totp/auth%2Dservice:dev%2Eted%2Ekim%40gmail%2Ecom?secret=GEZDGNBV&issuer=auth%2Dservice&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::Email;
+
+// A browser the user chose to remember after completing 2FA. Logins from it skip
+// the second factor until the device is revoked or its cookie expires.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrustedDevice {
+    pub id: String,
+    pub email: Email,
+    pub created_at: i64,
+    pub user_agent: Option<String>,
+}
+
+impl TrustedDevice {
+    pub fn new(email: Email, user_agent: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            email,
+            created_at: Utc::now().timestamp(),
+            user_agent,
+        }
+    }
+}
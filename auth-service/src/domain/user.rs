@@ -4,15 +4,60 @@ use crate::domain::{Email, Password};
 pub struct User {
     pub email: Email,
     pub password: Password,
-    pub require_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    // Unverified users can't log in until they follow the link emailed to them
+    pub email_verified: bool,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, require_2fa: bool) -> Self {
+    pub fn new(email: Email, password: Password, two_fa_method: TwoFAMethod) -> Self {
         Self {
             email,
             password,
-            require_2fa,
+            two_fa_method,
+            email_verified: false,
+        }
+    }
+}
+
+// The second factor a user has to provide after their password
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TwoFAMethod {
+    #[default]
+    None,
+    Email,
+    // Codes are sent to the user's notification target instead of their inbox
+    Sms,
+    Push,
+    Totp,
+}
+
+impl TwoFAMethod {
+    // Whether codes are sent to the user, as opposed to generated by their authenticator app
+    pub fn sends_codes(&self) -> bool {
+        matches!(self, Self::Email | Self::Sms | Self::Push)
+    }
+
+    pub fn parse(method: &str) -> Result<Self, String> {
+        match method {
+            "none" => Ok(Self::None),
+            "email" => Ok(Self::Email),
+            "sms" => Ok(Self::Sms),
+            "push" => Ok(Self::Push),
+            "totp" => Ok(Self::Totp),
+            _ => Err(format!("Unknown 2FA method: {}", method)),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::None => "none",
+            Self::Email => "email",
+            Self::Sms => "sms",
+            Self::Push => "push",
+            Self::Totp => "totp",
         }
     }
 }
@@ -0,0 +1,588 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::Value;
+use rand::RngCore;
+use ring::{digest, signature};
+use serde::Deserialize;
+
+use crate::domain::{Email, LoginAttemptId};
+
+// How long a browser has to complete a ceremony after it was started
+pub const WEBAUTHN_CHALLENGE_TTL_SECONDS: u64 = 300;
+
+// COSE algorithm identifier of ECDSA with P-256 and SHA-256, the only kind of
+// passkey accepted. Every platform authenticator supports it.
+pub const COSE_ALGORITHM_ES256: i64 = -7;
+
+const CHALLENGE_LENGTH: usize = 32;
+const USER_HANDLE_LENGTH: usize = 32;
+
+// Authenticator data flags
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// Random value the authenticator signs, base64url encoded like browsers echo it back
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebAuthnChallenge(String);
+
+impl WebAuthnChallenge {
+    pub fn parse(challenge: String) -> Result<Self, String> {
+        match URL_SAFE_NO_PAD.decode(&challenge) {
+            Ok(bytes) if bytes.len() == CHALLENGE_LENGTH => Ok(Self(challenge)),
+            _ => Err("Invalid WebAuthn challenge".to_owned()),
+        }
+    }
+}
+
+impl Default for WebAuthnChallenge {
+    fn default() -> Self {
+        let mut bytes = [0u8; CHALLENGE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for WebAuthnChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Random id authenticators store with a user's passkeys in place of the email
+// address. Every passkey of a user shares the same one.
+pub fn generate_user_handle() -> String {
+    let mut bytes = [0u8; USER_HANDLE_LENGTH];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// What a challenge was issued for
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebAuthnCeremony {
+    Registration {
+        email: Email,
+        user_handle: String,
+    },
+    // Second step of a login that started with the password
+    SecondFactor {
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+    },
+    // Login with a passkey alone, the browser picks which one
+    Passwordless,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingWebAuthnCeremony {
+    pub challenge: WebAuthnChallenge,
+    pub ceremony: WebAuthnCeremony,
+}
+
+// A passkey registered by a user. The public key is an uncompressed P-256 point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebAuthnCredential {
+    // base64url encoded credential id chosen by the authenticator
+    pub id: String,
+    pub email: Email,
+    // base64url encoded user handle the authenticator returns on passwordless logins
+    pub user_handle: String,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+    pub created_at: i64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnError {
+    MalformedResponse,
+    CeremonyMismatch,
+    ChallengeMismatch,
+    OriginMismatch,
+    RelyingPartyMismatch,
+    UserNotPresent,
+    UserNotVerified,
+    UnsupportedKey,
+    InvalidSignature,
+    // The authenticator's counter went backwards, so it may have been cloned
+    SignCountNotIncreased,
+}
+
+// The relying party ceremonies are verified against
+pub struct RelyingParty<'a> {
+    pub id: &'a str,
+    pub origin: &'a str,
+}
+
+// Credential created by a successful registration ceremony
+#[derive(Debug, PartialEq)]
+pub struct RegisteredKey {
+    pub credential_id: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+// Checks the response to `navigator.credentials.create()`. Attestation isn't
+// requested, so which authenticator made the key is not verified.
+pub fn verify_registration(
+    relying_party: &RelyingParty<'_>,
+    challenge: &WebAuthnChallenge,
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+) -> Result<RegisteredKey, WebAuthnError> {
+    verify_client_data(
+        relying_party,
+        challenge,
+        "webauthn.create",
+        client_data_json,
+    )?;
+
+    let attestation: Value =
+        ciborium::from_reader(attestation_object).map_err(|_| WebAuthnError::MalformedResponse)?;
+    let auth_data = map_get(&attestation, &Value::Text("authData".to_owned()))
+        .and_then(Value::as_bytes)
+        .ok_or(WebAuthnError::MalformedResponse)?;
+
+    let auth_data = AuthenticatorData::parse(auth_data)?;
+    auth_data.verify(relying_party, false)?;
+
+    let (credential_id, public_key) = auth_data
+        .attested_credential
+        .ok_or(WebAuthnError::MalformedResponse)?;
+
+    Ok(RegisteredKey {
+        credential_id,
+        public_key,
+        sign_count: auth_data.sign_count,
+    })
+}
+
+// Checks the response to `navigator.credentials.get()` and returns the
+// authenticator's new signature counter
+pub fn verify_assertion(
+    relying_party: &RelyingParty<'_>,
+    challenge: &WebAuthnChallenge,
+    credential: &WebAuthnCredential,
+    require_user_verification: bool,
+    client_data_json: &[u8],
+    authenticator_data: &[u8],
+    signature: &[u8],
+) -> Result<u32, WebAuthnError> {
+    verify_client_data(relying_party, challenge, "webauthn.get", client_data_json)?;
+
+    let auth_data = AuthenticatorData::parse(authenticator_data)?;
+    auth_data.verify(relying_party, require_user_verification)?;
+
+    // The authenticator signs its data followed by the hash of the client data
+    let mut signed = authenticator_data.to_vec();
+    signed.extend_from_slice(digest::digest(&digest::SHA256, client_data_json).as_ref());
+
+    signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, &credential.public_key)
+        .verify(&signed, signature)
+        .map_err(|_| WebAuthnError::InvalidSignature)?;
+
+    // Authenticators that don't count signatures always report 0
+    if (auth_data.sign_count != 0 || credential.sign_count != 0)
+        && auth_data.sign_count <= credential.sign_count
+    {
+        return Err(WebAuthnError::SignCountNotIncreased);
+    }
+
+    Ok(auth_data.sign_count)
+}
+
+#[derive(Deserialize)]
+struct CollectedClientData {
+    #[serde(rename = "type")]
+    ceremony: String,
+    challenge: String,
+    origin: String,
+    #[serde(default, rename = "crossOrigin")]
+    cross_origin: bool,
+}
+
+fn verify_client_data(
+    relying_party: &RelyingParty<'_>,
+    challenge: &WebAuthnChallenge,
+    ceremony: &str,
+    client_data_json: &[u8],
+) -> Result<(), WebAuthnError> {
+    let client_data: CollectedClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| WebAuthnError::MalformedResponse)?;
+
+    if client_data.ceremony != ceremony {
+        return Err(WebAuthnError::CeremonyMismatch);
+    }
+    if client_data.challenge != challenge.as_ref() {
+        return Err(WebAuthnError::ChallengeMismatch);
+    }
+    if client_data.origin != relying_party.origin || client_data.cross_origin {
+        return Err(WebAuthnError::OriginMismatch);
+    }
+
+    Ok(())
+}
+
+struct AuthenticatorData {
+    rp_id_hash: Vec<u8>,
+    flags: u8,
+    sign_count: u32,
+    // Credential id and public key, only present when a credential was created
+    attested_credential: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl AuthenticatorData {
+    // rpIdHash (32) | flags (1) | signCount (4) | attested credential data | extensions
+    fn parse(data: &[u8]) -> Result<Self, WebAuthnError> {
+        if data.len() < 37 {
+            return Err(WebAuthnError::MalformedResponse);
+        }
+
+        let flags = data[32];
+        let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+        let attested_credential = match flags & FLAG_ATTESTED_CREDENTIAL_DATA {
+            0 => None,
+            // aaguid (16) | credentialIdLength (2) | credentialId | credentialPublicKey
+            _ => {
+                let rest = data
+                    .get(37 + 16..)
+                    .ok_or(WebAuthnError::MalformedResponse)?;
+                if rest.len() < 2 {
+                    return Err(WebAuthnError::MalformedResponse);
+                }
+                let id_length = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+                let credential_id = rest
+                    .get(2..2 + id_length)
+                    .ok_or(WebAuthnError::MalformedResponse)?
+                    .to_vec();
+
+                let mut cose_key = &rest[2 + id_length..];
+                let cose_key: Value = ciborium::from_reader(&mut cose_key)
+                    .map_err(|_| WebAuthnError::MalformedResponse)?;
+
+                Some((credential_id, parse_cose_key(&cose_key)?))
+            }
+        };
+
+        Ok(Self {
+            rp_id_hash: data[..32].to_vec(),
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+
+    fn verify(
+        &self,
+        relying_party: &RelyingParty<'_>,
+        require_user_verification: bool,
+    ) -> Result<(), WebAuthnError> {
+        let rp_id_hash = digest::digest(&digest::SHA256, relying_party.id.as_bytes());
+        if self.rp_id_hash != rp_id_hash.as_ref() {
+            return Err(WebAuthnError::RelyingPartyMismatch);
+        }
+        if self.flags & FLAG_USER_PRESENT == 0 {
+            return Err(WebAuthnError::UserNotPresent);
+        }
+        if require_user_verification && self.flags & FLAG_USER_VERIFIED == 0 {
+            return Err(WebAuthnError::UserNotVerified);
+        }
+
+        Ok(())
+    }
+}
+
+// Converts an EC2 P-256 COSE key (RFC 9053) to an uncompressed point
+fn parse_cose_key(key: &Value) -> Result<Vec<u8>, WebAuthnError> {
+    let int = |label: i64| map_get(key, &Value::Integer(label.into()));
+    let is = |label: i64, expected: i64| {
+        int(label)
+            .and_then(Value::as_integer)
+            .is_some_and(|value| value == expected.into())
+    };
+
+    // Key type EC2, algorithm ES256, curve P-256
+    if !(is(1, 2) && is(3, COSE_ALGORITHM_ES256) && is(-1, 1)) {
+        return Err(WebAuthnError::UnsupportedKey);
+    }
+
+    let coordinate = |label: i64| {
+        int(label)
+            .and_then(Value::as_bytes)
+            .filter(|bytes| bytes.len() == 32)
+            .ok_or(WebAuthnError::UnsupportedKey)
+    };
+
+    let mut point = vec![0x04];
+    point.extend_from_slice(coordinate(-2)?);
+    point.extend_from_slice(coordinate(-3)?);
+    Ok(point)
+}
+
+fn map_get<'a>(map: &'a Value, key: &Value) -> Option<&'a Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+    };
+
+    use super::*;
+
+    const RELYING_PARTY: RelyingParty<'static> = RelyingParty {
+        id: "localhost",
+        origin: "http://localhost:3000",
+    };
+
+    fn client_data(ceremony: &str, challenge: &WebAuthnChallenge, origin: &str) -> Vec<u8> {
+        serde_json::json!({
+            "type": ceremony,
+            "challenge": challenge.as_ref(),
+            "origin": origin,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn authenticator_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = digest::digest(&digest::SHA256, rp_id.as_bytes())
+            .as_ref()
+            .to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    fn cose_key(public_key: &[u8]) -> Value {
+        Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (
+                Value::Integer(3.into()),
+                Value::Integer(COSE_ALGORITHM_ES256.into()),
+            ),
+            (Value::Integer((-1).into()), Value::Integer(1.into())),
+            (
+                Value::Integer((-2).into()),
+                Value::Bytes(public_key[1..33].to_vec()),
+            ),
+            (
+                Value::Integer((-3).into()),
+                Value::Bytes(public_key[33..].to_vec()),
+            ),
+        ])
+    }
+
+    fn attestation_object(auth_data: Vec<u8>) -> Vec<u8> {
+        let object = Value::Map(vec![
+            (
+                Value::Text("fmt".to_owned()),
+                Value::Text("none".to_owned()),
+            ),
+            (Value::Text("attStmt".to_owned()), Value::Map(vec![])),
+            (Value::Text("authData".to_owned()), Value::Bytes(auth_data)),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&object, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn key_pair() -> EcdsaKeyPair {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap()
+    }
+
+    fn register(key_pair: &EcdsaKeyPair, challenge: &WebAuthnChallenge) -> (Vec<u8>, Vec<u8>) {
+        let mut auth_data = authenticator_data(
+            "localhost",
+            FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL_DATA,
+            0,
+        );
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&4u16.to_be_bytes());
+        auth_data.extend_from_slice(b"cred");
+        ciborium::into_writer(&cose_key(key_pair.public_key().as_ref()), &mut auth_data).unwrap();
+
+        (
+            client_data("webauthn.create", challenge, RELYING_PARTY.origin),
+            attestation_object(auth_data),
+        )
+    }
+
+    fn credential(key_pair: &EcdsaKeyPair, sign_count: u32) -> WebAuthnCredential {
+        WebAuthnCredential {
+            id: URL_SAFE_NO_PAD.encode(b"cred"),
+            email: Email::parse("test@example.com".to_owned()).unwrap(),
+            user_handle: generate_user_handle(),
+            public_key: key_pair.public_key().as_ref().to_vec(),
+            sign_count,
+            created_at: 0,
+        }
+    }
+
+    fn sign(key_pair: &EcdsaKeyPair, auth_data: &[u8], client_data: &[u8]) -> Vec<u8> {
+        let mut signed = auth_data.to_vec();
+        signed.extend_from_slice(digest::digest(&digest::SHA256, client_data).as_ref());
+        key_pair
+            .sign(&SystemRandom::new(), &signed)
+            .unwrap()
+            .as_ref()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let challenge = WebAuthnChallenge::default();
+        assert_eq!(
+            WebAuthnChallenge::parse(challenge.as_ref().to_owned()),
+            Ok(challenge)
+        );
+        assert!(WebAuthnChallenge::parse("too-short".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_verify_registration() {
+        let key_pair = key_pair();
+        let challenge = WebAuthnChallenge::default();
+        let (client_data, attestation_object) = register(&key_pair, &challenge);
+
+        let result = verify_registration(
+            &RELYING_PARTY,
+            &challenge,
+            &client_data,
+            &attestation_object,
+        );
+        assert_eq!(
+            result,
+            Ok(RegisteredKey {
+                credential_id: b"cred".to_vec(),
+                public_key: key_pair.public_key().as_ref().to_vec(),
+                sign_count: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_registration_with_wrong_challenge_or_relying_party() {
+        let key_pair = key_pair();
+        let challenge = WebAuthnChallenge::default();
+        let (client_data, attestation_object) = register(&key_pair, &challenge);
+
+        let result = verify_registration(
+            &RELYING_PARTY,
+            &WebAuthnChallenge::default(),
+            &client_data,
+            &attestation_object,
+        );
+        assert_eq!(result, Err(WebAuthnError::ChallengeMismatch));
+
+        let other_origin = RelyingParty {
+            id: "localhost",
+            origin: "https://evil.example.com",
+        };
+        let result =
+            verify_registration(&other_origin, &challenge, &client_data, &attestation_object);
+        assert_eq!(result, Err(WebAuthnError::OriginMismatch));
+
+        let other_rp_id = RelyingParty {
+            id: "example.com",
+            origin: RELYING_PARTY.origin,
+        };
+        let result =
+            verify_registration(&other_rp_id, &challenge, &client_data, &attestation_object);
+        assert_eq!(result, Err(WebAuthnError::RelyingPartyMismatch));
+    }
+
+    #[test]
+    fn test_verify_assertion() {
+        let key_pair = key_pair();
+        let challenge = WebAuthnChallenge::default();
+        let client_data = client_data("webauthn.get", &challenge, RELYING_PARTY.origin);
+        let auth_data = authenticator_data("localhost", FLAG_USER_PRESENT, 5);
+        let signature = sign(&key_pair, &auth_data, &client_data);
+
+        let result = verify_assertion(
+            &RELYING_PARTY,
+            &challenge,
+            &credential(&key_pair, 4),
+            false,
+            &client_data,
+            &auth_data,
+            &signature,
+        );
+        assert_eq!(result, Ok(5));
+
+        // The same counter value again suggests a cloned authenticator
+        let result = verify_assertion(
+            &RELYING_PARTY,
+            &challenge,
+            &credential(&key_pair, 5),
+            false,
+            &client_data,
+            &auth_data,
+            &signature,
+        );
+        assert_eq!(result, Err(WebAuthnError::SignCountNotIncreased));
+
+        // Passwordless logins need the user to be verified, not just present
+        let result = verify_assertion(
+            &RELYING_PARTY,
+            &challenge,
+            &credential(&key_pair, 4),
+            true,
+            &client_data,
+            &auth_data,
+            &signature,
+        );
+        assert_eq!(result, Err(WebAuthnError::UserNotVerified));
+    }
+
+    #[test]
+    fn test_verify_assertion_with_wrong_key_or_ceremony() {
+        let other_key_pair = key_pair();
+        let key_pair = key_pair();
+        let challenge = WebAuthnChallenge::default();
+        let create_client_data = client_data("webauthn.create", &challenge, RELYING_PARTY.origin);
+        let client_data = client_data("webauthn.get", &challenge, RELYING_PARTY.origin);
+        let auth_data = authenticator_data("localhost", FLAG_USER_PRESENT, 0);
+        let signature = sign(&key_pair, &auth_data, &client_data);
+
+        let result = verify_assertion(
+            &RELYING_PARTY,
+            &challenge,
+            &credential(&other_key_pair, 0),
+            false,
+            &client_data,
+            &auth_data,
+            &signature,
+        );
+        assert_eq!(result, Err(WebAuthnError::InvalidSignature));
+
+        let result = verify_assertion(
+            &RELYING_PARTY,
+            &challenge,
+            &credential(&key_pair, 0),
+            false,
+            &create_client_data,
+            &auth_data,
+            &signature,
+        );
+        assert_eq!(result, Err(WebAuthnError::CeremonyMismatch));
+    }
+
+    #[test]
+    fn test_parse_cose_key_rejects_other_algorithms() {
+        let key_pair = key_pair();
+        let mut key = cose_key(key_pair.public_key().as_ref());
+        if let Value::Map(entries) = &mut key {
+            // RS256
+            entries[1].1 = Value::Integer((-257).into());
+        }
+        assert_eq!(parse_cose_key(&key), Err(WebAuthnError::UnsupportedKey));
+    }
+}
@@ -1,6 +1,6 @@
 use std::{error::Error};
 
-use axum::{http::{self}, response::{IntoResponse, Response}, routing::{delete, post}, serve::Serve, Json, Router};
+use axum::{http::{self, header}, middleware, response::{IntoResponse, Response}, routing::{delete, get, post}, serve::Serve, Json, Router};
 use redis::{Client, RedisResult};
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use tower_http::{cors::CorsLayer, services::ServeDir};
@@ -27,20 +27,58 @@ impl Application {
         let allowed_origins = ALLOWED_ORIGINS.clone();
 
         let cors = CorsLayer::new()
-            .allow_methods([http::Method::GET, http::Method::POST])
+            .allow_methods([http::Method::GET, http::Method::POST, http::Method::DELETE])
             // Allow cookies to be included in requests
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        // Routes that can only be called with a valid auth token
+        let protected_routes = Router::new()
+            .route("/logout", post(routes::logout))
+            .route("/logout-all", post(routes::logout_all))
+            .route("/sessions", get(routes::list_sessions))
+            .route("/sessions/:session_id", delete(routes::delete_session))
+            .route("/trusted-devices", get(routes::list_trusted_devices))
+            .route("/trusted-devices/:device_id", delete(routes::delete_trusted_device))
+            .route("/enroll-totp", post(routes::enroll_totp))
+            .route("/confirm-totp", post(routes::confirm_totp))
+            .route("/request-2fa-code", post(routes::request_2fa_code))
+            .route("/enable-2fa", post(routes::enable_2fa))
+            .route("/disable-2fa", post(routes::disable_2fa))
+            .route("/webauthn/register/start", post(routes::start_passkey_registration))
+            .route("/webauthn/register/finish", post(routes::finish_passkey_registration))
+            .route("/webauthn/credentials", get(routes::list_passkeys))
+            .route("/webauthn/credentials/:credential_id", delete(routes::delete_passkey))
+            .route(
+                "/recovery-codes",
+                get(routes::count_recovery_codes).post(routes::regenerate_recovery_codes),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                utils::require_auth,
+            ));
+
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(routes::signup))
             .route("/delete-account", delete(routes::delete_account))
             .route("/login", post(routes::login))
-            .route("/logout", post(routes::logout))
             .route("/verify-2fa", post(routes::verify_2fa))
+            .route("/resend-2fa-code", post(routes::resend_2fa_code))
+            .route("/verify-email", get(routes::verify_email))
+            .route("/resend-verification-email", post(routes::resend_verification_email))
+            .route("/forgot-password", post(routes::forgot_password))
+            .route(
+                "/reset-password",
+                get(routes::reset_password_page).post(routes::reset_password),
+            )
+            .route("/webauthn/login/start", post(routes::start_passkey_login))
+            .route("/webauthn/login/finish", post(routes::finish_passkey_login))
             .route("/verify-token", post(routes::verify_token))
-            // .route("/refresh-token")
+            .route("/introspect", post(routes::introspect))
+            .route("/refresh-token", post(routes::refresh_token))
+            .route("/.well-known/jwks.json", get(routes::jwks))
+            .merge(protected_routes)
             .with_state(app_state)
             .layer(cors);
 
@@ -60,23 +98,60 @@ impl Application {
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    // Only set when the client is throttled
+    #[serde(default, rename = "retryAfter", skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
+        let retry_after = match self {
+            AuthAPIError::ResendCooldown(secs)
+            | AuthAPIError::VerificationEmailCooldown(secs)
+            | AuthAPIError::PasswordResetEmailCooldown(secs) => Some(secs),
+            _ => None,
+        };
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (http::StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (http::StatusCode::BAD_REQUEST, "Invalid credentials"),
             AuthAPIError::IncorrectCredentials => (http::StatusCode::UNAUTHORIZED, "Incorrect credentials"),
             AuthAPIError::InvalidToken => (http::StatusCode::UNAUTHORIZED, "Invalid auth token"),
             AuthAPIError::MissingToken => (http::StatusCode::BAD_REQUEST, "Missing auth token"),
+            AuthAPIError::SessionNotFound => (http::StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::TrustedDeviceNotFound => (http::StatusCode::NOT_FOUND, "Trusted device not found"),
+            AuthAPIError::InvalidClientCredentials => (http::StatusCode::UNAUTHORIZED, "Invalid client credentials"),
+            AuthAPIError::TotpEnrollmentNotFound => (http::StatusCode::BAD_REQUEST, "No TOTP enrollment in progress"),
+            AuthAPIError::NotificationTargetNotFound => (http::StatusCode::BAD_REQUEST, "No phone number or push device awaiting confirmation"),
+            AuthAPIError::PasskeyNotRegistered => (http::StatusCode::BAD_REQUEST, "No passkey registered"),
+            AuthAPIError::PasskeyAlreadyRegistered => (http::StatusCode::CONFLICT, "Passkey already registered"),
+            AuthAPIError::PasskeyNotFound => (http::StatusCode::NOT_FOUND, "Passkey not found"),
+            AuthAPIError::TwoFANotEnabled => (http::StatusCode::BAD_REQUEST, "2FA is not enabled"),
+            AuthAPIError::TwoFAAlreadyEnabled => (http::StatusCode::CONFLICT, "2FA is already enabled"),
+            AuthAPIError::TooMany2FAAttempts => (http::StatusCode::TOO_MANY_REQUESTS, "Too many incorrect 2FA codes, please log in again"),
+            AuthAPIError::TwoFALockedOut => (http::StatusCode::TOO_MANY_REQUESTS, "Too many incorrect 2FA codes, please try again later"),
+            AuthAPIError::TwoFACodeNotResendable => (http::StatusCode::BAD_REQUEST, "2FA codes are only sent for email, SMS and push 2FA"),
+            AuthAPIError::TwoFAMethodUnavailable => (http::StatusCode::BAD_REQUEST, "This 2FA method is not available"),
+            AuthAPIError::ResendCooldown(_) => (http::StatusCode::TOO_MANY_REQUESTS, "Please wait before requesting another 2FA code"),
+            AuthAPIError::TooMany2FAResends => (http::StatusCode::TOO_MANY_REQUESTS, "Too many 2FA codes requested, please log in again"),
+            AuthAPIError::TooMany2FACodeRequests => (http::StatusCode::TOO_MANY_REQUESTS, "Too many 2FA codes requested, please try again later"),
+            AuthAPIError::VerificationEmailCooldown(_) => (http::StatusCode::TOO_MANY_REQUESTS, "Please wait before requesting another verification email"),
+            AuthAPIError::TooManyVerificationEmails => (http::StatusCode::TOO_MANY_REQUESTS, "Too many verification emails requested, please try again later"),
+            AuthAPIError::PasswordResetEmailCooldown(_) => (http::StatusCode::TOO_MANY_REQUESTS, "Please wait before requesting another password reset email"),
+            AuthAPIError::TooManyPasswordResetEmails => (http::StatusCode::TOO_MANY_REQUESTS, "Too many password reset emails requested, please try again later"),
+            AuthAPIError::EmailNotVerified => (http::StatusCode::FORBIDDEN, "Email address not verified"),
+            AuthAPIError::InvalidVerificationLink => (http::StatusCode::BAD_REQUEST, "Invalid or expired verification link"),
+            AuthAPIError::InvalidResetLink => (http::StatusCode::BAD_REQUEST, "Invalid or expired password reset link"),
             AuthAPIError::UnexpectedError => (http::StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error"),
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
+            retry_after,
         });
 
-        (status, body).into_response()
+        match retry_after {
+            Some(secs) => (status, [(header::RETRY_AFTER, secs.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
     }
 }
 
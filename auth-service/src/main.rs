@@ -1,13 +1,29 @@
 use std::sync::Arc;
 
 use auth_service::{
-    app_state, get_postgres_pool, get_redis_client,
-    services::{MockEmailClient, PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
-    utils::{prod, DATABASE_URL, REDIS_HOST_NAME},
+    app_state::{self, EmailClientType, NotificationChannelType},
+    get_postgres_pool, get_redis_client,
+    services::{
+        EmailOutboxWorker, HttpEmailClient, MockEmailClient, WebhookNotificationChannel,
+        WebhookSettings,
+        PostgresEmailOutboxStore, PostgresNotificationTargetStore, PostgresRecoveryCodeStore,
+        PostgresTotpSecretStore, PostgresUserStore, PostgresWebAuthnCredentialStore,
+        RedisBannedTokenStore, RedisPasswordResetTokenStore, RedisRefreshTokenStore, RedisSessionStore, RedisTrustedDeviceStore,
+        RedisTwoFACodeStore, RedisVerificationEmailStore, RedisWebAuthnChallengeStore, SmtpEmailClient,
+    },
+    utils::{
+        load_email_outbox_settings, load_http_email_settings, load_push_webhook_settings,
+        load_smtp_settings, load_sms_webhook_settings, prod, reload_jwt_signing_key,
+        DATABASE_URL, EMAIL_BRANDING, EMAIL_CLIENT, PUSH_CHANNEL, REDIS_HOST_NAME,
+        SERVICE_CLIENTS, SMS_CHANNEL, TWO_FA_CODE_SECRET,
+    },
     Application,
 };
 use sqlx::PgPool;
-use tokio::sync::RwLock;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::RwLock,
+};
 
 #[tokio::main]
 async fn main() {
@@ -15,17 +31,62 @@ async fn main() {
     let redis_conn = Arc::new(RwLock::new(configure_redis()));
 
     // let user_store = Arc::new(RwLock::new(HashmapUserStore::default()));
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
     let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn)));
-    let email_client = Arc::new(MockEmailClient {});
+    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(
+        redis_conn.clone(),
+        TWO_FA_CODE_SECRET.as_bytes(),
+    )));
+    let refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone())));
+    let session_store = Arc::new(RwLock::new(RedisSessionStore::new(redis_conn.clone())));
+    let totp_secret_store = Arc::new(RwLock::new(PostgresTotpSecretStore::new(pg_pool.clone())));
+    let recovery_code_store = Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+    let trusted_device_store = Arc::new(RwLock::new(RedisTrustedDeviceStore::new(redis_conn.clone())));
+    let notification_target_store =
+        Arc::new(RwLock::new(PostgresNotificationTargetStore::new(pg_pool.clone())));
+    let webauthn_challenge_store =
+        Arc::new(RwLock::new(RedisWebAuthnChallengeStore::new(redis_conn.clone())));
+    let password_reset_token_store =
+        Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(redis_conn.clone())));
+    let verification_email_store =
+        Arc::new(RwLock::new(RedisVerificationEmailStore::new(redis_conn)));
+    let webauthn_credential_store =
+        Arc::new(RwLock::new(PostgresWebAuthnCredentialStore::new(pg_pool.clone())));
+    let email_outbox = Arc::new(RwLock::new(PostgresEmailOutboxStore::new(pg_pool)));
+    let sms_channel = configure_notification_channel("SMS", &SMS_CHANNEL, load_sms_webhook_settings);
+    let push_channel =
+        configure_notification_channel("PUSH", &PUSH_CHANNEL, load_push_webhook_settings);
+    let service_clients = Arc::new(SERVICE_CLIENTS.clone());
     let app_state = app_state::AppState::new(
         user_store,
         banned_token_store,
         two_fa_code_store,
-        email_client,
+        refresh_token_store,
+        session_store,
+        totp_secret_store,
+        recovery_code_store,
+        trusted_device_store,
+        notification_target_store,
+        webauthn_challenge_store,
+        webauthn_credential_store,
+        password_reset_token_store,
+        verification_email_store,
+        email_outbox.clone(),
+        sms_channel,
+        push_channel,
+        service_clients,
     );
 
+    // Checked before anything is sent, rather than when the first email links back here
+    lazy_static::initialize(&EMAIL_BRANDING);
+
+    let outbox_settings = load_email_outbox_settings().expect("Invalid email outbox settings");
+    let outbox_worker =
+        EmailOutboxWorker::new(email_outbox, configure_email_client(), outbox_settings);
+    tokio::spawn(outbox_worker.run());
+
+    tokio::spawn(rotate_jwt_signing_key_on_sighup());
+
     let app = Application::build(app_state, prod::APP_ADDRESS)
         .await
         .expect("Failed to build app");
@@ -52,3 +113,48 @@ fn configure_redis() -> redis::Connection {
         .get_connection()
         .expect("Failed to get Redis connection")
 }
+
+fn configure_email_client() -> EmailClientType {
+    match EMAIL_CLIENT.as_str() {
+        "smtp" => {
+            let settings = load_smtp_settings().expect("Invalid SMTP settings");
+            Arc::new(SmtpEmailClient::new(settings).expect("Failed to create SMTP client"))
+        }
+        "http" => {
+            let settings = load_http_email_settings().expect("Invalid email API settings");
+            Arc::new(HttpEmailClient::new(settings).expect("Failed to create email API client"))
+        }
+        "mock" => Arc::new(MockEmailClient::default()),
+        other => panic!("{} is not a valid EMAIL_CLIENT, use smtp, http or mock.", other),
+    }
+}
+
+fn configure_notification_channel(
+    name: &str,
+    channel: &str,
+    load_settings: fn() -> Result<WebhookSettings, String>,
+) -> Option<NotificationChannelType> {
+    match channel {
+        "webhook" => {
+            let settings = load_settings().expect("Invalid notification webhook settings");
+            Some(Arc::new(
+                WebhookNotificationChannel::new(settings)
+                    .expect("Failed to create notification webhook client"),
+            ))
+        }
+        "none" => None,
+        other => panic!("{} is not a valid {}_CHANNEL, use webhook or none.", other, name),
+    }
+}
+
+// Reload the JWT signing key on SIGHUP so keys can be rotated without a restart
+async fn rotate_jwt_signing_key_on_sighup() {
+    let mut hangup = signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+
+    while hangup.recv().await.is_some() {
+        match reload_jwt_signing_key() {
+            Ok(()) => println!("Rotated JWT signing key"),
+            Err(e) => println!("Failed to rotate JWT signing key: {}", e),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use axum::{extract::State, response::IntoResponse, Form, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, ClientCredentials},
+    utils::{validate_token, AUTH_TOKEN_SCOPE},
+};
+
+// Token introspection as described in RFC 7662. Only registered backend services
+// may introspect tokens, and any token that fails validation is reported as inactive.
+pub async fn introspect(
+    State(state): State<AppState>,
+    credentials: ClientCredentials,
+    Form(request): Form<IntrospectionRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    if !state.service_clients.authenticate(&credentials) {
+        return Err(AuthAPIError::InvalidClientCredentials);
+    }
+
+    let response = match validate_token(
+        &request.token,
+        state.banned_token_store,
+        state.session_store,
+        state.user_store,
+    )
+    .await
+    {
+        Ok(claims) => IntrospectionResponse {
+            active: true,
+            token_type: Some("Bearer".to_owned()),
+            scope: Some(AUTH_TOKEN_SCOPE.to_owned()),
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+            sid: Some(claims.sid),
+        },
+        Err(_) => IntrospectionResponse::default(),
+    };
+
+    Ok(Json(response))
+}
+
+#[derive(Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: String,
+    // Only access tokens can be introspected, so the hint is accepted but not needed
+    pub token_type_hint: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
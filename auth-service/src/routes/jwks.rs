@@ -0,0 +1,8 @@
+use axum::{response::IntoResponse, Json};
+
+use crate::utils::jwt_public_keys;
+
+// Publish the public JWT signing keys so other services can verify tokens locally
+pub async fn jwks() -> impl IntoResponse {
+    Json(jwt_public_keys())
+}
@@ -1,16 +1,28 @@
-use axum::{extract::State, http, response::IntoResponse, Json};
+use axum::{
+    extract::State,
+    http::{self, header, HeaderMap},
+    response::IntoResponse,
+    Json,
+};
 use axum_extra::extract::CookieJar;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::generate_auth_cookie,
+    app_state::{AppState, NotificationChannelType},
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, NotificationTarget, OutboxEmail, Password,
+        TrustedDeviceStoreError, TwoFACode, TwoFAMethod,
+    },
+    utils::{
+        decode_trusted_device_token, start_session, EmailMessage, EMAIL_BRANDING,
+        TRUSTED_DEVICE_COOKIE_NAME,
+    },
 };
 
 pub async fn login(
     State(state): State<AppState>,
     jar: CookieJar,
+    headers: HeaderMap,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let email = match Email::parse(request.email) {
@@ -38,14 +50,72 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    match user.require_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+    // Only checked once the password is known to be right, so the error doesn't
+    // tell others which addresses have an account
+    if !user.email_verified {
+        return (jar, Err(AuthAPIError::EmailNotVerified));
+    }
+
+    let token_generation = match user_store.get_token_generation(&user.email).await {
+        Ok(generation) => generation,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let trusted_device = match is_trusted_device(&jar, &user.email, token_generation, &state).await
+    {
+        Ok(trusted) => trusted,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    match user.two_fa_method {
+        TwoFAMethod::Email | TwoFAMethod::Sms | TwoFAMethod::Push | TwoFAMethod::Totp
+            if !trusted_device =>
+        {
+            handle_2fa(&user.email, user.two_fa_method, &state, jar).await
+        }
+        // Users without 2FA and those logging in from a device they trust
+        _ => {
+            handle_no_2fa(
+                &user.email,
+                token_generation,
+                user_agent(&headers),
+                request.return_token,
+                &state,
+                jar,
+            )
+            .await
+        }
+    }
+}
+
+// Whether the request carries a trusted device cookie for the user that hasn't been
+// revoked, either directly or by revoking all of the user's tokens
+async fn is_trusted_device(
+    jar: &CookieJar,
+    email: &Email,
+    token_generation: i32,
+    state: &AppState,
+) -> Result<bool, AuthAPIError> {
+    let claims = match jar
+        .get(TRUSTED_DEVICE_COOKIE_NAME)
+        .and_then(|cookie| decode_trusted_device_token(cookie.value()).ok())
+    {
+        Some(claims) if claims.sub == email.as_ref() && claims.generation == token_generation => {
+            claims
+        }
+        _ => return Ok(false),
+    };
+
+    match state.trusted_device_store.read().await.get_device(&claims.jti).await {
+        Ok(device) => Ok(&device.email == email),
+        Err(TrustedDeviceStoreError::DeviceNotFound) => Ok(false),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
     }
 }
 
 async fn handle_2fa(
     email: &Email,
+    two_fa_method: TwoFAMethod,
     state: &AppState,
     jar: CookieJar,
 ) -> (
@@ -53,50 +123,157 @@ async fn handle_2fa(
     Result<(http::StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
     let login_attempt_id = LoginAttemptId::default();
-    let two_fa_code = TwoFACode::default();
+    // TOTP users answer with a code from their authenticator app, so only the
+    // login attempt is recorded for them
+    let two_fa_code = two_fa_method.sends_codes().then(TwoFACode::default);
 
-    if state
-        .two_fa_code_store
-        .write()
-        .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
-        .await
-        .is_err()
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError));
-    }
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    let result = match &two_fa_code {
+        Some(code) => {
+            two_fa_code_store
+                .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+                .await
+        }
+        None => {
+            two_fa_code_store
+                .add_login_attempt(email.clone(), login_attempt_id.clone())
+                .await
+        }
+    };
+    drop(two_fa_code_store);
 
-    if state
-        .email_client
-        .send_email(email, "2FA Code", two_fa_code.as_ref())
-        .await
-        .is_err()
-    {
+    if result.is_err() {
         return (jar, Err(AuthAPIError::UnexpectedError));
     }
 
+    if let Some(two_fa_code) = &two_fa_code {
+        if let Err(e) = send_2fa_code(state, email, two_fa_method, two_fa_code).await {
+            return (jar, Err(e));
+        }
+    }
 
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
         message: "2FA required".to_owned(),
         login_attempt_id: login_attempt_id.as_ref().to_string(),
+        two_fa_method: two_fa_method.as_ref().to_string(),
     }));
 
     (jar, Ok((http::StatusCode::PARTIAL_CONTENT, response)))
 }
 
-async fn handle_no_2fa(
+// Sends the code through the channel the user chose for 2FA
+pub(crate) async fn send_2fa_code(
+    state: &AppState,
     email: &Email,
+    two_fa_method: TwoFAMethod,
+    two_fa_code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+    match two_fa_method {
+        TwoFAMethod::Email => {
+            send_email(
+                state,
+                email,
+                EmailMessage::TwoFACode(two_fa_code.as_ref().to_owned()),
+            )
+            .await
+        }
+        TwoFAMethod::Sms | TwoFAMethod::Push => {
+            let target = state
+                .notification_target_store
+                .read()
+                .await
+                .get_target(email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+            notify_2fa_code(state, two_fa_method, &target, two_fa_code).await
+        }
+        TwoFAMethod::None | TwoFAMethod::Totp => Ok(()),
+    }
+}
+
+// Renders the message with the configured branding and queues it in the outbox,
+// the email is delivered in the background
+pub(crate) async fn send_email(
+    state: &AppState,
+    email: &Email,
+    message: EmailMessage,
+) -> Result<(), AuthAPIError> {
+    let content = message
+        .render(&EMAIL_BRANDING)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_outbox
+        .write()
+        .await
+        .enqueue(OutboxEmail::new(email.clone(), content))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Sends the code to the target over the SMS or push channel
+pub(crate) async fn notify_2fa_code(
+    state: &AppState,
+    two_fa_method: TwoFAMethod,
+    target: &NotificationTarget,
+    two_fa_code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+    notification_channel(state, two_fa_method)?
+        .send_notification(target, &format!("Your 2FA code is {}", two_fa_code.as_ref()))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// The channel codes of the 2FA method are sent through, if this deployment has one
+pub(crate) fn notification_channel(
+    state: &AppState,
+    two_fa_method: TwoFAMethod,
+) -> Result<&NotificationChannelType, AuthAPIError> {
+    let channel = match two_fa_method {
+        TwoFAMethod::Sms => &state.sms_channel,
+        TwoFAMethod::Push => &state.push_channel,
+        _ => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    channel.as_ref().ok_or(AuthAPIError::TwoFAMethodUnavailable)
+}
+
+// Starts a session for a user who needs no further factor
+pub(crate) async fn handle_no_2fa(
+    email: &Email,
+    token_generation: i32,
+    user_agent: Option<String>,
+    return_token: bool,
+    state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(http::StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = match generate_auth_cookie(email) {
-        Ok(cookie) => cookie,
+    let (auth_cookie, refresh_cookie) = match start_session(
+        email,
+        token_generation,
+        user_agent,
+        state.session_store.clone(),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
+        Ok(cookies) => cookies,
         Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    // Clients that can't store cookies get the token in the response body instead
+    if return_token {
+        let response = Json(LoginResponse::TokenAuth(TokenAuthResponse {
+            token: auth_cookie.value().to_owned(),
+            refresh_token: refresh_cookie.value().to_owned(),
+        }));
+        return (jar, Ok((http::StatusCode::OK, response)));
+    }
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
     (
         updated_jar,
@@ -104,10 +281,20 @@ async fn handle_no_2fa(
     )
 }
 
+// Remembered with the session so users can tell their sessions apart
+pub(crate) fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
 #[derive(Deserialize)]
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    #[serde(default, rename = "returnToken")]
+    pub return_token: bool,
 }
 
 // The login route can return 2 possible success responses.
@@ -117,6 +304,7 @@ pub struct LoginRequest {
 pub enum LoginResponse {
     RegularAuth,
     TwoFactorAuth(TwoFactorAuthResponse),
+    TokenAuth(TokenAuthResponse),
 }
 
 // If a user requires 2FA, this JSON body should be returned!
@@ -125,4 +313,16 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    // Which factor the client should ask for: "email", "sms", "push" or "totp"
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: String,
+}
+
+// If the client asked for the token in the body, this JSON body is returned instead of cookies
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenAuthResponse {
+    pub token: String,
+    // Exchanged for a new pair of tokens at /refresh-token once the token expires
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
 }
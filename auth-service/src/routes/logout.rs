@@ -4,38 +4,35 @@ use axum_extra::extract::{cookie::Cookie, CookieJar};
 use crate::{
     app_state::AppState,
     domain::AuthAPIError,
-    utils::{validate_token, JWT_COOKIE_NAME},
+    routes::revoke_session,
+    utils::{AuthenticatedUser, JWT_COOKIE_NAME, JWT_REFRESH_COOKIE_NAME},
 };
 
 pub async fn logout(
     State(state): State<AppState>,
+    AuthenticatedUser { claims, .. }: AuthenticatedUser,
     jar: CookieJar,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    let cookie = match jar.get(JWT_COOKIE_NAME) {
-        Some(cookie) => cookie,
-        None => return (jar, Err(AuthAPIError::MissingToken)),
-    };
-
-    // Validate token
-    let token = cookie.value().to_owned();
-    let _ = match validate_token(&token, state.banned_token_store.clone()).await {
-        Ok(claims) => claims,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
-    };
-
     if state
         .banned_token_store
         .write()
         .await
-        .add_token(token)
+        .add_token(claims.jti)
         .await
         .is_err()
     {
         return (jar, Err(AuthAPIError::UnexpectedError));
     };
 
-    // Remove jwt cookie
-    let jar = jar.remove(Cookie::from(JWT_COOKIE_NAME));
+    // End the session and revoke its refresh token family
+    if revoke_session(&state, &claims.sid).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Remove jwt and refresh cookies
+    let jar = jar
+        .remove(Cookie::from(JWT_COOKIE_NAME))
+        .remove(Cookie::from(JWT_REFRESH_COOKIE_NAME));
 
     (jar, Ok(http::StatusCode::OK))
 }
@@ -0,0 +1,56 @@
+use axum::{extract::State, http, response::IntoResponse};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email},
+    routes::revoke_session,
+    utils::{AuthenticatedUser, JWT_COOKIE_NAME, JWT_REFRESH_COOKIE_NAME},
+};
+
+// Log the user out on every device by invalidating all tokens issued to them so far
+pub async fn logout_all(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    if state
+        .user_store
+        .write()
+        .await
+        .increment_token_generation(&email)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    if let Err(e) = end_all_sessions(&state, &email).await {
+        return (jar, Err(e));
+    }
+
+    let jar = jar
+        .remove(Cookie::from(JWT_COOKIE_NAME))
+        .remove(Cookie::from(JWT_REFRESH_COOKIE_NAME));
+
+    (jar, Ok(http::StatusCode::OK))
+}
+
+// Ends all sessions of the user so none of them can be refreshed anymore
+pub(crate) async fn end_all_sessions(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    for session in sessions {
+        revoke_session(state, &session.id)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(())
+}
@@ -1,16 +1,40 @@
 pub mod login;
 pub mod logout;
+pub mod logout_all;
 pub mod signup;
 pub mod verify_2fa;
+pub mod resend_2fa_code;
 pub mod verify_token;
+pub mod introspect;
 pub mod delete_account;
-// mod refresh_token;
+pub mod refresh_token;
+pub mod jwks;
+pub mod sessions;
+pub mod totp;
+pub mod recovery_codes;
+pub mod two_fa;
+pub mod trusted_devices;
+pub mod webauthn;
+pub mod verify_email;
+pub mod reset_password;
 
 // re-export items from sub-modules
 pub use login::*;
 pub use logout::*;
+pub use logout_all::*;
 pub use signup::*;
 pub use verify_2fa::*;
+pub use resend_2fa_code::*;
 pub use verify_token::*;
+pub use introspect::*;
 pub use delete_account::*;
-// pub use refresh_token::*;
\ No newline at end of file
+pub use refresh_token::*;
+pub use jwks::*;
+pub use sessions::*;
+pub use totp::*;
+pub use recovery_codes::*;
+pub use two_fa::*;
+pub use trusted_devices::*;
+pub use webauthn::*;
+pub use verify_email::*;
+pub use reset_password::*;
\ No newline at end of file
@@ -0,0 +1,96 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password, RecoveryCode, TwoFAMethod},
+    routes::send_email,
+    utils::{AuthenticatedUser, EmailMessage, SecurityNotice},
+};
+
+// Replace the user's recovery codes with a new set, e.g. after running low.
+// The codes are only ever shown in this response. New codes get past 2FA, so
+// the password is required, like for turning 2FA off.
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Json(request): Json<RegenerateRecoveryCodesRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user = {
+        let user_store = state.user_store.read().await;
+
+        user_store
+            .validate_user(&email, &password)
+            .await
+            .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+        user_store
+            .get_user(&email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?
+    };
+
+    if user.two_fa_method == TwoFAMethod::None {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+
+    // The codes are already replaced, a notice that couldn't be sent doesn't undo that
+    let notice = EmailMessage::SecurityNotice(SecurityNotice::RecoveryCodesRegenerated);
+    let _ = send_email(&state, &email, notice).await;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+pub async fn count_recovery_codes(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let remaining = state
+        .recovery_code_store
+        .read()
+        .await
+        .count_codes(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(RemainingRecoveryCodesResponse { remaining }))
+}
+
+// Generate a new set of recovery codes, invalidating any codes issued before
+pub(crate) async fn issue_recovery_codes(
+    state: &AppState,
+    email: &Email,
+) -> Result<Vec<String>, AuthAPIError> {
+    let codes = RecoveryCode::generate_set();
+
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .set_codes(email, &codes)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(codes.iter().map(|code| code.as_ref().to_owned()).collect())
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateRecoveryCodesRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemainingRecoveryCodesResponse {
+    pub remaining: usize,
+}
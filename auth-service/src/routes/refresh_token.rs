@@ -0,0 +1,131 @@
+use axum::{
+    extract::State,
+    http,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, RefreshTokenStoreError, SessionStoreError, UserStoreError},
+    utils::{decode_refresh_token, generate_auth_cookie, generate_refresh_cookie, JWT_REFRESH_COOKIE_NAME},
+};
+
+use super::login::TokenAuthResponse;
+
+pub async fn refresh_token(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Option<Json<RefreshTokenRequest>>,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+    // Clients that got their tokens in a response body send the refresh token the same way
+    let body_token = request.and_then(|Json(request)| request.refresh_token);
+    let return_token = body_token.is_some();
+
+    let refresh_token = match body_token {
+        Some(token) => token,
+        None => match jar.get(JWT_REFRESH_COOKIE_NAME) {
+            Some(cookie) => cookie.value().to_owned(),
+            None => return (jar, Err(AuthAPIError::MissingToken)),
+        },
+    };
+
+    let claims = match decode_refresh_token(&refresh_token) {
+        Ok(claims) => claims,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let email = match Email::parse(claims.sub) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // The refresh token family is named after the session it belongs to
+    match state.session_store.read().await.get_session(&claims.family).await {
+        Ok(session) if session.email == email => (),
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {
+            let jar = jar.remove(Cookie::from(JWT_REFRESH_COOKIE_NAME));
+            return (jar, Err(AuthAPIError::InvalidToken));
+        }
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    let token_generation = match state.user_store.read().await.get_token_generation(&email).await {
+        Ok(generation) => generation,
+        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    match refresh_token_store.get_current_token(&claims.family).await {
+        Ok(current_token_id) if current_token_id == claims.jti => (),
+        Ok(_) => {
+            // An already rotated refresh token was used again, so it has most likely been
+            // stolen. Revoke the whole family to log out both the thief and the user.
+            if refresh_token_store.revoke_family(&claims.family).await.is_err() {
+                return (jar, Err(AuthAPIError::UnexpectedError));
+            }
+            let jar = jar.remove(Cookie::from(JWT_REFRESH_COOKIE_NAME));
+            return (jar, Err(AuthAPIError::InvalidToken));
+        }
+        Err(RefreshTokenStoreError::FamilyNotFound) => {
+            let jar = jar.remove(Cookie::from(JWT_REFRESH_COOKIE_NAME));
+            return (jar, Err(AuthAPIError::InvalidToken));
+        }
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    // Rotate the refresh token
+    let token_id = Uuid::new_v4().to_string();
+    if refresh_token_store
+        .set_current_token(&claims.family, &token_id)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Keep the session alive for as long as the rotated refresh token is valid
+    if state
+        .session_store
+        .write()
+        .await
+        .refresh_session(&claims.family)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    let refresh_cookie = match generate_refresh_cookie(&email, &claims.family, &token_id) {
+        Ok(cookie) => cookie,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let auth_cookie = match generate_auth_cookie(&email, &claims.family, token_generation) {
+        Ok(cookie) => cookie,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    if return_token {
+        let response = Json(TokenAuthResponse {
+            token: auth_cookie.value().to_owned(),
+            refresh_token: refresh_cookie.value().to_owned(),
+        });
+        return (jar, Ok((http::StatusCode::OK, response).into_response()));
+    }
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    (updated_jar, Ok(http::StatusCode::OK.into_response()))
+}
+
+#[derive(Deserialize)]
+pub struct RefreshTokenRequest {
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: Option<String>,
+}
@@ -0,0 +1,86 @@
+use axum::{extract::State, http, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode, TwoFACodeStoreError},
+    utils::{MAX_2FA_RESENDS, TWO_FA_RESEND_COOLDOWN_SECS},
+};
+
+use super::login::send_2fa_code;
+
+// Sends a new code for a pending login attempt, so users whose 2FA message got
+// delayed or lost don't have to enter their password again.
+pub async fn resend_2fa_code(
+    State(state): State<AppState>,
+    Json(request): Json<Resend2FACodeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    match two_fa_code_store.contains_code(&email, &login_attempt_id).await {
+        Ok(true) => (),
+        Ok(false) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if !user.two_fa_method.sends_codes() {
+        return Err(AuthAPIError::TwoFACodeNotResendable);
+    }
+
+    // A fresh code is sent rather than the old one, which may have been intercepted
+    let two_fa_code = TwoFACode::default();
+
+    match two_fa_code_store
+        .replace_code(
+            &email,
+            &login_attempt_id,
+            two_fa_code.clone(),
+            *TWO_FA_RESEND_COOLDOWN_SECS,
+            *MAX_2FA_RESENDS,
+        )
+        .await
+    {
+        Ok(()) => (),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(TwoFACodeStoreError::ResendCooldown(secs)) => {
+            return Err(AuthAPIError::ResendCooldown(secs))
+        }
+        Err(TwoFACodeStoreError::TooManyResends) => return Err(AuthAPIError::TooMany2FAResends),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+    drop(two_fa_code_store);
+
+    send_2fa_code(&state, &email, user.two_fa_method, &two_fa_code).await?;
+
+    let response = Json(Resend2FACodeResponse {
+        message: "2FA code sent".to_owned(),
+    });
+
+    Ok((http::StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct Resend2FACodeRequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Resend2FACodeResponse {
+    pub message: String,
+}
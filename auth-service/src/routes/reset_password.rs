@@ -0,0 +1,196 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http,
+    response::{Html, IntoResponse},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, Password, PasswordReset, PasswordResetToken,
+        PasswordResetTokenStoreError, UserStoreError,
+    },
+    utils::{
+        EmailBranding, EmailMessage, SecurityNotice, EMAIL_BRANDING, MAX_PASSWORD_RESET_EMAILS,
+        PASSWORD_RESET_EMAIL_COOLDOWN_SECS,
+    },
+};
+
+use super::{login::send_email, logout_all::end_all_sessions, webauthn::remove_passkeys};
+
+// Always accepted, so the response doesn't tell which addresses have an account
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(request): Json<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Counted before looking the user up, so the limit doesn't tell either
+    match state
+        .password_reset_token_store
+        .write()
+        .await
+        .record_reset_email(
+            &email,
+            PASSWORD_RESET_EMAIL_COOLDOWN_SECS,
+            MAX_PASSWORD_RESET_EMAILS,
+        )
+        .await
+    {
+        Ok(()) => (),
+        Err(PasswordResetTokenStoreError::ResetEmailCooldown(secs)) => {
+            return Err(AuthAPIError::PasswordResetEmailCooldown(secs))
+        }
+        Err(PasswordResetTokenStoreError::TooManyResetEmails) => {
+            return Err(AuthAPIError::TooManyPasswordResetEmails)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let token_generation = match state
+        .user_store
+        .read()
+        .await
+        .get_token_generation(&email)
+        .await
+    {
+        Ok(generation) => generation,
+        Err(UserStoreError::UserNotFound) => return Ok(http::StatusCode::ACCEPTED),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let token = PasswordResetToken::default();
+    let reset = PasswordReset {
+        email: email.clone(),
+        token_generation,
+    };
+    state
+        .password_reset_token_store
+        .write()
+        .await
+        .add_token(&token, reset)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let link = format!(
+        "{}/reset-password?token={}",
+        EMAIL_BRANDING.url,
+        token.as_ref()
+    );
+    send_email(&state, &email, EmailMessage::ResetPassword(link)).await?;
+
+    Ok(http::StatusCode::ACCEPTED)
+}
+
+#[derive(Template)]
+#[template(path = "pages/reset_password.html")]
+struct ResetPasswordPage<'a> {
+    token: &'a str,
+    branding: &'a EmailBranding,
+}
+
+// Opened from the link in the reset email. Shows a form that sends the token along
+// with the new password to `reset_password`. The token is only used up there.
+pub async fn reset_password_page(
+    Query(request): Query<ResetPasswordPageRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token =
+        PasswordResetToken::parse(request.token).map_err(|_| AuthAPIError::InvalidResetLink)?;
+
+    let page = ResetPasswordPage {
+        token: token.as_ref(),
+        branding: &EMAIL_BRANDING,
+    }
+    .render()
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Html(page))
+}
+
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token =
+        PasswordResetToken::parse(request.token).map_err(|_| AuthAPIError::InvalidResetLink)?;
+    // Checked before the token is used up, so a too weak password can be corrected
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let reset = match state
+        .password_reset_token_store
+        .write()
+        .await
+        .take_token(&token)
+        .await
+    {
+        Ok(reset) => reset,
+        Err(PasswordResetTokenStoreError::TokenNotFound) => {
+            return Err(AuthAPIError::InvalidResetLink)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let mut user_store = state.user_store.write().await;
+
+    // Links sent before the password was last reset, or before the user logged
+    // out everywhere, are no longer valid
+    match user_store.get_token_generation(&reset.email).await {
+        Ok(generation) if generation == reset.token_generation => (),
+        Ok(_) | Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidResetLink),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    user_store
+        .update_password(&reset.email, password)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    // Whoever knew the old password is logged out. Bumping the generation while
+    // the store is still locked also keeps a second link from passing the check.
+    user_store
+        .increment_token_generation(&reset.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    // Following the emailed link proves the user owns the address
+    user_store
+        .mark_email_verified(&reset.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(user_store);
+
+    end_all_sessions(&state, &reset.email).await?;
+    // A passkey added by whoever got into the account logs in without the password
+    remove_passkeys(&state, &reset.email).await?;
+
+    let notice = EmailMessage::SecurityNotice(SecurityNotice::PasswordChanged);
+    let _ = send_email(&state, &reset.email, notice).await;
+
+    let response = Json(ResetPasswordResponse {
+        message: "Password updated".to_owned(),
+    });
+    Ok((http::StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordPageRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ResetPasswordResponse {
+    pub message: String,
+}
@@ -0,0 +1,97 @@
+use std::cmp::Reverse;
+
+use axum::{
+    extract::{Path, State},
+    http,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, SessionStoreError},
+    utils::AuthenticatedUser,
+};
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, claims }: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Most recent sessions first
+    sessions.sort_by_key(|session| Reverse(session.created_at));
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            current: session.id == claims.sid,
+            id: session.id,
+            created_at: session.created_at,
+            user_agent: session.user_agent,
+        })
+        .collect();
+
+    Ok(Json(ListSessionsResponse { sessions }))
+}
+
+pub async fn delete_session(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    // Users may only revoke their own sessions
+    match state.session_store.read().await.get_session(&session_id).await {
+        Ok(session) if session.email == email => (),
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {
+            return Err(AuthAPIError::SessionNotFound)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    revoke_session(&state, &session_id).await?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+// Remove the session and revoke its refresh token family. Auth tokens issued for
+// the session fail validation from now on.
+pub(crate) async fn revoke_session(state: &AppState, session_id: &str) -> Result<(), AuthAPIError> {
+    state
+        .session_store
+        .write()
+        .await
+        .remove_session(session_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_family(session_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListSessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    pub current: bool,
+}
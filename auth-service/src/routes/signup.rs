@@ -1,8 +1,10 @@
 use axum::{extract::State, http, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 
-use crate::domain::{AuthAPIError, Email, Password};
-use crate::{app_state::AppState, domain::User};
+use crate::domain::{AuthAPIError, Email, Password, TwoFAMethod};
+use crate::{app_state::AppState, domain::User, routes::issue_recovery_codes};
+
+use super::verify_email::send_verification_email;
 
 pub async fn signup(
     State(state): State<AppState>,
@@ -13,7 +15,12 @@ pub async fn signup(
     let password = Password::parse(request.password)
         .map_err(|_| AuthAPIError::InvalidCredentials)?;
     
-    let user = User::new(email.clone(), password, request.requires_2fa);
+    // New users start out with email codes; authenticator apps are enrolled once logged in
+    let two_fa_method = match request.requires_2fa {
+        true => TwoFAMethod::Email,
+        false => TwoFAMethod::None,
+    };
+    let user = User::new(email.clone(), password, two_fa_method);
     let mut user_store = state.user_store.write().await;
     
     match user_store.get_user(&email).await {
@@ -24,8 +31,16 @@ pub async fn signup(
          
     match user_store.add_user(user).await {
         Ok(_) => {
+            send_verification_email(&state, &email).await?;
+
+            // Fallback for users who lose access to their second factor
+            let recovery_codes = match two_fa_method {
+                TwoFAMethod::None => Vec::new(),
+                _ => issue_recovery_codes(&state, &email).await?,
+            };
             let response = Json(SignupResponse{
                 message: "User created successfully!".to_string(),
+                recovery_codes,
             });
             Ok((http::StatusCode::CREATED, response))
         },
@@ -47,4 +62,6 @@ pub struct SignupRequest {
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct SignupResponse {
     pub message: String,
+    #[serde(default, rename = "recoveryCodes", skip_serializing_if = "Vec::is_empty")]
+    pub recovery_codes: Vec<String>,
 }
\ No newline at end of file
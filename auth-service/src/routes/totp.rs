@@ -0,0 +1,110 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, TotpSecret, TotpSecretStoreError, TwoFACode, TwoFAMethod},
+    routes::{issue_recovery_codes, send_email, RecoveryCodesResponse},
+    utils::{AuthenticatedUser, EmailMessage, SecurityNotice, JWT_ISSUER},
+};
+
+// Start enrolling an authenticator app. The new secret isn't used for logins
+// until the user confirms it with a code generated by the app. Only users
+// without 2FA can enroll, switching from another method takes turning 2FA off
+// first, which needs the password and the current second factor.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    ensure_2fa_disabled(&state, &email).await?;
+
+    let secret = TotpSecret::default();
+
+    state
+        .totp_secret_store
+        .write()
+        .await
+        .set_pending_secret(&email, secret.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(EnrollTotpResponse {
+        otpauth_uri: secret.otpauth_uri(&JWT_ISSUER, &email),
+        secret: secret.as_ref().to_owned(),
+    }))
+}
+
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // 2FA may have been turned on another way since enrolling
+    ensure_2fa_disabled(&state, &email).await?;
+
+    let mut totp_secret_store = state.totp_secret_store.write().await;
+
+    let secret = match totp_secret_store.get_pending_secret(&email).await {
+        Ok(secret) => secret,
+        Err(TotpSecretStoreError::SecretNotFound) => {
+            return Err(AuthAPIError::TotpEnrollmentNotFound)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let step = secret
+        .verify(&code, Utc::now().timestamp() as u64)
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    // The confirmation code counts as used, so it can't also be used to log in
+    totp_secret_store
+        .activate_pending_secret(&email, step)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&email, TwoFAMethod::Totp)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+
+    // 2FA is already on, a notice that couldn't be sent doesn't undo that
+    let notice = EmailMessage::SecurityNotice(SecurityNotice::TwoFAEnabled);
+    let _ = send_email(&state, &email, notice).await;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+async fn ensure_2fa_disabled(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    match user.two_fa_method {
+        TwoFAMethod::None => Ok(()),
+        _ => Err(AuthAPIError::TwoFAAlreadyEnabled),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+}
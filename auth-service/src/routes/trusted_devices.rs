@@ -0,0 +1,112 @@
+use std::cmp::Reverse;
+
+use axum::{
+    extract::{Path, State},
+    http,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, TrustedDeviceStoreError},
+    utils::{decode_trusted_device_token, AuthenticatedUser, TRUSTED_DEVICE_COOKIE_NAME},
+};
+
+pub async fn list_trusted_devices(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut devices = state
+        .trusted_device_store
+        .read()
+        .await
+        .get_devices(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Most recently trusted devices first
+    devices.sort_by_key(|device| Reverse(device.created_at));
+
+    // The device making the request, if it is remembered
+    let current_device_id = jar
+        .get(TRUSTED_DEVICE_COOKIE_NAME)
+        .and_then(|cookie| decode_trusted_device_token(cookie.value()).ok())
+        .map(|claims| claims.jti);
+
+    let devices = devices
+        .into_iter()
+        .map(|device| TrustedDeviceResponse {
+            current: current_device_id.as_deref() == Some(device.id.as_str()),
+            id: device.id,
+            created_at: device.created_at,
+            user_agent: device.user_agent,
+        })
+        .collect();
+
+    Ok(Json(ListTrustedDevicesResponse { devices }))
+}
+
+pub async fn delete_trusted_device(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Path(device_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut trusted_device_store = state.trusted_device_store.write().await;
+
+    // Users may only revoke their own devices
+    match trusted_device_store.get_device(&device_id).await {
+        Ok(device) if device.email == email => (),
+        Ok(_) | Err(TrustedDeviceStoreError::DeviceNotFound) => {
+            return Err(AuthAPIError::TrustedDeviceNotFound)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    trusted_device_store
+        .remove_device(&device_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+// Forgets every device of the user, so their next login asks for 2FA again
+pub(crate) async fn revoke_trusted_devices(
+    state: &AppState,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    let mut trusted_device_store = state.trusted_device_store.write().await;
+
+    let devices = trusted_device_store
+        .get_devices(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    for device in devices {
+        trusted_device_store
+            .remove_device(&device.id)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListTrustedDevicesResponse {
+    pub devices: Vec<TrustedDeviceResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDeviceResponse {
+    pub id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    pub current: bool,
+}
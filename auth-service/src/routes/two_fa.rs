@@ -0,0 +1,375 @@
+use axum::{extract::State, http, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, NotificationTarget, NotificationTargetStoreError,
+        Password, TwoFACode, TwoFACodeStoreError, TwoFAMethod,
+    },
+    routes::{
+        issue_recovery_codes, notification_channel, notify_2fa_code, remove_passkeys,
+        revoke_trusted_devices, send_2fa_code, send_email, verify_second_factor,
+        RecoveryCodesResponse, SecondFactor,
+    },
+    utils::{
+        AuthenticatedUser, EmailMessage, SecurityNotice, MAX_2FA_CODE_REQUESTS,
+        TWO_FA_RESEND_COOLDOWN_SECS,
+    },
+};
+
+// Sends a code to the signed-in user, so they can prove they control where their
+// codes go before turning 2FA on, or before turning it off again. Without a body
+// the code goes where login codes go, or to the user's inbox if 2FA is off.
+// Naming a phone number or push device sends it there instead, and the target is
+// kept pending until `enable_2fa` confirms it.
+pub async fn request_2fa_code(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    request: Option<Json<Request2FACodeRequest>>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let two_fa_method = match &request {
+        Some(Json(request)) => TwoFAMethod::parse(&request.two_fa_method)
+            .map_err(|_| AuthAPIError::InvalidCredentials)?,
+        None => {
+            let user = state
+                .user_store
+                .read()
+                .await
+                .get_user(&email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+            match user.two_fa_method.sends_codes() {
+                true => user.two_fa_method,
+                false => TwoFAMethod::Email,
+            }
+        }
+    };
+
+    if !two_fa_method.sends_codes() {
+        return Err(AuthAPIError::TwoFACodeNotResendable);
+    }
+
+    let new_target = match (request, two_fa_method) {
+        (Some(Json(request)), TwoFAMethod::Sms | TwoFAMethod::Push) => {
+            notification_channel(&state, two_fa_method)?;
+            let target = request.target.ok_or(AuthAPIError::InvalidCredentials)?;
+            Some(
+                NotificationTarget::parse(two_fa_method, target)
+                    .map_err(|_| AuthAPIError::InvalidCredentials)?,
+            )
+        }
+        _ => None,
+    };
+
+    let login_attempt_id = LoginAttemptId::default();
+    let two_fa_code = TwoFACode::default();
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    // Each code gets a login attempt of its own. Fewer are allowed than a user can
+    // have pending, so they can't push out the user's actual login attempts.
+    // Codes sent to a phone number or device are limited for it too, whichever
+    // account asks for them.
+    for key in code_request_keys(&email, new_target.as_ref()) {
+        match two_fa_code_store
+            .record_code_request(&key, *TWO_FA_RESEND_COOLDOWN_SECS, MAX_2FA_CODE_REQUESTS)
+            .await
+        {
+            Ok(()) => (),
+            Err(TwoFACodeStoreError::ResendCooldown(secs)) => {
+                return Err(AuthAPIError::ResendCooldown(secs))
+            }
+            Err(TwoFACodeStoreError::TooManyResends) => {
+                return Err(AuthAPIError::TooMany2FACodeRequests)
+            }
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        }
+    }
+
+    two_fa_code_store
+        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(two_fa_code_store);
+
+    match new_target {
+        Some(target) => {
+            state
+                .notification_target_store
+                .write()
+                .await
+                .set_pending_target(&email, two_fa_method, target.clone(), &login_attempt_id)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+            notify_2fa_code(&state, two_fa_method, &target, &two_fa_code).await?;
+        }
+        None => send_2fa_code(&state, &email, two_fa_method, &two_fa_code).await?,
+    }
+
+    Ok(Json(Request2FACodeResponse {
+        message: "2FA code sent".to_owned(),
+        login_attempt_id: login_attempt_id.as_ref().to_owned(),
+    }))
+}
+
+// Turns on 2FA with a code from `request_2fa_code`. SMS and push 2FA use the
+// target the code was sent to. Authenticator apps are set up through
+// `enroll_totp` instead.
+pub async fn enable_2fa(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Json(request): Json<Enable2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let two_fa_method = match request.two_fa_method {
+        Some(method) => {
+            TwoFAMethod::parse(&method).map_err(|_| AuthAPIError::InvalidCredentials)?
+        }
+        None => TwoFAMethod::Email,
+    };
+
+    if !two_fa_method.sends_codes() {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if user.two_fa_method != TwoFAMethod::None {
+        return Err(AuthAPIError::TwoFAAlreadyEnabled);
+    }
+
+    let has_target = two_fa_method != TwoFAMethod::Email;
+
+    let target = match has_target {
+        true => match state
+            .notification_target_store
+            .read()
+            .await
+            .get_pending_target(&email)
+            .await
+        {
+            // The code must be the one that was sent to the pending target, not one
+            // sent to the user's inbox or for another login attempt
+            Ok((pending_method, target, pending_login_attempt_id))
+                if pending_method == two_fa_method
+                    && pending_login_attempt_id == login_attempt_id =>
+            {
+                Some(target)
+            }
+            Ok(_) | Err(NotificationTargetStoreError::TargetNotFound) => {
+                return Err(AuthAPIError::NotificationTargetNotFound)
+            }
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        },
+        false => None,
+    };
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    verify_second_factor(
+        &state,
+        &mut *two_fa_code_store,
+        &email,
+        &login_attempt_id,
+        SecondFactor::Code(code),
+        two_fa_method,
+    )
+    .await?;
+
+    // The code that was asked for is used up, so the count doesn't hold back asking
+    // for one to turn 2FA off again
+    for key in code_request_keys(&email, target.as_ref()) {
+        two_fa_code_store
+            .clear_code_requests(&key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+    drop(two_fa_code_store);
+
+    if has_target {
+        match state
+            .notification_target_store
+            .write()
+            .await
+            .activate_pending_target(&email, &login_attempt_id)
+            .await
+        {
+            Ok(()) => (),
+            // Replaced by a newer pending target in the meantime
+            Err(NotificationTargetStoreError::TargetNotFound) => {
+                return Err(AuthAPIError::NotificationTargetNotFound)
+            }
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        }
+    }
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&email, two_fa_method)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+
+    // 2FA is already on, a notice that couldn't be sent doesn't undo that
+    let notice = EmailMessage::SecurityNotice(SecurityNotice::TwoFAEnabled);
+    let _ = send_email(&state, &email, notice).await;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+// Turns 2FA off. Someone holding a stolen session shouldn't be able to do this,
+// so both the password and the current second factor are required.
+pub async fn disable_2fa(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Json(request): Json<Disable2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let second_factor = SecondFactor::parse(request.two_fa_code)?;
+    let login_attempt_id = match request.login_attempt_id {
+        Some(id) => Some(LoginAttemptId::parse(id).map_err(|_| AuthAPIError::InvalidCredentials)?),
+        None => None,
+    };
+
+    let user = {
+        let user_store = state.user_store.read().await;
+
+        user_store
+            .validate_user(&email, &password)
+            .await
+            .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+        user_store
+            .get_user(&email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?
+    };
+
+    if user.two_fa_method == TwoFAMethod::None {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    // Sent codes belong to the login attempt they were requested with, and wrong
+    // guesses are counted against it. TOTP and recovery codes are counted per user.
+    let login_attempt_id = match (login_attempt_id, &second_factor) {
+        (Some(id), _) => id,
+        (None, SecondFactor::Code(_)) if user.two_fa_method.sends_codes() => {
+            return Err(AuthAPIError::InvalidCredentials)
+        }
+        (None, _) => LoginAttemptId::default(),
+    };
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    verify_second_factor(
+        &state,
+        &mut *two_fa_code_store,
+        &email,
+        &login_attempt_id,
+        second_factor,
+        user.two_fa_method,
+    )
+    .await?;
+
+    match two_fa_code_store.remove_code(&email, &login_attempt_id).await {
+        Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => (),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+    drop(two_fa_code_store);
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&email, TwoFAMethod::None)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Left over recovery codes would otherwise work again once 2FA is turned back on
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .set_codes(&email, &[])
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Neither should skip 2FA or receive codes once it is turned back on
+    revoke_trusted_devices(&state, &email).await?;
+    // Passkeys are a second factor too
+    remove_passkeys(&state, &email).await?;
+
+    state
+        .notification_target_store
+        .write()
+        .await
+        .remove_targets(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // 2FA is already off, a notice that couldn't be sent doesn't undo that
+    let notice = EmailMessage::SecurityNotice(SecurityNotice::TwoFADisabled);
+    let _ = send_email(&state, &email, notice).await;
+
+    Ok(http::StatusCode::OK)
+}
+
+// Keys codes sent by `request_2fa_code` are counted against
+fn code_request_keys(email: &Email, target: Option<&NotificationTarget>) -> Vec<String> {
+    let mut keys = vec![format!("user:{}", email.as_ref())];
+    if let Some(target) = target {
+        keys.push(format!("target:{}", target.as_ref()));
+    }
+    keys
+}
+
+#[derive(Deserialize)]
+pub struct Request2FACodeRequest {
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: String,
+    // Phone number or push device token, for SMS and push 2FA
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request2FACodeResponse {
+    pub message: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct Enable2FARequest {
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+    // Defaults to email
+    #[serde(default, rename = "twoFAMethod")]
+    pub two_fa_method: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Disable2FARequest {
+    pub password: String,
+    #[serde(default, rename = "loginAttemptId")]
+    pub login_attempt_id: Option<String>,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+}
@@ -1,16 +1,32 @@
-use axum::{extract::State, http, response::IntoResponse, Json};
-use axum_extra::extract::CookieJar;
+use axum::{
+    extract::State,
+    http::{self, HeaderMap},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use chrono::Utc;
 use serde::Deserialize;
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, RecoveryCode, RecoveryCodeStoreError,
+        TotpSecretStoreError, TrustedDevice, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+        TwoFAMethod,
+    },
+    utils::{
+        generate_trusted_device_cookie, start_session, MAX_2FA_ATTEMPTS, MAX_2FA_USER_FAILURES,
+        TWO_FA_LOCKOUT_SECS,
+    },
 };
 
+use super::login::{user_agent, TokenAuthResponse};
+
 pub async fn verify_2fa(
     State(state): State<AppState>,
     jar: CookieJar,
+    headers: HeaderMap,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let email = match Email::parse(request.email) {
@@ -23,36 +39,256 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let two_fa_code = match TwoFACode::parse(request.two_fa_code.to_string()) {
-        Ok(code) => code,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    let second_factor = match SecondFactor::parse(request.two_fa_code) {
+        Ok(second_factor) => second_factor,
+        Err(e) => return (jar, Err(e)),
     };
 
     let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    
-    let code_tuple = match two_fa_code_store.get_code(&email).await {
-        Ok(code_tuple) => code_tuple,
+
+    match two_fa_code_store.contains_code(&email, &login_attempt_id).await {
+        Ok(true) => (),
+        Ok(false) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    let user_store = state.user_store.read().await;
+
+    let user = match user_store.get_user(&email).await {
+        Ok(user) => user,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    if code_tuple != (login_attempt_id, two_fa_code) {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    if let Err(e) = verify_second_factor(
+        &state,
+        &mut *two_fa_code_store,
+        &email,
+        &login_attempt_id,
+        second_factor,
+        user.two_fa_method,
+    )
+    .await
+    {
+        return (jar, Err(e));
     }
 
-    if two_fa_code_store.remove_code(&email).await.is_err() {
-        return (jar, Err(AuthAPIError::UnexpectedError));
+    // Only this attempt is used up, logins in progress on other devices stay valid.
+    // An emailed code was already removed when it was consumed.
+    match two_fa_code_store.remove_code(&email, &login_attempt_id).await {
+        Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => (),
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     }
 
-    let auth_cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
+    let token_generation = match user_store.get_token_generation(&email).await {
+        Ok(generation) => generation,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let (auth_cookie, refresh_cookie) = match start_session(
+        &email,
+        token_generation,
+        user_agent(&headers),
+        state.session_store.clone(),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
+        Ok(cookies) => cookies,
         Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    // Later logins from this device won't ask for a second factor
+    let jar = if request.remember_device {
+        match remember_device(&state, &email, token_generation, user_agent(&headers)).await {
+            Ok(device_cookie) => jar.add(device_cookie),
+            Err(e) => return (jar, Err(e)),
+        }
+    } else {
+        jar
+    };
+
+    if request.return_token {
+        let response = Json(TokenAuthResponse {
+            token: auth_cookie.value().to_owned(),
+            refresh_token: refresh_cookie.value().to_owned(),
+        });
+        return (jar, Ok((http::StatusCode::OK, response).into_response()));
+    }
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
     (updated_jar, Ok(http::StatusCode::OK.into_response()))
 }
 
+async fn remember_device(
+    state: &AppState,
+    email: &Email,
+    token_generation: i32,
+    user_agent: Option<String>,
+) -> Result<Cookie<'static>, AuthAPIError> {
+    let device = TrustedDevice::new(email.clone(), user_agent);
+    let device_id = device.id.clone();
+
+    state
+        .trusted_device_store
+        .write()
+        .await
+        .add_device(device)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    generate_trusted_device_cookie(email, &device_id, token_generation)
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// The 2FA code field also accepts a recovery code in place of the second factor
+pub(crate) enum SecondFactor {
+    Code(TwoFACode),
+    RecoveryCode(RecoveryCode),
+}
+
+impl SecondFactor {
+    pub(crate) fn parse(code: String) -> Result<Self, AuthAPIError> {
+        match TwoFACode::parse(code.clone()) {
+            Ok(code) => Ok(Self::Code(code)),
+            Err(_) => RecoveryCode::parse(code)
+                .map(Self::RecoveryCode)
+                .map_err(|_| AuthAPIError::InvalidCredentials),
+        }
+    }
+}
+
+// Checks the second factor given for a pending 2FA code. Every wrong guess counts
+// against the code, so codes can't be brute-forced. TOTP and recovery codes stay the
+// same for new login attempts, so wrong guesses at them also count against the user.
+pub(crate) async fn verify_second_factor(
+    state: &AppState,
+    two_fa_code_store: &mut (dyn TwoFACodeStore + Send + Sync),
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    second_factor: SecondFactor,
+    two_fa_method: TwoFAMethod,
+) -> Result<(), AuthAPIError> {
+    let counts_against_user = match second_factor {
+        SecondFactor::RecoveryCode(_) => true,
+        SecondFactor::Code(_) => two_fa_method == TwoFAMethod::Totp,
+    };
+
+    if counts_against_user {
+        match two_fa_code_store
+            .is_user_locked_out(email, *MAX_2FA_USER_FAILURES)
+            .await
+        {
+            Ok(false) => (),
+            Ok(true) => return Err(AuthAPIError::TwoFALockedOut),
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        }
+    }
+
+    match check_second_factor(
+        state,
+        two_fa_code_store,
+        email,
+        login_attempt_id,
+        second_factor,
+        two_fa_method,
+    )
+    .await
+    {
+        Ok(()) if counts_against_user => two_fa_code_store
+            .clear_user_failures(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError),
+        Err(AuthAPIError::IncorrectCredentials) => {
+            if counts_against_user {
+                match two_fa_code_store
+                    .record_user_failure(email, *MAX_2FA_USER_FAILURES, TWO_FA_LOCKOUT_SECS)
+                    .await
+                {
+                    Ok(()) => (),
+                    Err(TwoFACodeStoreError::TooManyAttempts) => {
+                        return Err(AuthAPIError::TwoFALockedOut)
+                    }
+                    Err(_) => return Err(AuthAPIError::UnexpectedError),
+                }
+            }
+
+            match two_fa_code_store
+                .record_failed_attempt(email, login_attempt_id, *MAX_2FA_ATTEMPTS)
+                .await
+            {
+                // Not found when a concurrent request already used up the login attempt
+                Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+                    Err(AuthAPIError::IncorrectCredentials)
+                }
+                Err(TwoFACodeStoreError::TooManyAttempts) => Err(AuthAPIError::TooMany2FAAttempts),
+                Err(_) => Err(AuthAPIError::UnexpectedError),
+            }
+        }
+        result => result,
+    }
+}
+
+async fn check_second_factor(
+    state: &AppState,
+    two_fa_code_store: &mut (dyn TwoFACodeStore + Send + Sync),
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    second_factor: SecondFactor,
+    two_fa_method: TwoFAMethod,
+) -> Result<(), AuthAPIError> {
+    match (second_factor, two_fa_method) {
+        (SecondFactor::RecoveryCode(code), _) => {
+            match state.recovery_code_store.write().await.use_code(email, &code).await {
+                Ok(()) => Ok(()),
+                Err(RecoveryCodeStoreError::CodeNotFound) => {
+                    Err(AuthAPIError::IncorrectCredentials)
+                }
+                Err(_) => Err(AuthAPIError::UnexpectedError),
+            }
+        }
+        (SecondFactor::Code(code), TwoFAMethod::Totp) => {
+            verify_totp_code(state, email, &code).await
+        }
+        (SecondFactor::Code(code), _) => {
+            match two_fa_code_store
+                .consume_code(email, login_attempt_id, &code)
+                .await
+            {
+                Ok(()) => Ok(()),
+                Err(TwoFACodeStoreError::UnexpectedError) => Err(AuthAPIError::UnexpectedError),
+                Err(_) => Err(AuthAPIError::IncorrectCredentials),
+            }
+        }
+    }
+}
+
+// Checks the code against the user's authenticator app secret. Each code is
+// accepted only once, even while it is still within its validity window.
+async fn verify_totp_code(
+    state: &AppState,
+    email: &Email,
+    code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+    let mut totp_secret_store = state.totp_secret_store.write().await;
+
+    let secret = match totp_secret_store.get_secret(email).await {
+        Ok(secret) => secret,
+        Err(TotpSecretStoreError::SecretNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let step = secret
+        .verify(code, Utc::now().timestamp() as u64)
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    match totp_secret_store.use_step(email, step).await {
+        Ok(()) => Ok(()),
+        Err(TotpSecretStoreError::UnexpectedError) => Err(AuthAPIError::UnexpectedError),
+        Err(_) => Err(AuthAPIError::IncorrectCredentials),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Verify2FARequest {
     pub email: String,
@@ -60,4 +296,8 @@ pub struct Verify2FARequest {
     pub login_attempt_id: String,
     #[serde(rename = "2FACode")]
     pub two_fa_code: String,
+    #[serde(default, rename = "returnToken")]
+    pub return_token: bool,
+    #[serde(default, rename = "rememberDevice")]
+    pub remember_device: bool,
 }
@@ -0,0 +1,112 @@
+use axum::{
+    extract::{Query, State},
+    http,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, UserStoreError, VerificationEmailStoreError},
+    utils::{
+        decode_email_verification_token, generate_email_verification_token, EmailMessage,
+        EMAIL_BRANDING, MAX_VERIFICATION_EMAIL_RESENDS, VERIFICATION_EMAIL_RESEND_COOLDOWN_SECS,
+    },
+};
+
+use super::login::send_email;
+
+// Opened from the link in the verification email, so the token comes in the query
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Query(request): Query<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = decode_email_verification_token(&request.token)
+        .map_err(|_| AuthAPIError::InvalidVerificationLink)?;
+    let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidVerificationLink)?;
+
+    match state
+        .user_store
+        .write()
+        .await
+        .mark_email_verified(&email)
+        .await
+    {
+        Ok(()) => (),
+        // The account was deleted after the link was sent
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidVerificationLink),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email address verified".to_owned(),
+    });
+    Ok((http::StatusCode::OK, response))
+}
+
+// Always accepted, so the response doesn't tell which addresses have an account
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Counted for every address asked for, whether it has an account or not
+    match state
+        .verification_email_store
+        .write()
+        .await
+        .record_resend(
+            &email,
+            VERIFICATION_EMAIL_RESEND_COOLDOWN_SECS,
+            MAX_VERIFICATION_EMAIL_RESENDS,
+        )
+        .await
+    {
+        Ok(()) => (),
+        Err(VerificationEmailStoreError::ResendCooldown(secs)) => {
+            return Err(AuthAPIError::VerificationEmailCooldown(secs))
+        }
+        Err(VerificationEmailStoreError::TooManyResends) => {
+            return Err(AuthAPIError::TooManyVerificationEmails)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let user = state.user_store.read().await.get_user(&email).await;
+    match user {
+        Ok(user) if !user.email_verified => send_verification_email(&state, &email).await?,
+        Ok(_) | Err(UserStoreError::UserNotFound) => (),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    Ok(http::StatusCode::ACCEPTED)
+}
+
+// Emails the user a signed link that verifies their address
+pub(crate) async fn send_verification_email(
+    state: &AppState,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    let token =
+        generate_email_verification_token(email).map_err(|_| AuthAPIError::UnexpectedError)?;
+    let link = format!("{}/verify-email?token={}", EMAIL_BRANDING.url, token);
+
+    send_email(state, email, EmailMessage::VerifyEmail(link)).await
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
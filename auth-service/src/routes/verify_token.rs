@@ -7,9 +7,14 @@ pub async fn verify_token(
     State(state): State<AppState>,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    if validate_token(&request.token, state.banned_token_store)
-        .await
-        .is_err()
+    if validate_token(
+        &request.token,
+        state.banned_token_store,
+        state.session_store,
+        state.user_store,
+    )
+    .await
+    .is_err()
     {
         return Err(AuthAPIError::InvalidToken);
     }
@@ -0,0 +1,656 @@
+use axum::{
+    extract::{Path, State},
+    http::{self, HeaderMap},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        generate_user_handle, verify_assertion, verify_registration, AuthAPIError, Email,
+        LoginAttemptId, Password, PendingWebAuthnCeremony, RelyingParty, TwoFACodeStoreError,
+        WebAuthnCeremony, WebAuthnChallenge, WebAuthnChallengeStoreError, WebAuthnCredential,
+        WebAuthnCredentialStoreError, WebAuthnError, COSE_ALGORITHM_ES256,
+        WEBAUTHN_CHALLENGE_TTL_SECONDS,
+    },
+    utils::{
+        AuthenticatedUser, EmailMessage, SecurityNotice, WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID,
+        WEBAUTHN_RP_NAME,
+    },
+};
+
+use super::login::{handle_no_2fa, send_email, user_agent};
+
+// Returns the options to pass to `navigator.credentials.create()`. A passkey
+// logs in without the password, so the password is required to add one, like
+// for turning 2FA off.
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Json(request): Json<StartPasskeyRegistrationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    state
+        .user_store
+        .read()
+        .await
+        .validate_user(&email, &password)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let credentials = state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credentials(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Further passkeys share the handle of the first one
+    let user_handle = match credentials.first() {
+        Some(credential) => credential.user_handle.clone(),
+        None => generate_user_handle(),
+    };
+
+    // The authenticator refuses to create a second passkey for the same account
+    let exclude_credentials = credentials
+        .into_iter()
+        .map(|credential| CredentialDescriptor::new(credential.id))
+        .collect();
+
+    let challenge = WebAuthnChallenge::default();
+    let ceremony_id = start_ceremony(
+        &state,
+        challenge.clone(),
+        WebAuthnCeremony::Registration {
+            email: email.clone(),
+            user_handle: user_handle.clone(),
+        },
+    )
+    .await?;
+
+    Ok(Json(StartPasskeyRegistrationResponse {
+        ceremony_id,
+        public_key: CredentialCreationOptions {
+            challenge: challenge.as_ref().to_owned(),
+            rp: RelyingPartyEntity {
+                id: WEBAUTHN_RP_ID.clone(),
+                name: WEBAUTHN_RP_NAME.clone(),
+            },
+            user: UserEntity {
+                id: user_handle,
+                name: email.as_ref().to_owned(),
+                display_name: email.as_ref().to_owned(),
+            },
+            pub_key_cred_params: vec![CredentialParameters {
+                credential_type: PUBLIC_KEY.to_owned(),
+                alg: COSE_ALGORITHM_ES256,
+            }],
+            timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+            attestation: "none".to_owned(),
+            exclude_credentials,
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: "required".to_owned(),
+                user_verification: "preferred".to_owned(),
+            },
+        },
+    }))
+}
+
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Json(request): Json<FinishPasskeyRegistrationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let pending = take_ceremony(&state, &request.ceremony_id).await?;
+
+    let user_handle = match pending.ceremony {
+        WebAuthnCeremony::Registration {
+            email: ceremony_email,
+            user_handle,
+        } if ceremony_email == email => user_handle,
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    };
+
+    let key = verify_registration(
+        &relying_party(),
+        &pending.challenge,
+        &decode(&request.credential.response.client_data_json)?,
+        &decode(&request.credential.response.attestation_object)?,
+    )
+    .map_err(map_webauthn_error)?;
+
+    let credential_id = URL_SAFE_NO_PAD.encode(&key.credential_id);
+    if credential_id != request.credential.id {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let credential = WebAuthnCredential {
+        id: credential_id.clone(),
+        email: email.clone(),
+        user_handle,
+        public_key: key.public_key,
+        sign_count: key.sign_count,
+        created_at: Utc::now().timestamp(),
+    };
+
+    match state
+        .webauthn_credential_store
+        .write()
+        .await
+        .add_credential(credential)
+        .await
+    {
+        Ok(()) => (),
+        Err(WebAuthnCredentialStoreError::CredentialAlreadyExists) => {
+            return Err(AuthAPIError::PasskeyAlreadyRegistered)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    // Anyone holding the passkey can now log in without the password
+    let notice = EmailMessage::SecurityNotice(SecurityNotice::PasskeyAdded);
+    let _ = send_email(&state, &email, notice).await;
+
+    Ok((
+        http::StatusCode::CREATED,
+        Json(FinishPasskeyRegistrationResponse { credential_id }),
+    ))
+}
+
+// Returns the options to pass to `navigator.credentials.get()`. With the login
+// attempt of a password login the passkey is used as the second factor, without
+// a body it replaces the password.
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+    request: Option<Json<StartPasskeyLoginRequest>>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (ceremony, allow_credentials, user_verification) = match request {
+        Some(Json(request)) => {
+            let email =
+                Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+            let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+                .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+            ensure_login_attempt(&state, &email, &login_attempt_id).await?;
+
+            let credentials = state
+                .webauthn_credential_store
+                .read()
+                .await
+                .get_credentials(&email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            if credentials.is_empty() {
+                return Err(AuthAPIError::PasskeyNotRegistered);
+            }
+
+            let allow_credentials = credentials
+                .into_iter()
+                .map(|credential| CredentialDescriptor::new(credential.id))
+                .collect();
+            let ceremony = WebAuthnCeremony::SecondFactor {
+                email,
+                login_attempt_id,
+            };
+            (ceremony, allow_credentials, "preferred")
+        }
+        // The browser offers the passkeys it holds for this site
+        None => (WebAuthnCeremony::Passwordless, vec![], "required"),
+    };
+
+    let challenge = WebAuthnChallenge::default();
+    let ceremony_id = start_ceremony(&state, challenge.clone(), ceremony).await?;
+
+    Ok(Json(StartPasskeyLoginResponse {
+        ceremony_id,
+        public_key: CredentialRequestOptions {
+            challenge: challenge.as_ref().to_owned(),
+            rp_id: WEBAUTHN_RP_ID.clone(),
+            timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+            allow_credentials,
+            user_verification: user_verification.to_owned(),
+        },
+    }))
+}
+
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(request): Json<FinishPasskeyLoginRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match check_assertion(&state, &request).await {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let token_generation = {
+        let user_store = state.user_store.read().await;
+
+        // Like a password login, a passkey doesn't get unverified users in
+        match user_store.get_user(&email).await {
+            Ok(user) if user.email_verified => (),
+            Ok(_) => return (jar, Err(AuthAPIError::EmailNotVerified)),
+            Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        }
+
+        match user_store.get_token_generation(&email).await {
+            Ok(generation) => generation,
+            Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        }
+    };
+
+    handle_no_2fa(
+        &email,
+        token_generation,
+        user_agent(&headers),
+        request.return_token,
+        &state,
+        jar,
+    )
+    .await
+}
+
+pub async fn list_passkeys(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let passkeys = state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credentials(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?
+        .into_iter()
+        .map(|credential| PasskeyResponse {
+            id: credential.id,
+            created_at: credential.created_at,
+        })
+        .collect();
+
+    Ok(Json(ListPasskeysResponse { passkeys }))
+}
+
+pub async fn delete_passkey(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Path(credential_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut webauthn_credential_store = state.webauthn_credential_store.write().await;
+
+    // Users may only remove their own passkeys
+    match webauthn_credential_store
+        .get_credential(&credential_id)
+        .await
+    {
+        Ok(credential) if credential.email == email => (),
+        Ok(_) | Err(WebAuthnCredentialStoreError::CredentialNotFound) => {
+            return Err(AuthAPIError::PasskeyNotFound)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    webauthn_credential_store
+        .remove_credential(&credential_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+// Removes every passkey of the user, so none of them logs in anymore
+pub(crate) async fn remove_passkeys(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    state
+        .webauthn_credential_store
+        .write()
+        .await
+        .remove_credentials(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Verifies the signed challenge and returns the user the passkey belongs to
+async fn check_assertion(
+    state: &AppState,
+    request: &FinishPasskeyLoginRequest,
+) -> Result<Email, AuthAPIError> {
+    let response = &request.credential.response;
+    let client_data_json = decode(&response.client_data_json)?;
+    let authenticator_data = decode(&response.authenticator_data)?;
+    let signature = decode(&response.signature)?;
+
+    let pending = take_ceremony(state, &request.ceremony_id).await?;
+
+    let credential = match state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credential(&request.credential.id)
+        .await
+    {
+        Ok(credential) => credential,
+        Err(WebAuthnCredentialStoreError::CredentialNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    // Authenticators return the user handle of discoverable credentials
+    if let Some(handle) = &response.user_handle {
+        if handle != &credential.user_handle {
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+    }
+
+    let second_factor = match &pending.ceremony {
+        WebAuthnCeremony::SecondFactor {
+            email,
+            login_attempt_id,
+        } if email == &credential.email => {
+            ensure_login_attempt(state, email, login_attempt_id).await?;
+            Some(login_attempt_id)
+        }
+        WebAuthnCeremony::Passwordless => None,
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    };
+
+    // A passkey that replaces the password must also verify who is holding it
+    let sign_count = verify_assertion(
+        &relying_party(),
+        &pending.challenge,
+        &credential,
+        second_factor.is_none(),
+        &client_data_json,
+        &authenticator_data,
+        &signature,
+    )
+    .map_err(map_webauthn_error)?;
+
+    state
+        .webauthn_credential_store
+        .write()
+        .await
+        .update_sign_count(&credential.id, sign_count)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if let Some(login_attempt_id) = second_factor {
+        match state
+            .two_fa_code_store
+            .write()
+            .await
+            .remove_code(&credential.email, login_attempt_id)
+            .await
+        {
+            Ok(()) => (),
+            // Used up by a concurrent request in the meantime
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+                return Err(AuthAPIError::IncorrectCredentials)
+            }
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        }
+    }
+
+    Ok(credential.email)
+}
+
+// The login attempt must still be waiting for its second factor
+async fn ensure_login_attempt(
+    state: &AppState,
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+) -> Result<(), AuthAPIError> {
+    match state
+        .two_fa_code_store
+        .read()
+        .await
+        .contains_code(email, login_attempt_id)
+        .await
+    {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+async fn start_ceremony(
+    state: &AppState,
+    challenge: WebAuthnChallenge,
+    ceremony: WebAuthnCeremony,
+) -> Result<String, AuthAPIError> {
+    let ceremony_id = Uuid::new_v4().to_string();
+
+    state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .add_challenge(
+            &ceremony_id,
+            PendingWebAuthnCeremony {
+                challenge,
+                ceremony,
+            },
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(ceremony_id)
+}
+
+async fn take_ceremony(
+    state: &AppState,
+    ceremony_id: &str,
+) -> Result<PendingWebAuthnCeremony, AuthAPIError> {
+    match state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .take_challenge(ceremony_id)
+        .await
+    {
+        Ok(pending) => Ok(pending),
+        Err(WebAuthnChallengeStoreError::ChallengeNotFound) => {
+            Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+fn relying_party() -> RelyingParty<'static> {
+    RelyingParty {
+        id: &WEBAUTHN_RP_ID,
+        origin: &WEBAUTHN_ORIGIN,
+    }
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, AuthAPIError> {
+    URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+}
+
+fn map_webauthn_error(error: WebAuthnError) -> AuthAPIError {
+    match error {
+        WebAuthnError::MalformedResponse | WebAuthnError::UnsupportedKey => {
+            AuthAPIError::InvalidCredentials
+        }
+        _ => AuthAPIError::IncorrectCredentials,
+    }
+}
+
+const PUBLIC_KEY: &str = "public-key";
+
+#[derive(Deserialize)]
+pub struct StartPasskeyRegistrationRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StartPasskeyRegistrationResponse {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    #[serde(rename = "publicKey")]
+    pub public_key: CredentialCreationOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingPartyEntity,
+    pub user: UserEntity,
+    #[serde(rename = "pubKeyCredParams")]
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: u64,
+    pub attestation: String,
+    #[serde(rename = "excludeCredentials")]
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "authenticatorSelection")]
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelyingPartyEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+}
+
+impl CredentialDescriptor {
+    fn new(id: String) -> Self {
+        Self {
+            credential_type: PUBLIC_KEY.to_owned(),
+            id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticatorSelection {
+    #[serde(rename = "residentKey")]
+    pub resident_key: String,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+#[derive(Deserialize)]
+pub struct FinishPasskeyRegistrationRequest {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    pub credential: RegistrationCredential,
+}
+
+#[derive(Deserialize)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Deserialize)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FinishPasskeyRegistrationResponse {
+    #[serde(rename = "credentialId")]
+    pub credential_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskeyLoginRequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StartPasskeyLoginResponse {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    #[serde(rename = "publicKey")]
+    pub public_key: CredentialRequestOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialRequestOptions {
+    pub challenge: String,
+    #[serde(rename = "rpId")]
+    pub rp_id: String,
+    pub timeout: u64,
+    #[serde(rename = "allowCredentials")]
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+#[derive(Deserialize)]
+pub struct FinishPasskeyLoginRequest {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    pub credential: AssertionCredential,
+    #[serde(default, rename = "returnToken")]
+    pub return_token: bool,
+}
+
+#[derive(Deserialize)]
+pub struct AssertionCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Deserialize)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+    #[serde(default, rename = "userHandle")]
+    pub user_handle: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListPasskeysResponse {
+    pub passkeys: Vec<PasskeyResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyResponse {
+    pub id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
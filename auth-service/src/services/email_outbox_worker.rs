@@ -0,0 +1,312 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{
+    app_state::{EmailClientType, EmailOutboxStoreType},
+    domain::{EmailClientError, EmailOutboxStoreError, OutboxEmail},
+};
+
+pub struct EmailOutboxSettings {
+    pub poll_interval: Duration,
+    // Most emails sent per poll
+    pub batch_size: u32,
+    // Attempts before an email that keeps failing is dead-lettered
+    pub max_attempts: u32,
+    // Wait before the first retry, doubled after every failed attempt
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
+    // How long a claimed email is hidden from other workers while it's being sent
+    pub lease: Duration,
+    // How long sent and dead-lettered emails are kept, and how often they're cleaned up
+    pub retention: Duration,
+    pub cleanup_interval: Duration,
+}
+
+// Delivers the emails queued in the outbox, so request handlers never wait on
+// (or fail because of) the email provider
+pub struct EmailOutboxWorker {
+    outbox: EmailOutboxStoreType,
+    email_client: EmailClientType,
+    settings: EmailOutboxSettings,
+}
+
+impl EmailOutboxWorker {
+    pub fn new(
+        outbox: EmailOutboxStoreType,
+        email_client: EmailClientType,
+        settings: EmailOutboxSettings,
+    ) -> Self {
+        Self {
+            outbox,
+            email_client,
+            settings,
+        }
+    }
+
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.settings.poll_interval);
+        let mut cleanup = tokio::time::interval(self.settings.cleanup_interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(e) = self.deliver_due().await {
+                        println!("Failed to deliver queued emails: {:?}", e);
+                    }
+                }
+                _ = cleanup.tick() => {
+                    if let Err(e) = self.delete_finished().await {
+                        println!("Failed to clean up the email outbox: {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    // Send the emails that are due, up to a batch, returning how many were attempted
+    pub async fn deliver_due(&self) -> Result<usize, EmailOutboxStoreError> {
+        let started = Utc::now().timestamp_millis();
+        let mut count = 0;
+        while count < self.settings.batch_size as usize {
+            // Claimed one at a time, so the lease only has to cover a single send
+            let lease_until = Utc::now().timestamp_millis() + millis(self.settings.lease);
+            let claimed = self
+                .outbox
+                .write()
+                .await
+                .claim_due(started, lease_until, 1)
+                .await?;
+            let Some(email) = claimed.into_iter().next() else {
+                break;
+            };
+
+            self.deliver(email, started).await?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    // Delete the sent and dead-lettered emails that are past retention
+    pub async fn delete_finished(&self) -> Result<u64, EmailOutboxStoreError> {
+        let before = Utc::now().timestamp_millis() - millis(self.settings.retention);
+        self.outbox.write().await.delete_finished(before).await
+    }
+
+    async fn deliver(&self, email: OutboxEmail, started: i64) -> Result<(), EmailOutboxStoreError> {
+        let result = self
+            .email_client
+            .send_email(&email.recipient, &email.content)
+            .await;
+        let mut outbox = self.outbox.write().await;
+
+        match result {
+            Ok(()) => {
+                outbox
+                    .mark_sent(&email.id, Utc::now().timestamp_millis())
+                    .await
+            }
+            Err(EmailClientError::Unavailable(e))
+                if email.attempts < self.settings.max_attempts =>
+            {
+                // Not before the next poll, which would otherwise claim it again straight away
+                let retry_at = (Utc::now().timestamp_millis()
+                    + millis(self.retry_delay(email.attempts)))
+                .max(started + 1);
+                outbox.retry_later(&email.id, retry_at, &e).await
+            }
+            Err(
+                EmailClientError::InvalidEmail(e)
+                | EmailClientError::Rejected(e)
+                | EmailClientError::Unavailable(e),
+            ) => {
+                println!("Gave up on email {}: {}", email.id, e);
+                outbox.dead_letter(&email.id, &e).await
+            }
+        }
+    }
+
+    fn retry_delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.settings
+            .retry_delay
+            .saturating_mul(factor)
+            .min(self.settings.max_retry_delay)
+    }
+}
+
+fn millis(duration: Duration) -> i64 {
+    duration.as_millis().try_into().unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::sync::RwLock;
+
+    use crate::{
+        domain::{Email, EmailClient, EmailContent, EmailOutboxStore, OutboxEmailStatus},
+        services::HashmapEmailOutboxStore,
+    };
+
+    use super::*;
+
+    // Fails the first `failures` sends with the given error, then succeeds
+    struct FlakyEmailClient {
+        failures: usize,
+        error: fn(String) -> EmailClientError,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailClient for FlakyEmailClient {
+        async fn send_email(
+            &self,
+            _recipient: &Email,
+            _content: &EmailContent,
+        ) -> Result<(), EmailClientError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call < self.failures {
+                return Err((self.error)("failed".to_owned()));
+            }
+            Ok(())
+        }
+    }
+
+    fn settings(max_attempts: u32) -> EmailOutboxSettings {
+        EmailOutboxSettings {
+            poll_interval: Duration::from_millis(10),
+            batch_size: 10,
+            max_attempts,
+            retry_delay: Duration::ZERO,
+            max_retry_delay: Duration::ZERO,
+            lease: Duration::from_secs(30),
+            retention: Duration::from_secs(3600),
+            cleanup_interval: Duration::from_secs(3600),
+        }
+    }
+
+    async fn setup(
+        client: FlakyEmailClient,
+        max_attempts: u32,
+    ) -> (EmailOutboxWorker, Arc<FlakyEmailClient>, String) {
+        let outbox = Arc::new(RwLock::new(HashmapEmailOutboxStore::default()));
+        let email = OutboxEmail::new(
+            Email::parse("test@example.com".to_owned()).unwrap(),
+            EmailContent {
+                subject: "Subject".to_owned(),
+                html_body: "<p>Body</p>".to_owned(),
+                text_body: "Body".to_owned(),
+            },
+        );
+        let id = email.id.clone();
+        outbox.write().await.enqueue(email).await.unwrap();
+
+        let client = Arc::new(client);
+        let worker = EmailOutboxWorker::new(outbox, client.clone(), settings(max_attempts));
+        (worker, client, id)
+    }
+
+    fn flaky(failures: usize, error: fn(String) -> EmailClientError) -> FlakyEmailClient {
+        FlakyEmailClient {
+            failures,
+            error,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    // Retries are never due in the poll that made them
+    async fn next_poll() {
+        tokio::time::sleep(Duration::from_millis(2)).await;
+    }
+
+    async fn status(worker: &EmailOutboxWorker, id: &str) -> OutboxEmailStatus {
+        worker.outbox.read().await.get_status(id).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delivers_queued_email_once() {
+        let (worker, client, id) = setup(flaky(0, EmailClientError::Unavailable), 3).await;
+
+        assert_eq!(worker.deliver_due().await, Ok(1));
+        assert_eq!(worker.deliver_due().await, Ok(0));
+
+        assert_eq!(status(&worker, &id).await, OutboxEmailStatus::Sent);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_delivers_at_most_a_batch_per_poll() {
+        let (mut worker, client, _) = setup(flaky(0, EmailClientError::Unavailable), 3).await;
+        worker.settings.batch_size = 2;
+        for _ in 0..2 {
+            let email = OutboxEmail::new(
+                Email::parse("test@example.com".to_owned()).unwrap(),
+                EmailContent {
+                    subject: "Subject".to_owned(),
+                    html_body: "<p>Body</p>".to_owned(),
+                    text_body: "Body".to_owned(),
+                },
+            );
+            worker.outbox.write().await.enqueue(email).await.unwrap();
+        }
+
+        assert_eq!(worker.deliver_due().await, Ok(2));
+        assert_eq!(worker.deliver_due().await, Ok(1));
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retries_while_provider_is_unavailable() {
+        let (worker, client, id) = setup(flaky(2, EmailClientError::Unavailable), 3).await;
+
+        for _ in 0..3 {
+            worker.deliver_due().await.unwrap();
+            next_poll().await;
+        }
+
+        assert_eq!(status(&worker, &id).await, OutboxEmailStatus::Sent);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters_after_max_attempts() {
+        let (worker, client, id) = setup(flaky(usize::MAX, EmailClientError::Unavailable), 3).await;
+
+        for _ in 0..5 {
+            worker.deliver_due().await.unwrap();
+            next_poll().await;
+        }
+
+        assert_eq!(status(&worker, &id).await, OutboxEmailStatus::Dead);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters_rejected_email_without_retrying() {
+        let (worker, client, id) = setup(flaky(usize::MAX, EmailClientError::Rejected), 3).await;
+
+        worker.deliver_due().await.unwrap();
+        worker.deliver_due().await.unwrap();
+
+        assert_eq!(status(&worker, &id).await, OutboxEmailStatus::Dead);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_delay_backs_off_up_to_the_cap() {
+        let (mut worker, _, _) = setup(flaky(0, EmailClientError::Unavailable), 3).await;
+        worker.settings.retry_delay = Duration::from_secs(1);
+        worker.settings.max_retry_delay = Duration::from_secs(5);
+
+        assert_eq!(worker.retry_delay(1), Duration::from_secs(1));
+        assert_eq!(worker.retry_delay(2), Duration::from_secs(2));
+        assert_eq!(worker.retry_delay(3), Duration::from_secs(4));
+        assert_eq!(worker.retry_delay(4), Duration::from_secs(5));
+        assert_eq!(worker.retry_delay(40), Duration::from_secs(5));
+    }
+}
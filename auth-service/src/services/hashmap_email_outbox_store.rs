@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{EmailOutboxStore, EmailOutboxStoreError, OutboxEmail, OutboxEmailStatus};
+
+#[derive(Default)]
+pub struct HashmapEmailOutboxStore {
+    emails: HashMap<String, StoredEmail>,
+}
+
+struct StoredEmail {
+    email: OutboxEmail,
+    status: OutboxEmailStatus,
+    next_attempt_at: i64,
+    // Queue order, emails due at the same time go out oldest first
+    sequence: usize,
+    created_at: i64,
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for HashmapEmailOutboxStore {
+    async fn enqueue(&mut self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError> {
+        let sequence = self.emails.len();
+        self.emails.entry(email.id.clone()).or_insert(StoredEmail {
+            email,
+            status: OutboxEmailStatus::Pending,
+            next_attempt_at: i64::MIN,
+            sequence,
+            created_at: Utc::now().timestamp_millis(),
+        });
+        Ok(())
+    }
+
+    async fn claim_due(
+        &mut self,
+        now: i64,
+        lease_until: i64,
+        limit: u32,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let mut due: Vec<&mut StoredEmail> = self
+            .emails
+            .values_mut()
+            .filter(|stored| {
+                stored.status == OutboxEmailStatus::Pending && stored.next_attempt_at <= now
+            })
+            .collect();
+        due.sort_by_key(|stored| (stored.next_attempt_at, stored.sequence));
+
+        Ok(due
+            .into_iter()
+            .take(limit as usize)
+            .map(|stored| {
+                stored.email.attempts += 1;
+                stored.next_attempt_at = lease_until;
+                stored.email.clone()
+            })
+            .collect())
+    }
+
+    async fn mark_sent(&mut self, id: &str, _now: i64) -> Result<(), EmailOutboxStoreError> {
+        self.set_status(id, OutboxEmailStatus::Sent)
+    }
+
+    async fn retry_later(
+        &mut self,
+        id: &str,
+        retry_at: i64,
+        _error: &str,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let stored = self
+            .emails
+            .get_mut(id)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)?;
+        stored.next_attempt_at = retry_at;
+        Ok(())
+    }
+
+    async fn dead_letter(&mut self, id: &str, _error: &str) -> Result<(), EmailOutboxStoreError> {
+        self.set_status(id, OutboxEmailStatus::Dead)
+    }
+
+    async fn get_status(&self, id: &str) -> Result<OutboxEmailStatus, EmailOutboxStoreError> {
+        self.emails
+            .get(id)
+            .map(|stored| stored.status)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)
+    }
+
+    async fn delete_finished(&mut self, before: i64) -> Result<u64, EmailOutboxStoreError> {
+        let count = self.emails.len();
+        self.emails.retain(|_, stored| {
+            stored.status == OutboxEmailStatus::Pending || stored.created_at >= before
+        });
+        Ok((count - self.emails.len()) as u64)
+    }
+}
+
+impl HashmapEmailOutboxStore {
+    fn set_status(
+        &mut self,
+        id: &str,
+        status: OutboxEmailStatus,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let stored = self
+            .emails
+            .get_mut(id)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)?;
+        stored.status = status;
+        // Finished emails don't need their codes and links anymore
+        stored.email.content.html_body.clear();
+        stored.email.content.text_body.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{Email, EmailContent};
+
+    use super::*;
+
+    fn outbox_email() -> OutboxEmail {
+        OutboxEmail::new(
+            Email::parse("test@example.com".to_owned()).unwrap(),
+            EmailContent {
+                subject: "Subject".to_owned(),
+                html_body: "<p>Body</p>".to_owned(),
+                text_body: "Body".to_owned(),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_is_idempotent() {
+        let mut store = HashmapEmailOutboxStore::default();
+        let email = outbox_email();
+
+        store.enqueue(email.clone()).await.unwrap();
+        store.enqueue(email.clone()).await.unwrap();
+
+        let claimed = store.claim_due(0, 60, 10).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].id, email.id);
+        assert_eq!(claimed[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_claimed_emails_are_leased() {
+        let mut store = HashmapEmailOutboxStore::default();
+        let email = outbox_email();
+        store.enqueue(email.clone()).await.unwrap();
+
+        assert_eq!(store.claim_due(0, 60, 10).await.unwrap().len(), 1);
+        assert!(store.claim_due(30, 90, 10).await.unwrap().is_empty());
+
+        // The worker that claimed it didn't report back in time
+        let claimed = store.claim_due(60, 120, 10).await.unwrap();
+        assert_eq!(claimed[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_sent_and_dead_emails_are_not_claimed() {
+        let mut store = HashmapEmailOutboxStore::default();
+        let sent = outbox_email();
+        let dead = outbox_email();
+        let retried = outbox_email();
+        for email in [&sent, &dead, &retried] {
+            store.enqueue(email.clone()).await.unwrap();
+        }
+        store.claim_due(0, 60, 10).await.unwrap();
+
+        store.mark_sent(&sent.id, 0).await.unwrap();
+        store.dead_letter(&dead.id, "rejected").await.unwrap();
+        store
+            .retry_later(&retried.id, 10, "unavailable")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_status(&sent.id).await,
+            Ok(OutboxEmailStatus::Sent)
+        );
+        assert_eq!(
+            store.get_status(&dead.id).await,
+            Ok(OutboxEmailStatus::Dead)
+        );
+        assert_eq!(
+            store.get_status(&retried.id).await,
+            Ok(OutboxEmailStatus::Pending)
+        );
+
+        assert!(store.claim_due(9, 70, 10).await.unwrap().is_empty());
+        let claimed = store.claim_due(10, 70, 10).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].id, retried.id);
+    }
+
+    #[tokio::test]
+    async fn test_finished_emails_are_cleaned_up() {
+        let mut store = HashmapEmailOutboxStore::default();
+        let sent = outbox_email();
+        let dead = outbox_email();
+        let pending = outbox_email();
+        for email in [&sent, &dead, &pending] {
+            store.enqueue(email.clone()).await.unwrap();
+        }
+        store.mark_sent(&sent.id, 0).await.unwrap();
+        store.dead_letter(&dead.id, "rejected").await.unwrap();
+
+        let stored = &store.emails[&sent.id].email.content;
+        assert!(stored.html_body.is_empty() && stored.text_body.is_empty());
+
+        assert_eq!(store.delete_finished(i64::MIN).await, Ok(0));
+        assert_eq!(store.delete_finished(i64::MAX).await, Ok(2));
+        assert_eq!(
+            store.get_status(&sent.id).await,
+            Err(EmailOutboxStoreError::EmailNotFound)
+        );
+        assert_eq!(
+            store.get_status(&pending.id).await,
+            Ok(OutboxEmailStatus::Pending)
+        );
+    }
+}
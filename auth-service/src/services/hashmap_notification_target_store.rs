@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    Email, LoginAttemptId, NotificationTarget, NotificationTargetStore,
+    NotificationTargetStoreError, TwoFAMethod,
+};
+
+#[derive(Default)]
+struct NotificationTargets {
+    target: Option<NotificationTarget>,
+    pending_target: Option<(TwoFAMethod, NotificationTarget, LoginAttemptId)>,
+}
+
+#[derive(Default)]
+pub struct HashmapNotificationTargetStore {
+    targets: HashMap<Email, NotificationTargets>,
+}
+
+#[async_trait::async_trait]
+impl NotificationTargetStore for HashmapNotificationTargetStore {
+    async fn set_pending_target(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+        target: NotificationTarget,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), NotificationTargetStoreError> {
+        self.targets.entry(email.clone()).or_default().pending_target =
+            Some((two_fa_method, target, login_attempt_id.clone()));
+        Ok(())
+    }
+
+    async fn get_pending_target(
+        &self,
+        email: &Email,
+    ) -> Result<(TwoFAMethod, NotificationTarget, LoginAttemptId), NotificationTargetStoreError>
+    {
+        self.targets
+            .get(email)
+            .and_then(|targets| targets.pending_target.clone())
+            .ok_or(NotificationTargetStoreError::TargetNotFound)
+    }
+
+    async fn activate_pending_target(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), NotificationTargetStoreError> {
+        let targets = self
+            .targets
+            .get_mut(email)
+            .filter(|targets| {
+                matches!(&targets.pending_target, Some((_, _, id)) if id == login_attempt_id)
+            })
+            .ok_or(NotificationTargetStoreError::TargetNotFound)?;
+
+        targets.target = targets.pending_target.take().map(|(_, target, _)| target);
+        Ok(())
+    }
+
+    async fn get_target(
+        &self,
+        email: &Email,
+    ) -> Result<NotificationTarget, NotificationTargetStoreError> {
+        self.targets
+            .get(email)
+            .and_then(|targets| targets.target.clone())
+            .ok_or(NotificationTargetStoreError::TargetNotFound)
+    }
+
+    async fn remove_targets(&mut self, email: &Email) -> Result<(), NotificationTargetStoreError> {
+        self.targets.remove(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phone_number(number: &str) -> NotificationTarget {
+        NotificationTarget::parse(TwoFAMethod::Sms, number.to_owned()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_activate_pending_target() {
+        let mut store = HashmapNotificationTargetStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let target = phone_number("+15551234567");
+        let login_attempt_id = LoginAttemptId::default();
+
+        let result = store.activate_pending_target(&email, &login_attempt_id).await;
+        assert_eq!(result, Err(NotificationTargetStoreError::TargetNotFound));
+
+        store
+            .set_pending_target(&email, TwoFAMethod::Sms, target.clone(), &login_attempt_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_pending_target(&email).await,
+            Ok((TwoFAMethod::Sms, target.clone(), login_attempt_id.clone()))
+        );
+        assert_eq!(
+            store.get_target(&email).await,
+            Err(NotificationTargetStoreError::TargetNotFound)
+        );
+
+        // Only the login attempt the code was sent with can confirm the target
+        let result = store
+            .activate_pending_target(&email, &LoginAttemptId::default())
+            .await;
+        assert_eq!(result, Err(NotificationTargetStoreError::TargetNotFound));
+
+        let result = store.activate_pending_target(&email, &login_attempt_id).await;
+        assert!(result.is_ok());
+        assert_eq!(store.get_target(&email).await, Ok(target));
+        assert_eq!(
+            store.get_pending_target(&email).await,
+            Err(NotificationTargetStoreError::TargetNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_target_does_not_replace_active_target() {
+        let mut store = HashmapNotificationTargetStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let target = phone_number("+15551234567");
+        let login_attempt_id = LoginAttemptId::default();
+
+        store
+            .set_pending_target(&email, TwoFAMethod::Sms, target.clone(), &login_attempt_id)
+            .await
+            .unwrap();
+        store.activate_pending_target(&email, &login_attempt_id).await.unwrap();
+
+        store
+            .set_pending_target(
+                &email,
+                TwoFAMethod::Sms,
+                phone_number("+15557654321"),
+                &LoginAttemptId::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(store.get_target(&email).await, Ok(target));
+    }
+
+    #[tokio::test]
+    async fn test_remove_targets() {
+        let mut store = HashmapNotificationTargetStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+
+        store
+            .set_pending_target(
+                &email,
+                TwoFAMethod::Sms,
+                phone_number("+15551234567"),
+                &login_attempt_id,
+            )
+            .await
+            .unwrap();
+        store.activate_pending_target(&email, &login_attempt_id).await.unwrap();
+        store
+            .set_pending_target(
+                &email,
+                TwoFAMethod::Sms,
+                phone_number("+15557654321"),
+                &LoginAttemptId::default(),
+            )
+            .await
+            .unwrap();
+
+        let result = store.remove_targets(&email).await;
+        assert!(result.is_ok());
+        assert_eq!(
+            store.get_target(&email).await,
+            Err(NotificationTargetStoreError::TargetNotFound)
+        );
+        assert_eq!(
+            store.get_pending_target(&email).await,
+            Err(NotificationTargetStoreError::TargetNotFound)
+        );
+    }
+}
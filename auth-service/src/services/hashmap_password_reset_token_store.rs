@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    Email, PasswordReset, PasswordResetToken, PasswordResetTokenStore,
+    PasswordResetTokenStoreError, PASSWORD_RESET_EMAIL_WINDOW_SECONDS,
+    PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    // Keyed by token hash, with the unix timestamp the token expires at
+    tokens: HashMap<String, (PasswordReset, i64)>,
+    emails: HashMap<Email, SentEmails>,
+}
+
+struct SentEmails {
+    window_started_at: i64,
+    sent_at: i64,
+    count: u32,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        token: &PasswordResetToken,
+        reset: PasswordReset,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let expires_at = Utc::now().timestamp() + PASSWORD_RESET_TOKEN_TTL_SECONDS as i64;
+        self.tokens.insert(token.hash(), (reset, expires_at));
+        Ok(())
+    }
+
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<PasswordReset, PasswordResetTokenStoreError> {
+        match self.tokens.remove(&token.hash()) {
+            Some((reset, expires_at)) if expires_at > Utc::now().timestamp() => Ok(reset),
+            _ => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn record_reset_email(
+        &mut self,
+        email: &Email,
+        cooldown_secs: u64,
+        max_emails: u32,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let now = Utc::now().timestamp();
+        let window_ended = |sent: &SentEmails| {
+            sent.window_started_at + PASSWORD_RESET_EMAIL_WINDOW_SECONDS as i64 <= now
+        };
+
+        let sent = match self.emails.get_mut(email) {
+            Some(sent) if !window_ended(sent) => sent,
+            _ => {
+                self.emails.insert(
+                    email.clone(),
+                    SentEmails {
+                        window_started_at: now,
+                        sent_at: now,
+                        count: 1,
+                    },
+                );
+                return Ok(());
+            }
+        };
+
+        if sent.count >= max_emails {
+            return Err(PasswordResetTokenStoreError::TooManyResetEmails);
+        }
+        let wait = sent.sent_at + cooldown_secs as i64 - now;
+        if wait > 0 {
+            return Err(PasswordResetTokenStoreError::ResetEmailCooldown(
+                wait as u64,
+            ));
+        }
+
+        sent.sent_at = now;
+        sent.count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse("test@example.com".to_owned()).unwrap()
+    }
+
+    fn reset() -> PasswordReset {
+        PasswordReset {
+            email: email(),
+            token_generation: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_token_only_once() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let token = PasswordResetToken::default();
+
+        let result = store.add_token(&token, reset()).await;
+        assert!(result.is_ok());
+
+        let result = store.take_token(&PasswordResetToken::default()).await;
+        assert_eq!(result, Err(PasswordResetTokenStoreError::TokenNotFound));
+
+        let result = store.take_token(&token).await;
+        assert_eq!(result, Ok(reset()));
+
+        let result = store.take_token(&token).await;
+        assert_eq!(result, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_take_expired_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let token = PasswordResetToken::default();
+        store
+            .tokens
+            .insert(token.hash(), (reset(), Utc::now().timestamp()));
+
+        let result = store.take_token(&token).await;
+        assert_eq!(result, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_record_reset_email_cooldown() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+
+        assert_eq!(store.record_reset_email(&email(), 60, 5).await, Ok(()));
+        let result = store.record_reset_email(&email(), 60, 5).await;
+        assert!(matches!(
+            result,
+            Err(PasswordResetTokenStoreError::ResetEmailCooldown(wait)) if wait > 0 && wait <= 60
+        ));
+
+        let other = Email::parse("other@example.com".to_owned()).unwrap();
+        assert_eq!(store.record_reset_email(&other, 60, 5).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_record_reset_email_limit() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+
+        for _ in 0..3 {
+            assert_eq!(store.record_reset_email(&email(), 0, 3).await, Ok(()));
+        }
+        let result = store.record_reset_email(&email(), 0, 3).await;
+        assert_eq!(
+            result,
+            Err(PasswordResetTokenStoreError::TooManyResetEmails)
+        );
+
+        // The limit starts over once the window passed
+        store.emails.get_mut(&email()).unwrap().window_started_at -=
+            PASSWORD_RESET_EMAIL_WINDOW_SECONDS as i64;
+        assert_eq!(store.record_reset_email(&email(), 0, 3).await, Ok(()));
+    }
+}
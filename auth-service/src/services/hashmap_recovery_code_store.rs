@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::{Email, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError};
+
+#[derive(Default)]
+pub struct HashmapRecoveryCodeStore {
+    // email -> hashes of the recovery codes not used yet
+    codes: HashMap<Email, HashSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let hashes = codes.iter().map(RecoveryCode::hash).collect();
+        self.codes.insert(email.clone(), hashes);
+        Ok(())
+    }
+
+    async fn use_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let removed = self
+            .codes
+            .get_mut(email)
+            .is_some_and(|hashes| hashes.remove(&code.hash()));
+
+        match removed {
+            true => Ok(()),
+            false => Err(RecoveryCodeStoreError::CodeNotFound),
+        }
+    }
+
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        Ok(self.codes.get(email).map_or(0, HashSet::len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_use_code() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let codes = RecoveryCode::generate_set();
+
+        store.set_codes(&email, &codes).await.unwrap();
+        assert_eq!(store.count_codes(&email).await, Ok(codes.len()));
+
+        let result = store.use_code(&email, &codes[0]).await;
+        assert!(result.is_ok());
+        assert_eq!(store.count_codes(&email).await, Ok(codes.len() - 1));
+
+        let result = store.use_code(&email, &codes[0]).await;
+        assert_eq!(result, Err(RecoveryCodeStoreError::CodeNotFound));
+
+        let result = store.use_code(&email, &RecoveryCode::default()).await;
+        assert_eq!(result, Err(RecoveryCodeStoreError::CodeNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_set_codes_replaces_old_codes() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+        let old_codes = RecoveryCode::generate_set();
+        let new_codes = RecoveryCode::generate_set();
+
+        assert_eq!(store.count_codes(&email).await, Ok(0));
+
+        store.set_codes(&email, &old_codes).await.unwrap();
+        store.set_codes(&email, &new_codes).await.unwrap();
+
+        let result = store.use_code(&email, &old_codes[0]).await;
+        assert_eq!(result, Err(RecoveryCodeStoreError::CodeNotFound));
+
+        let result = store.use_code(&other_email, &new_codes[0]).await;
+        assert_eq!(result, Err(RecoveryCodeStoreError::CodeNotFound));
+
+        let result = store.use_code(&email, &new_codes[0]).await;
+        assert!(result.is_ok());
+    }
+}
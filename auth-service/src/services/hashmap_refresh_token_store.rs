@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use crate::domain::{RefreshTokenStore, RefreshTokenStoreError};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    // token family -> id of the only refresh token of that family still allowed to be used
+    families: HashMap<String, String>,
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn set_current_token(
+        &mut self,
+        family: &str,
+        token_id: &str,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.families.insert(family.to_owned(), token_id.to_owned());
+        Ok(())
+    }
+
+    async fn get_current_token(&self, family: &str) -> Result<String, RefreshTokenStoreError> {
+        match self.families.get(family) {
+            Some(token_id) => Ok(token_id.clone()),
+            None => Err(RefreshTokenStoreError::FamilyNotFound),
+        }
+    }
+
+    async fn revoke_family(&mut self, family: &str) -> Result<(), RefreshTokenStoreError> {
+        self.families.remove(family);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_set_current_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+
+        let result = store.set_current_token("family", "token_1").await;
+        assert!(result.is_ok());
+        assert_eq!(store.families.get("family"), Some(&"token_1".to_owned()));
+
+        let result = store.set_current_token("family", "token_2").await;
+        assert!(result.is_ok());
+        assert_eq!(store.families.get("family"), Some(&"token_2".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_get_current_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        store
+            .families
+            .insert("family".to_owned(), "token_1".to_owned());
+
+        let result = store.get_current_token("family").await;
+        assert_eq!(result, Ok("token_1".to_owned()));
+
+        let result = store.get_current_token("unknown_family").await;
+        assert_eq!(result, Err(RefreshTokenStoreError::FamilyNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        store
+            .families
+            .insert("family".to_owned(), "token_1".to_owned());
+
+        let result = store.revoke_family("family").await;
+        assert!(result.is_ok());
+        assert_eq!(
+            store.get_current_token("family").await,
+            Err(RefreshTokenStoreError::FamilyNotFound)
+        );
+    }
+}
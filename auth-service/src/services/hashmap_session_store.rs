@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::domain::{Email, Session, SessionStore, SessionStoreError};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<String, Session>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: &str) -> Result<Session, SessionStoreError> {
+        match self.sessions.get(session_id) {
+            Some(session) => Ok(session.clone()),
+            None => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        Ok(self
+            .sessions
+            .values()
+            .filter(|session| &session.email == email)
+            .cloned()
+            .collect())
+    }
+
+    async fn refresh_session(&mut self, session_id: &str) -> Result<(), SessionStoreError> {
+        match self.sessions.contains_key(session_id) {
+            true => Ok(()),
+            false => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_session(&mut self, session_id: &str) -> Result<(), SessionStoreError> {
+        self.sessions.remove(session_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_get_session() {
+        let mut store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let session = Session::new(email, Some("test-agent".to_owned()));
+
+        let result = store.add_session(session.clone()).await;
+        assert!(result.is_ok());
+
+        let result = store.get_session(&session.id).await;
+        assert_eq!(result, Ok(session));
+
+        let result = store.get_session("unknown_session").await;
+        assert_eq!(result, Err(SessionStoreError::SessionNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_get_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        store.add_session(Session::new(email.clone(), None)).await.unwrap();
+        store.add_session(Session::new(email.clone(), None)).await.unwrap();
+        store.add_session(Session::new(other_email, None)).await.unwrap();
+
+        let sessions = store.get_sessions(&email).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.iter().all(|session| session.email == email));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_session() {
+        let mut store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let session = Session::new(email, None);
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(store.refresh_session(&session.id).await, Ok(()));
+        assert_eq!(
+            store.refresh_session("unknown_session").await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_session() {
+        let mut store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let session = Session::new(email.clone(), None);
+        store.add_session(session.clone()).await.unwrap();
+
+        let result = store.remove_session(&session.id).await;
+        assert!(result.is_ok());
+        assert_eq!(
+            store.get_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert!(store.get_sessions(&email).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use crate::domain::{Email, TotpSecret, TotpSecretStore, TotpSecretStoreError};
+
+#[derive(Default)]
+struct TotpSecrets {
+    secret: Option<TotpSecret>,
+    pending_secret: Option<TotpSecret>,
+    last_used_step: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct HashmapTotpSecretStore {
+    secrets: HashMap<Email, TotpSecrets>,
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for HashmapTotpSecretStore {
+    async fn set_pending_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        self.secrets.entry(email.clone()).or_default().pending_secret = Some(secret);
+        Ok(())
+    }
+
+    async fn get_pending_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        self.secrets
+            .get(email)
+            .and_then(|secrets| secrets.pending_secret.clone())
+            .ok_or(TotpSecretStoreError::SecretNotFound)
+    }
+
+    async fn activate_pending_secret(
+        &mut self,
+        email: &Email,
+        step: u64,
+    ) -> Result<(), TotpSecretStoreError> {
+        let secrets = self
+            .secrets
+            .get_mut(email)
+            .filter(|secrets| secrets.pending_secret.is_some())
+            .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        secrets.secret = secrets.pending_secret.take();
+        secrets.last_used_step = Some(step);
+        Ok(())
+    }
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        self.secrets
+            .get(email)
+            .and_then(|secrets| secrets.secret.clone())
+            .ok_or(TotpSecretStoreError::SecretNotFound)
+    }
+
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError> {
+        let secrets = self
+            .secrets
+            .get_mut(email)
+            .filter(|secrets| secrets.secret.is_some())
+            .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        if secrets.last_used_step.is_some_and(|last_used| step <= last_used) {
+            return Err(TotpSecretStoreError::CodeAlreadyUsed);
+        }
+
+        secrets.last_used_step = Some(step);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_activate_pending_secret() {
+        let mut store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let secret = TotpSecret::default();
+
+        let result = store.activate_pending_secret(&email, 1).await;
+        assert_eq!(result, Err(TotpSecretStoreError::SecretNotFound));
+
+        store.set_pending_secret(&email, secret.clone()).await.unwrap();
+        assert_eq!(store.get_pending_secret(&email).await, Ok(secret.clone()));
+        assert_eq!(
+            store.get_secret(&email).await,
+            Err(TotpSecretStoreError::SecretNotFound)
+        );
+
+        let result = store.activate_pending_secret(&email, 1).await;
+        assert!(result.is_ok());
+        assert_eq!(store.get_secret(&email).await, Ok(secret));
+        assert_eq!(
+            store.get_pending_secret(&email).await,
+            Err(TotpSecretStoreError::SecretNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_secret_does_not_replace_active_secret() {
+        let mut store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let secret = TotpSecret::default();
+
+        store.set_pending_secret(&email, secret.clone()).await.unwrap();
+        store.activate_pending_secret(&email, 1).await.unwrap();
+
+        store
+            .set_pending_secret(&email, TotpSecret::default())
+            .await
+            .unwrap();
+        assert_eq!(store.get_secret(&email).await, Ok(secret));
+    }
+
+    #[tokio::test]
+    async fn test_use_step() {
+        let mut store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+
+        let result = store.use_step(&email, 1).await;
+        assert_eq!(result, Err(TotpSecretStoreError::SecretNotFound));
+
+        store
+            .set_pending_secret(&email, TotpSecret::default())
+            .await
+            .unwrap();
+        store.activate_pending_secret(&email, 5).await.unwrap();
+
+        // The step used to confirm the secret can't be used again
+        let result = store.use_step(&email, 5).await;
+        assert_eq!(result, Err(TotpSecretStoreError::CodeAlreadyUsed));
+
+        let result = store.use_step(&email, 6).await;
+        assert!(result.is_ok());
+
+        let result = store.use_step(&email, 6).await;
+        assert_eq!(result, Err(TotpSecretStoreError::CodeAlreadyUsed));
+
+        let result = store.use_step(&email, 4).await;
+        assert_eq!(result, Err(TotpSecretStoreError::CodeAlreadyUsed));
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use crate::domain::{Email, TrustedDevice, TrustedDeviceStore, TrustedDeviceStoreError};
+
+#[derive(Default)]
+pub struct HashmapTrustedDeviceStore {
+    devices: HashMap<String, TrustedDevice>,
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for HashmapTrustedDeviceStore {
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        self.devices.insert(device.id.clone(), device);
+        Ok(())
+    }
+
+    async fn get_device(&self, device_id: &str) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+        match self.devices.get(device_id) {
+            Some(device) => Ok(device.clone()),
+            None => Err(TrustedDeviceStoreError::DeviceNotFound),
+        }
+    }
+
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        Ok(self
+            .devices
+            .values()
+            .filter(|device| &device.email == email)
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_device(&mut self, device_id: &str) -> Result<(), TrustedDeviceStoreError> {
+        self.devices.remove(device_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_get_device() {
+        let mut store = HashmapTrustedDeviceStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let device = TrustedDevice::new(email, Some("test-agent".to_owned()));
+
+        let result = store.add_device(device.clone()).await;
+        assert!(result.is_ok());
+
+        let result = store.get_device(&device.id).await;
+        assert_eq!(result, Ok(device));
+
+        let result = store.get_device("unknown_device").await;
+        assert_eq!(result, Err(TrustedDeviceStoreError::DeviceNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_get_devices() {
+        let mut store = HashmapTrustedDeviceStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        store.add_device(TrustedDevice::new(email.clone(), None)).await.unwrap();
+        store.add_device(TrustedDevice::new(email.clone(), None)).await.unwrap();
+        store.add_device(TrustedDevice::new(other_email, None)).await.unwrap();
+
+        let devices = store.get_devices(&email).await.unwrap();
+        assert_eq!(devices.len(), 2);
+        assert!(devices.iter().all(|device| device.email == email));
+    }
+
+    #[tokio::test]
+    async fn test_remove_device() {
+        let mut store = HashmapTrustedDeviceStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let device = TrustedDevice::new(email.clone(), None);
+        store.add_device(device.clone()).await.unwrap();
+
+        let result = store.remove_device(&device.id).await;
+        assert!(result.is_ok());
+        assert_eq!(
+            store.get_device(&device.id).await,
+            Err(TrustedDeviceStoreError::DeviceNotFound)
+        );
+        assert!(store.get_devices(&email).await.unwrap().is_empty());
+    }
+}
@@ -1,11 +1,91 @@
 use std::collections::HashMap;
 
-use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use chrono::Utc;
+use rand::Rng;
+use ring::hmac;
 
+use crate::domain::{
+    Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+    MAX_PENDING_LOGIN_ATTEMPTS, TWO_FA_CODE_REQUEST_WINDOW_SECONDS,
+};
+
+struct PendingLoginAttempt {
+    id: LoginAttemptId,
+    // None for login attempts that were never sent a code
+    code_hash: Option<String>,
+    failed_attempts: u32,
+    // Unix time the current code was sent at
+    sent_at: i64,
+    resends: u32,
+}
 
-#[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    // Pending login attempts of each user, oldest first
+    codes: HashMap<Email, Vec<PendingLoginAttempt>>,
+    // Wrong TOTP and recovery codes of each user and the unix time the count resets at
+    user_failures: HashMap<Email, (u32, i64)>,
+    // Requests for codes counted against each key and the unix time of the last one
+    code_requests: HashMap<String, (u32, i64)>,
+    hash_key: hmac::Key,
+}
+
+impl Default for HashmapTwoFACodeStore {
+    fn default() -> Self {
+        // Codes don't outlive the store, so a random key is enough
+        let secret: [u8; 32] = rand::thread_rng().gen();
+        Self {
+            codes: HashMap::new(),
+            user_failures: HashMap::new(),
+            code_requests: HashMap::new(),
+            hash_key: hmac::Key::new(hmac::HMAC_SHA256, &secret),
+        }
+    }
+}
+
+impl HashmapTwoFACodeStore {
+    fn get_attempt(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Option<&PendingLoginAttempt> {
+        self.codes
+            .get(email)?
+            .iter()
+            .find(|attempt| &attempt.id == login_attempt_id)
+    }
+
+    fn get_attempt_mut(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Option<&mut PendingLoginAttempt> {
+        self.codes
+            .get_mut(email)?
+            .iter_mut()
+            .find(|attempt| &attempt.id == login_attempt_id)
+    }
+
+    fn insert_attempt(
+        &mut self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code_hash: Option<String>,
+    ) {
+        let attempts = self.codes.entry(email).or_default();
+        attempts.retain(|attempt| attempt.id != login_attempt_id);
+
+        if attempts.len() >= MAX_PENDING_LOGIN_ATTEMPTS {
+            attempts.drain(..=attempts.len() - MAX_PENDING_LOGIN_ATTEMPTS);
+        }
+
+        attempts.push(PendingLoginAttempt {
+            id: login_attempt_id,
+            code_hash,
+            failed_attempts: 0,
+            sent_at: Utc::now().timestamp(),
+            resends: 0,
+        });
+    }
 }
 
 #[async_trait::async_trait]
@@ -16,25 +96,192 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        let code_hash = code.hash(&self.hash_key);
+        self.insert_attempt(email, login_attempt_id, Some(code_hash));
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        match self.codes.remove(email) {
-            Some(_) => Ok(()),
-            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+    async fn add_login_attempt(
+        &mut self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        self.insert_attempt(email, login_attempt_id, None);
+        Ok(())
+    }
+
+    async fn remove_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempts = self
+            .codes
+            .get_mut(email)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let position = attempts
+            .iter()
+            .position(|attempt| &attempt.id == login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        attempts.remove(position);
+
+        if attempts.is_empty() {
+            self.codes.remove(email);
         }
+        Ok(())
     }
 
-    async fn get_code(
+    async fn contains_code(
         &self,
         email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.codes.get(email) {
-            Some(v) => Ok(v.clone()),
-            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<bool, TwoFACodeStoreError> {
+        Ok(self.get_attempt(email, login_attempt_id).is_some())
+    }
+
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt = self
+            .get_attempt(email, login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        match &attempt.code_hash {
+            Some(code_hash) if code.matches_hash(&self.hash_key, code_hash) => (),
+            _ => return Err(TwoFACodeStoreError::IncorrectCode),
         }
+
+        self.remove_code(email, login_attempt_id).await
+    }
+
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt = self
+            .get_attempt_mut(email, login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        attempt.failed_attempts += 1;
+
+        if attempt.failed_attempts >= max_attempts {
+            self.remove_code(email, login_attempt_id).await?;
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+
+        Ok(())
+    }
+
+    async fn replace_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        cooldown_secs: u64,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let code_hash = code.hash(&self.hash_key);
+        let attempt = self
+            .get_attempt_mut(email, login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if attempt.resends >= max_resends {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        let now = Utc::now().timestamp();
+        let wait = attempt.sent_at + cooldown_secs as i64 - now;
+        if wait > 0 {
+            return Err(TwoFACodeStoreError::ResendCooldown(wait as u64));
+        }
+
+        attempt.code_hash = Some(code_hash);
+        attempt.sent_at = now;
+        attempt.resends += 1;
+        Ok(())
+    }
+
+    async fn record_user_failure(
+        &mut self,
+        email: &Email,
+        max_failures: u32,
+        lockout_secs: u64,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let now = Utc::now().timestamp();
+        let (failures, resets_at) = self
+            .user_failures
+            .entry(email.clone())
+            .or_insert((0, now + lockout_secs as i64));
+
+        if *resets_at <= now {
+            *failures = 0;
+            *resets_at = now + lockout_secs as i64;
+        }
+        *failures += 1;
+
+        match *failures >= max_failures {
+            true => Err(TwoFACodeStoreError::TooManyAttempts),
+            false => Ok(()),
+        }
+    }
+
+    async fn is_user_locked_out(
+        &self,
+        email: &Email,
+        max_failures: u32,
+    ) -> Result<bool, TwoFACodeStoreError> {
+        Ok(match self.user_failures.get(email) {
+            Some(&(failures, resets_at)) => {
+                failures >= max_failures && resets_at > Utc::now().timestamp()
+            }
+            None => false,
+        })
+    }
+
+    async fn clear_user_failures(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        self.user_failures.remove(email);
+        Ok(())
+    }
+
+    async fn record_code_request(
+        &mut self,
+        key: &str,
+        cooldown_secs: u64,
+        max_requests: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let now = Utc::now().timestamp();
+
+        let (count, last_request_at) = match self.code_requests.get(key) {
+            Some(&(count, last_request_at))
+                if last_request_at + TWO_FA_CODE_REQUEST_WINDOW_SECONDS as i64 > now =>
+            {
+                (count, last_request_at)
+            }
+            _ => (0, 0),
+        };
+
+        if count > 0 {
+            if count >= max_requests {
+                return Err(TwoFACodeStoreError::TooManyResends);
+            }
+            let wait = last_request_at + cooldown_secs as i64 - now;
+            if wait > 0 {
+                return Err(TwoFACodeStoreError::ResendCooldown(wait as u64));
+            }
+        }
+
+        self.code_requests.insert(key.to_owned(), (count + 1, now));
+        Ok(())
+    }
+
+    async fn clear_code_requests(&mut self, key: &str) -> Result<(), TwoFACodeStoreError> {
+        self.code_requests.remove(key);
+        Ok(())
     }
 }
 
@@ -48,59 +295,351 @@ mod tests {
         let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
         let login_attempt_id = LoginAttemptId::parse("4dce63c8-2031-4e79-ad59-145fef4bd15b".to_string()).unwrap();
         let code = TwoFACode::parse("123456".to_string()).unwrap();
-        
+
         let result = two_fa_code_store
             .add_code(email.clone(), login_attempt_id.clone(), code.clone())
             .await;
 
         assert!(result.is_ok());
-        assert_eq!(two_fa_code_store.codes.get(&email), Some(&(login_attempt_id, code)));
+        assert_eq!(two_fa_code_store.contains_code(&email, &login_attempt_id).await, Ok(true));
+
+        // Only a hash of the code is kept
+        let attempt = two_fa_code_store.get_attempt(&email, &login_attempt_id).unwrap();
+        assert_ne!(attempt.code_hash.as_deref(), Some(code.as_ref()));
     }
 
     #[tokio::test]
-    async fn test_remove_code() {
+    async fn test_consume_code() {
         let mut two_fa_code_store = HashmapTwoFACodeStore::default();
         let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
-        let login_attempt_id = LoginAttemptId::parse("4dce63c8-2031-4e79-ad59-145fef4bd15b".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::parse("123456".to_string()).unwrap();
+        let wrong_code = TwoFACode::parse("654321".to_string()).unwrap();
 
         two_fa_code_store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .await
+            .unwrap();
+
+        let result = two_fa_code_store
+            .consume_code(&email, &login_attempt_id, &wrong_code)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::IncorrectCode));
+        assert_eq!(two_fa_code_store.contains_code(&email, &login_attempt_id).await, Ok(true));
+
+        let result = two_fa_code_store
+            .consume_code(&email, &login_attempt_id, &code)
+            .await;
+        assert_eq!(result, Ok(()));
+
+        // A code can only be used once
+        let result = two_fa_code_store
+            .consume_code(&email, &login_attempt_id, &code)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    }
 
-        let result = two_fa_code_store.remove_code(&email).await;
+    #[tokio::test]
+    async fn test_add_login_attempt() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
 
+        let result = two_fa_code_store
+            .add_login_attempt(email.clone(), login_attempt_id.clone())
+            .await;
         assert!(result.is_ok());
-        assert_eq!(two_fa_code_store.codes.get(&email), None);
+        assert_eq!(two_fa_code_store.contains_code(&email, &login_attempt_id).await, Ok(true));
+
+        // No code was sent, so none can be used for the login attempt
+        let result = two_fa_code_store
+            .consume_code(&email, &login_attempt_id, &TwoFACode::default())
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::IncorrectCode));
+
+        let result = two_fa_code_store
+            .record_failed_attempt(&email, &login_attempt_id, 1)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::TooManyAttempts));
+        assert_eq!(two_fa_code_store.contains_code(&email, &login_attempt_id).await, Ok(false));
     }
 
     #[tokio::test]
-    async fn test_get_code() {
+    async fn test_record_user_failure() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+
+        for _ in 0..2 {
+            let result = two_fa_code_store.record_user_failure(&email, 3, 60).await;
+            assert_eq!(result, Ok(()));
+        }
+        assert_eq!(two_fa_code_store.is_user_locked_out(&email, 3).await, Ok(false));
+
+        let result = two_fa_code_store.record_user_failure(&email, 3, 60).await;
+        assert_eq!(result, Err(TwoFACodeStoreError::TooManyAttempts));
+        assert_eq!(two_fa_code_store.is_user_locked_out(&email, 3).await, Ok(true));
+
+        two_fa_code_store.clear_user_failures(&email).await.unwrap();
+        assert_eq!(two_fa_code_store.is_user_locked_out(&email, 3).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_user_failures_reset_after_lockout() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+
+        // Without a lockout period every failure starts a new count
+        for _ in 0..3 {
+            let result = two_fa_code_store.record_user_failure(&email, 2, 0).await;
+            assert_eq!(result, Ok(()));
+        }
+        assert_eq!(two_fa_code_store.is_user_locked_out(&email, 1).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_add_code_keeps_other_login_attempts() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let first_attempt_id = LoginAttemptId::default();
+        let second_attempt_id = LoginAttemptId::default();
+        let first_code = TwoFACode::parse("123456".to_string()).unwrap();
+        let second_code = TwoFACode::parse("654321".to_string()).unwrap();
+
+        two_fa_code_store
+            .add_code(email.clone(), first_attempt_id.clone(), first_code.clone())
+            .await
+            .unwrap();
+        two_fa_code_store
+            .add_code(email.clone(), second_attempt_id.clone(), second_code.clone())
+            .await
+            .unwrap();
+
+        let result = two_fa_code_store
+            .consume_code(&email, &first_attempt_id, &second_code)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::IncorrectCode));
+
+        let result = two_fa_code_store
+            .consume_code(&email, &second_attempt_id, &second_code)
+            .await;
+        assert_eq!(result, Ok(()));
+
+        let result = two_fa_code_store
+            .consume_code(&email, &first_attempt_id, &first_code)
+            .await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_add_code_drops_oldest_login_attempt_over_cap() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+
+        let mut login_attempt_ids = Vec::new();
+        for _ in 0..=MAX_PENDING_LOGIN_ATTEMPTS {
+            let login_attempt_id = LoginAttemptId::default();
+            two_fa_code_store
+                .add_code(email.clone(), login_attempt_id.clone(), TwoFACode::default())
+                .await
+                .unwrap();
+            login_attempt_ids.push(login_attempt_id);
+        }
+
+        assert_eq!(
+            two_fa_code_store.contains_code(&email, &login_attempt_ids[0]).await,
+            Ok(false)
+        );
+        for login_attempt_id in &login_attempt_ids[1..] {
+            assert_eq!(two_fa_code_store.contains_code(&email, login_attempt_id).await, Ok(true));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remove_code() {
         let mut two_fa_code_store = HashmapTwoFACodeStore::default();
         let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
         let login_attempt_id = LoginAttemptId::parse("4dce63c8-2031-4e79-ad59-145fef4bd15b".to_string()).unwrap();
+        let other_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::parse("123456".to_string()).unwrap();
-        
+
         two_fa_code_store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
-        
-        let result = two_fa_code_store.get_code(&email).await;
+            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .await
+            .unwrap();
+        two_fa_code_store
+            .add_code(email.clone(), other_attempt_id.clone(), code)
+            .await
+            .unwrap();
+
+        let result = two_fa_code_store.remove_code(&email, &login_attempt_id).await;
+
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), (login_attempt_id, code));
+        assert_eq!(two_fa_code_store.contains_code(&email, &login_attempt_id).await, Ok(false));
+        assert_eq!(two_fa_code_store.contains_code(&email, &other_attempt_id).await, Ok(true));
+
+        two_fa_code_store.remove_code(&email, &other_attempt_id).await.unwrap();
+        assert!(!two_fa_code_store.codes.contains_key(&email));
     }
 
     #[tokio::test]
-    async fn test_get_code_not_found() {
+    async fn test_contains_code_not_found() {
         let store = HashmapTwoFACodeStore::default();
         let email = Email::parse("test@example.com".to_string()).unwrap();
 
-        let result = store.get_code(&email).await;
+        let result = store.contains_code(&email, &LoginAttemptId::default()).await;
+
+        assert_eq!(result, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        let result = two_fa_code_store
+            .record_failed_attempt(&email, &login_attempt_id, 3)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+        two_fa_code_store
+            .add_code(email.clone(), login_attempt_id.clone(), code)
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let result = two_fa_code_store
+                .record_failed_attempt(&email, &login_attempt_id, 3)
+                .await;
+            assert_eq!(result, Ok(()));
+        }
+        let result = two_fa_code_store
+            .record_failed_attempt(&email, &login_attempt_id, 3)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::TooManyAttempts));
+
+        // The code can't be used anymore once the limit is reached
+        assert_eq!(two_fa_code_store.contains_code(&email, &login_attempt_id).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_add_code_resets_failed_attempts() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+
+        two_fa_code_store
+            .add_code(email.clone(), login_attempt_id.clone(), TwoFACode::default())
+            .await
+            .unwrap();
+        let result = two_fa_code_store
+            .record_failed_attempt(&email, &login_attempt_id, 2)
+            .await;
+        assert_eq!(result, Ok(()));
 
-        assert!(result.is_err());
+        two_fa_code_store
+            .add_code(email.clone(), login_attempt_id.clone(), TwoFACode::default())
+            .await
+            .unwrap();
+        let result = two_fa_code_store
+            .record_failed_attempt(&email, &login_attempt_id, 2)
+            .await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_replace_code() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let old_code = TwoFACode::parse("123456".to_string()).unwrap();
+        let new_code = TwoFACode::parse("654321".to_string()).unwrap();
+
+        let result = two_fa_code_store
+            .replace_code(&email, &login_attempt_id, new_code.clone(), 0, 2)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+        two_fa_code_store
+            .add_code(email.clone(), login_attempt_id.clone(), old_code.clone())
+            .await
+            .unwrap();
+        two_fa_code_store
+            .record_failed_attempt(&email, &login_attempt_id, 2)
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let result = two_fa_code_store
+                .replace_code(&email, &login_attempt_id, new_code.clone(), 0, 2)
+                .await;
+            assert_eq!(result, Ok(()));
+        }
+        let result = two_fa_code_store
+            .consume_code(&email, &login_attempt_id, &old_code)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::IncorrectCode));
+
+        let result = two_fa_code_store
+            .replace_code(&email, &login_attempt_id, new_code, 0, 2)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::TooManyResends));
+
+        // Wrong guesses against the old code still count
+        let result = two_fa_code_store
+            .record_failed_attempt(&email, &login_attempt_id, 2)
+            .await;
+        assert_eq!(result, Err(TwoFACodeStoreError::TooManyAttempts));
+    }
+
+    #[tokio::test]
+    async fn test_replace_code_cooldown() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::parse("123456".to_string()).unwrap();
+
+        two_fa_code_store
+            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .await
+            .unwrap();
+
+        let result = two_fa_code_store
+            .replace_code(&email, &login_attempt_id, TwoFACode::default(), 60, 3)
+            .await;
+        assert!(matches!(
+            result,
+            Err(TwoFACodeStoreError::ResendCooldown(wait)) if wait > 0 && wait <= 60
+        ));
+
+        // The code stays unchanged while throttled
+        let result = two_fa_code_store
+            .consume_code(&email, &login_attempt_id, &code)
+            .await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_record_code_request() {
+        let mut two_fa_code_store = HashmapTwoFACodeStore::default();
+
+        assert_eq!(two_fa_code_store.record_code_request("user", 0, 2).await, Ok(()));
+        assert_eq!(two_fa_code_store.record_code_request("target", 30, 2).await, Ok(()));
+
+        let result = two_fa_code_store.record_code_request("target", 30, 2).await;
+        assert!(matches!(
+            result,
+            Err(TwoFACodeStoreError::ResendCooldown(wait)) if wait > 0 && wait <= 30
+        ));
+
+        assert_eq!(two_fa_code_store.record_code_request("user", 0, 2).await, Ok(()));
         assert_eq!(
-            result.unwrap_err(),
-            TwoFACodeStoreError::LoginAttemptIdNotFound
+            two_fa_code_store.record_code_request("user", 0, 2).await,
+            Err(TwoFACodeStoreError::TooManyResends)
         );
+
+        assert_eq!(two_fa_code_store.clear_code_requests("user").await, Ok(()));
+        assert_eq!(two_fa_code_store.record_code_request("user", 0, 2).await, Ok(()));
     }
-}
\ No newline at end of file
+}
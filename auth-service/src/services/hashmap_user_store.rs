@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::domain::{Email, Password, TwoFAMethod, User, UserStore, UserStoreError};
 
 #[derive(Default)]
 pub struct HashmapUserStore {
     pub users: HashMap<Email, User>,
+    pub token_generations: HashMap<Email, i32>,
 }
 
 #[async_trait::async_trait]
@@ -47,7 +48,10 @@ impl UserStore for HashmapUserStore {
     async fn delete_user(&mut self, email: &Email, password: &Password) -> Result<(), UserStoreError> {
         if self.validate_user(email, password).await.is_ok() {
             match self.users.remove(email) {
-                Some(_u) => return Ok(()),
+                Some(_u) => {
+                    self.token_generations.remove(email);
+                    return Ok(());
+                },
                 None => {
                     
                     return Err(UserStoreError::UserNotFound);
@@ -57,6 +61,60 @@ impl UserStore for HashmapUserStore {
 
         Err(UserStoreError::InvalidCredentials)
     }
+
+    async fn get_token_generation(&self, email: &Email) -> Result<i32, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(self.token_generations.get(email).copied().unwrap_or_default())
+    }
+
+    async fn increment_token_generation(&mut self, email: &Email) -> Result<i32, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        let generation = self.token_generations.entry(email.clone()).or_default();
+        *generation += 1;
+        Ok(*generation)
+    }
+
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.two_fa_method = two_fa_method;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.email_verified = true;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
 }                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                            
 
 
@@ -73,7 +131,7 @@ mod tests{
         let user = User::new(
             email,
             password,
-            false,
+            TwoFAMethod::None,
         );
 
         // Test adding a new user
@@ -89,8 +147,8 @@ mod tests{
         let mut user_store = HashmapUserStore::default();
         let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
         let password = Password::parse("password".to_string()).unwrap();
-        let require_2fa = false;
-        let user = User::new(email.clone(), password.clone(), require_2fa);
+        let two_fa_method = TwoFAMethod::None;
+        let user = User::new(email.clone(), password.clone(), two_fa_method);
 
         // Test getting a user that exists
         user_store.users.insert(email.clone(), user.clone());
@@ -110,9 +168,9 @@ mod tests{
         
         let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
         let password = Password::parse("password".to_string()).unwrap();
-        let require_2fa = false;
+        let two_fa_method = TwoFAMethod::None;
         
-        let user = User::new(email.clone(), password.clone(), require_2fa);
+        let user = User::new(email.clone(), password.clone(), two_fa_method);
 
         // Test validating a user that exists with correct password
         user_store.users.insert(email.clone(), user.clone());
@@ -139,14 +197,15 @@ mod tests{
     async fn test_delete_user() {
         let mut user_store = HashmapUserStore {
             users: HashMap::new(),
+            token_generations: HashMap::new(),
         };
         
         let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
         let password = Password::parse("password".to_string()).unwrap();
         let wrong_password = Password::parse("wrong_password".to_string()).unwrap();
-        let require_2fa = true;
+        let two_fa_method = TwoFAMethod::Email;
 
-        let user = User::new(email.clone(), password.clone(), require_2fa);
+        let user = User::new(email.clone(), password.clone(), two_fa_method);
 
         let result = user_store.add_user(user).await;
         assert!(result.is_ok());
@@ -160,5 +219,89 @@ mod tests{
         let result = user_store.delete_user(&email, &password).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_token_generation() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let password = Password::parse("password".to_string()).unwrap();
+
+        let result = user_store.get_token_generation(&email).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        let result = user_store.increment_token_generation(&email).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        let user = User::new(email.clone(), password, TwoFAMethod::None);
+        user_store.add_user(user).await.unwrap();
+
+        let result = user_store.get_token_generation(&email).await;
+        assert_eq!(result, Ok(0));
+
+        let result = user_store.increment_token_generation(&email).await;
+        assert_eq!(result, Ok(1));
+
+        let result = user_store.get_token_generation(&email).await;
+        assert_eq!(result, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let password = Password::parse("password".to_string()).unwrap();
+
+        let result = user_store.set_two_fa_method(&email, TwoFAMethod::Totp).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        let user = User::new(email.clone(), password, TwoFAMethod::Email);
+        user_store.add_user(user).await.unwrap();
+
+        let result = user_store.set_two_fa_method(&email, TwoFAMethod::Totp).await;
+        assert_eq!(result, Ok(()));
+
+        let user = user_store.get_user(&email).await.unwrap();
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+    }
+
+    #[tokio::test]
+    async fn test_mark_email_verified() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let password = Password::parse("password".to_string()).unwrap();
+
+        let result = user_store.mark_email_verified(&email).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        let user = User::new(email.clone(), password, TwoFAMethod::None);
+        user_store.add_user(user).await.unwrap();
+        assert!(!user_store.get_user(&email).await.unwrap().email_verified);
+
+        let result = user_store.mark_email_verified(&email).await;
+        assert_eq!(result, Ok(()));
+        assert!(user_store.get_user(&email).await.unwrap().email_verified);
+    }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("dev.ted.kim@gmail.com".to_string()).unwrap();
+        let password = Password::parse("password".to_string()).unwrap();
+        let new_password = Password::parse("new-password".to_string()).unwrap();
+
+        let result = user_store.update_password(&email, new_password.clone()).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        let user = User::new(email.clone(), password.clone(), TwoFAMethod::None);
+        user_store.add_user(user).await.unwrap();
+
+        let result = user_store.update_password(&email, new_password.clone()).await;
+        assert_eq!(result, Ok(()));
+
+        let result = user_store.validate_user(&email, &password).await;
+        assert_eq!(result, Err(UserStoreError::InvalidCredentials));
+        let result = user_store.validate_user(&email, &new_password).await;
+        assert_eq!(result, Ok(()));
+    }
     
 }
\ No newline at end of file
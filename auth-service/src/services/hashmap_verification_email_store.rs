@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    Email, VerificationEmailStore, VerificationEmailStoreError,
+    VERIFICATION_EMAIL_RESEND_WINDOW_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapVerificationEmailStore {
+    resends: HashMap<Email, Resends>,
+}
+
+struct Resends {
+    window_started_at: i64,
+    sent_at: i64,
+    count: u32,
+}
+
+#[async_trait::async_trait]
+impl VerificationEmailStore for HashmapVerificationEmailStore {
+    async fn record_resend(
+        &mut self,
+        email: &Email,
+        cooldown_secs: u64,
+        max_resends: u32,
+    ) -> Result<(), VerificationEmailStoreError> {
+        let now = Utc::now().timestamp();
+        let window_ended = |resends: &Resends| {
+            resends.window_started_at + VERIFICATION_EMAIL_RESEND_WINDOW_SECONDS as i64 <= now
+        };
+
+        let resends = match self.resends.get_mut(email) {
+            Some(resends) if !window_ended(resends) => resends,
+            _ => {
+                self.resends.insert(
+                    email.clone(),
+                    Resends {
+                        window_started_at: now,
+                        sent_at: now,
+                        count: 1,
+                    },
+                );
+                return Ok(());
+            }
+        };
+
+        if resends.count >= max_resends {
+            return Err(VerificationEmailStoreError::TooManyResends);
+        }
+        let wait = resends.sent_at + cooldown_secs as i64 - now;
+        if wait > 0 {
+            return Err(VerificationEmailStoreError::ResendCooldown(wait as u64));
+        }
+
+        resends.sent_at = now;
+        resends.count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse("test@example.com".to_owned()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_record_resend_cooldown() {
+        let mut store = HashmapVerificationEmailStore::default();
+
+        assert_eq!(store.record_resend(&email(), 60, 5).await, Ok(()));
+        let result = store.record_resend(&email(), 60, 5).await;
+        assert!(matches!(
+            result,
+            Err(VerificationEmailStoreError::ResendCooldown(wait)) if wait > 0 && wait <= 60
+        ));
+
+        let other = Email::parse("other@example.com".to_owned()).unwrap();
+        assert_eq!(store.record_resend(&other, 60, 5).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_record_resend_limit() {
+        let mut store = HashmapVerificationEmailStore::default();
+
+        for _ in 0..3 {
+            assert_eq!(store.record_resend(&email(), 0, 3).await, Ok(()));
+        }
+        let result = store.record_resend(&email(), 0, 3).await;
+        assert_eq!(result, Err(VerificationEmailStoreError::TooManyResends));
+
+        // The limit starts over once the window passed
+        store.resends.get_mut(&email()).unwrap().window_started_at -=
+            VERIFICATION_EMAIL_RESEND_WINDOW_SECONDS as i64;
+        assert_eq!(store.record_resend(&email(), 0, 3).await, Ok(()));
+    }
+}
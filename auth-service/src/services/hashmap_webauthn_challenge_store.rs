@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use crate::domain::{PendingWebAuthnCeremony, WebAuthnChallengeStore, WebAuthnChallengeStoreError};
+
+#[derive(Default)]
+pub struct HashmapWebAuthnChallengeStore {
+    challenges: HashMap<String, PendingWebAuthnCeremony>,
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for HashmapWebAuthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        ceremony_id: &str,
+        pending: PendingWebAuthnCeremony,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        self.challenges.insert(ceremony_id.to_owned(), pending);
+        Ok(())
+    }
+
+    async fn take_challenge(
+        &mut self,
+        ceremony_id: &str,
+    ) -> Result<PendingWebAuthnCeremony, WebAuthnChallengeStoreError> {
+        self.challenges
+            .remove(ceremony_id)
+            .ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{generate_user_handle, Email, WebAuthnCeremony, WebAuthnChallenge};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_challenge_only_once() {
+        let mut store = HashmapWebAuthnChallengeStore::default();
+        let pending = PendingWebAuthnCeremony {
+            challenge: WebAuthnChallenge::default(),
+            ceremony: WebAuthnCeremony::Registration {
+                email: Email::parse("test@example.com".to_owned()).unwrap(),
+                user_handle: generate_user_handle(),
+            },
+        };
+
+        let result = store.add_challenge("ceremony_id", pending.clone()).await;
+        assert!(result.is_ok());
+
+        let result = store.take_challenge("ceremony_id").await;
+        assert_eq!(result, Ok(pending));
+
+        let result = store.take_challenge("ceremony_id").await;
+        assert_eq!(result, Err(WebAuthnChallengeStoreError::ChallengeNotFound));
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    Email, WebAuthnCredential, WebAuthnCredentialStore, WebAuthnCredentialStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapWebAuthnCredentialStore {
+    // credential id -> credential
+    credentials: HashMap<String, WebAuthnCredential>,
+}
+
+#[async_trait::async_trait]
+impl WebAuthnCredentialStore for HashmapWebAuthnCredentialStore {
+    async fn add_credential(
+        &mut self,
+        credential: WebAuthnCredential,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        if self.credentials.contains_key(&credential.id) {
+            return Err(WebAuthnCredentialStoreError::CredentialAlreadyExists);
+        }
+        self.credentials.insert(credential.id.clone(), credential);
+        Ok(())
+    }
+
+    async fn get_credential(
+        &self,
+        credential_id: &str,
+    ) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError> {
+        self.credentials
+            .get(credential_id)
+            .cloned()
+            .ok_or(WebAuthnCredentialStoreError::CredentialNotFound)
+    }
+
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebAuthnCredential>, WebAuthnCredentialStoreError> {
+        Ok(self
+            .credentials
+            .values()
+            .filter(|credential| &credential.email == email)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &str,
+        sign_count: u32,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        let credential = self
+            .credentials
+            .get_mut(credential_id)
+            .ok_or(WebAuthnCredentialStoreError::CredentialNotFound)?;
+        credential.sign_count = sign_count;
+        Ok(())
+    }
+
+    async fn remove_credential(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        self.credentials
+            .remove(credential_id)
+            .map(|_| ())
+            .ok_or(WebAuthnCredentialStoreError::CredentialNotFound)
+    }
+
+    async fn remove_credentials(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        self.credentials
+            .retain(|_, credential| &credential.email != email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credential(id: &str, email: &Email) -> WebAuthnCredential {
+        WebAuthnCredential {
+            id: id.to_owned(),
+            email: email.clone(),
+            user_handle: "handle".to_owned(),
+            public_key: vec![4; 65],
+            sign_count: 0,
+            created_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_credentials() {
+        let mut store = HashmapWebAuthnCredentialStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other = Email::parse("other@example.com".to_owned()).unwrap();
+
+        store.add_credential(credential("a", &email)).await.unwrap();
+        store.add_credential(credential("b", &other)).await.unwrap();
+
+        let result = store.add_credential(credential("a", &email)).await;
+        assert_eq!(
+            result,
+            Err(WebAuthnCredentialStoreError::CredentialAlreadyExists)
+        );
+
+        assert_eq!(store.get_credential("a").await, Ok(credential("a", &email)));
+        assert_eq!(
+            store.get_credentials(&email).await,
+            Ok(vec![credential("a", &email)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_sign_count() {
+        let mut store = HashmapWebAuthnCredentialStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        store.add_credential(credential("a", &email)).await.unwrap();
+
+        store.update_sign_count("a", 7).await.unwrap();
+        assert_eq!(store.get_credential("a").await.unwrap().sign_count, 7);
+
+        let result = store.update_sign_count("missing", 1).await;
+        assert_eq!(
+            result,
+            Err(WebAuthnCredentialStoreError::CredentialNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_credentials() {
+        let mut store = HashmapWebAuthnCredentialStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other = Email::parse("other@example.com".to_owned()).unwrap();
+        store.add_credential(credential("a", &email)).await.unwrap();
+        store.add_credential(credential("b", &email)).await.unwrap();
+        store.add_credential(credential("c", &other)).await.unwrap();
+
+        store.remove_credential("a").await.unwrap();
+        let result = store.remove_credential("a").await;
+        assert_eq!(
+            result,
+            Err(WebAuthnCredentialStoreError::CredentialNotFound)
+        );
+
+        store.remove_credentials(&email).await.unwrap();
+        assert_eq!(store.get_credentials(&email).await, Ok(vec![]));
+        assert_eq!(
+            store.get_credentials(&other).await,
+            Ok(vec![credential("c", &other)])
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use reqwest::{header, StatusCode};
+use serde::Serialize;
+
+use crate::domain::{Email, EmailClient, EmailClientError, EmailContent};
+
+// Header the email API authenticates the server with
+pub const EMAIL_API_TOKEN_HEADER: &str = "X-Postmark-Server-Token";
+
+pub struct HttpEmailSettings {
+    pub base_url: String,
+    pub token: String,
+    pub sender: String,
+    pub timeout: Duration,
+    // Attempts after the first one when the API is unavailable
+    pub max_retries: u32,
+    // Wait before the first retry, doubled for every retry after it
+    pub retry_delay: Duration,
+    // Longest wait between retries. When the API asks to wait longer, the email
+    // is left for the outbox worker to retry later.
+    pub max_retry_delay: Duration,
+}
+
+// Sends emails through a transactional email HTTP API (Postmark)
+pub struct HttpEmailClient {
+    http_client: reqwest::Client,
+    url: String,
+    token: String,
+    sender: String,
+    max_retries: u32,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+}
+
+impl HttpEmailClient {
+    pub fn new(settings: HttpEmailSettings) -> Result<Self, String> {
+        let http_client = reqwest::Client::builder()
+            .timeout(settings.timeout)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        Ok(Self {
+            http_client,
+            url: format!("{}/email", settings.base_url.trim_end_matches('/')),
+            token: settings.token,
+            sender: settings.sender,
+            max_retries: settings.max_retries,
+            retry_delay: settings.retry_delay,
+            max_retry_delay: settings.max_retry_delay,
+        })
+    }
+
+    async fn post_email(&self, request: &SendEmailRequest<'_>) -> Result<(), Attempt> {
+        let response = self
+            .http_client
+            .post(&self.url)
+            .header(header::ACCEPT, "application/json")
+            .header(EMAIL_API_TOKEN_HEADER, &self.token)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| Attempt::Retry(e.to_string(), None))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let retry_after = retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        let message = format!("Email API responded with {}: {}", status, body);
+
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => Err(Attempt::Retry(message, retry_after)),
+            status if status.is_server_error() => Err(Attempt::Retry(message, None)),
+            _ => Err(Attempt::Fail(EmailClientError::Rejected(message))),
+        }
+    }
+
+    fn retry_delay(&self, retries: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retries);
+        self.retry_delay
+            .saturating_mul(factor)
+            .min(self.max_retry_delay)
+    }
+}
+
+// Seconds the API asks to wait before the next request, when rate limited
+fn retry_after(headers: &header::HeaderMap) -> Option<Duration> {
+    let secs = headers.get(header::RETRY_AFTER)?.to_str().ok()?;
+    secs.trim().parse().ok().map(Duration::from_secs)
+}
+
+// Why an attempt to send an email failed
+enum Attempt {
+    // Worth trying again after a while, at the earliest after the given delay
+    Retry(String, Option<Duration>),
+    Fail(EmailClientError),
+}
+
+#[async_trait::async_trait]
+impl EmailClient for HttpEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        content: &EmailContent,
+    ) -> Result<(), EmailClientError> {
+        let request = SendEmailRequest {
+            from: &self.sender,
+            to: recipient.as_ref(),
+            subject: &content.subject,
+            html_body: &content.html_body,
+            text_body: &content.text_body,
+            message_stream: "outbound",
+        };
+
+        let mut retries = 0;
+        loop {
+            match self.post_email(&request).await {
+                Ok(()) => return Ok(()),
+                Err(Attempt::Retry(message, _)) if retries == self.max_retries => {
+                    return Err(EmailClientError::Unavailable(message))
+                }
+                Err(Attempt::Retry(message, Some(delay))) if delay > self.max_retry_delay => {
+                    return Err(EmailClientError::Unavailable(message))
+                }
+                Err(Attempt::Retry(_, retry_after)) => {
+                    let delay = self
+                        .retry_delay(retries)
+                        .max(retry_after.unwrap_or_default());
+                    tokio::time::sleep(delay).await;
+                    retries += 1;
+                }
+                Err(Attempt::Fail(e)) => return Err(e),
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    message_stream: &'a str,
+}
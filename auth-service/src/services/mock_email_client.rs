@@ -1,23 +1,108 @@
-use crate::domain::{Email, EmailClient};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
 
+use crate::domain::{Email, EmailClient, EmailClientError, EmailContent};
+
+// Emails hold codes and links, and the mock is also the default client outside of
+// tests, so only the most recent ones are kept
+const MAX_KEPT_EMAILS: usize = 100;
+
+// Keeps the latest emails instead of delivering them, so tests can read them
 #[derive(Default)]
-pub struct MockEmailClient;
+pub struct MockEmailClient {
+    sent: Mutex<VecDeque<(Email, EmailContent)>>,
+    unavailable: AtomicBool,
+}
+
+impl MockEmailClient {
+    // Simulates an outage of the email provider while set
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.unavailable.store(unavailable, Ordering::SeqCst);
+    }
+
+    // The most recent email sent to the recipient
+    pub fn last_email(&self, recipient: &Email) -> Option<EmailContent> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .rev()
+            .find(|(email, _)| email == recipient)
+            .map(|(_, content)| content.clone())
+    }
+
+    // Every email kept for the recipient, oldest first
+    pub fn emails(&self, recipient: &Email) -> Vec<EmailContent> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(email, _)| email == recipient)
+            .map(|(_, content)| content.clone())
+            .collect()
+    }
+}
 
 #[async_trait::async_trait]
 impl EmailClient for MockEmailClient {
     async fn send_email(
         &self,
         recipient: &Email,
-        subject: &str,
-        content: &str,
-    ) -> Result<(), String> {
+        content: &EmailContent,
+    ) -> Result<(), EmailClientError> {
+        if self.unavailable.load(Ordering::SeqCst) {
+            return Err(EmailClientError::Unavailable(
+                "Mock email provider is down".to_owned(),
+            ));
+        }
+
         println!(
             "Sending email to {} with subject: {} and content: {}",
             recipient.as_ref(),
-            subject,
-            content
+            content.subject,
+            content.text_body
         );
 
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        if sent.len() >= MAX_KEPT_EMAILS {
+            sent.pop_front();
+        }
+        sent.push_back((recipient.clone(), content.clone()));
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(subject: &str) -> EmailContent {
+        EmailContent {
+            subject: subject.to_owned(),
+            html_body: String::new(),
+            text_body: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keeps_only_latest_emails() {
+        let client = MockEmailClient::default();
+        let first = Email::parse("first@example.com".to_owned()).unwrap();
+        let other = Email::parse("other@example.com".to_owned()).unwrap();
+
+        client.send_email(&first, &content("first")).await.unwrap();
+        for _ in 0..MAX_KEPT_EMAILS {
+            client.send_email(&other, &content("other")).await.unwrap();
+        }
+
+        assert_eq!(client.sent.lock().unwrap().len(), MAX_KEPT_EMAILS);
+        assert!(client.last_email(&first).is_none());
+        assert!(client.last_email(&other).is_some());
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Mutex;
+
+use crate::domain::{NotificationChannel, NotificationTarget};
+
+// Keeps every notification instead of delivering it, so tests can read the codes.
+// Only for tests, deployments without a real channel refuse SMS and push 2FA.
+#[derive(Default)]
+pub struct MockNotificationChannel {
+    sent: Mutex<Vec<(NotificationTarget, String)>>,
+}
+
+impl MockNotificationChannel {
+    // Content of the most recent notification sent to the recipient
+    pub fn last_notification(&self, recipient: &NotificationTarget) -> Option<String> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .rev()
+            .find(|(target, _)| target == recipient)
+            .map(|(_, content)| content.clone())
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for MockNotificationChannel {
+    async fn send_notification(
+        &self,
+        recipient: &NotificationTarget,
+        content: &str,
+    ) -> Result<(), String> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((recipient.clone(), content.to_owned()));
+
+        Ok(())
+    }
+}
@@ -1,15 +1,69 @@
 pub mod hashmap_user_store;
 pub mod hashset_banned_token_store;
 pub mod hashmap_two_fa_code_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_session_store;
+pub mod hashmap_totp_secret_store;
+pub mod hashmap_recovery_code_store;
+pub mod hashmap_trusted_device_store;
+pub mod hashmap_notification_target_store;
+pub mod hashmap_webauthn_challenge_store;
+pub mod hashmap_webauthn_credential_store;
+pub mod hashmap_email_outbox_store;
+pub mod hashmap_password_reset_token_store;
+pub mod hashmap_verification_email_store;
+pub mod email_outbox_worker;
+pub mod http_email_client;
 pub mod mock_email_client;
+pub mod mock_notification_channel;
 pub mod postgres_user_store;
+pub mod postgres_totp_secret_store;
+pub mod postgres_recovery_code_store;
+pub mod postgres_notification_target_store;
+pub mod postgres_webauthn_credential_store;
+pub mod postgres_email_outbox_store;
 pub mod redis_banned_token_store;
 pub mod redis_two_fa_code_store;
+pub mod redis_refresh_token_store;
+pub mod redis_session_store;
+pub mod redis_trusted_device_store;
+pub mod redis_webauthn_challenge_store;
+pub mod redis_password_reset_token_store;
+pub mod redis_verification_email_store;
+pub mod smtp_email_client;
+pub mod webhook_notification_channel;
 
 pub use hashmap_user_store::*;
 pub use hashset_banned_token_store::*;
 pub use hashmap_two_fa_code_store::*;
+pub use hashmap_refresh_token_store::*;
+pub use hashmap_session_store::*;
+pub use hashmap_totp_secret_store::*;
+pub use hashmap_recovery_code_store::*;
+pub use hashmap_trusted_device_store::*;
+pub use hashmap_notification_target_store::*;
+pub use hashmap_webauthn_challenge_store::*;
+pub use hashmap_webauthn_credential_store::*;
+pub use hashmap_email_outbox_store::*;
+pub use hashmap_password_reset_token_store::*;
+pub use hashmap_verification_email_store::*;
+pub use email_outbox_worker::*;
+pub use http_email_client::*;
 pub use mock_email_client::*;
+pub use mock_notification_channel::*;
 pub use postgres_user_store::*;
+pub use postgres_totp_secret_store::*;
+pub use postgres_recovery_code_store::*;
+pub use postgres_notification_target_store::*;
+pub use postgres_webauthn_credential_store::*;
+pub use postgres_email_outbox_store::*;
 pub use redis_banned_token_store::*;
-pub use redis_two_fa_code_store::*;
\ No newline at end of file
+pub use redis_two_fa_code_store::*;
+pub use redis_refresh_token_store::*;
+pub use redis_session_store::*;
+pub use redis_trusted_device_store::*;
+pub use redis_webauthn_challenge_store::*;
+pub use redis_password_reset_token_store::*;
+pub use redis_verification_email_store::*;
+pub use smtp_email_client::*;
+pub use webhook_notification_channel::*;
@@ -0,0 +1,183 @@
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::domain::{
+    Email, EmailContent, EmailOutboxStore, EmailOutboxStoreError, OutboxEmail, OutboxEmailStatus,
+};
+
+pub struct PostgresEmailOutboxStore {
+    pool: PgPool,
+}
+
+impl PostgresEmailOutboxStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for PostgresEmailOutboxStore {
+    async fn enqueue(&mut self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError> {
+        let now = Utc::now().timestamp_millis();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO email_outbox
+            (id, recipient, subject, html_body, text_body, next_attempt_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+            email.id,
+            email.recipient.as_ref(),
+            email.content.subject,
+            email.content.html_body,
+            email.content.text_body,
+            now,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn claim_due(
+        &mut self,
+        now: i64,
+        lease_until: i64,
+        limit: u32,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        // SKIP LOCKED lets several workers claim different emails at the same time
+        let records = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET attempts = attempts + 1, next_attempt_at = $2
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= $1
+                ORDER BY next_attempt_at, created_at
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, html_body, text_body, attempts
+            "#,
+            now,
+            lease_until,
+            i64::from(limit),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                Ok(OutboxEmail {
+                    id: record.id,
+                    recipient: Email::parse(record.recipient)
+                        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?,
+                    content: EmailContent {
+                        subject: record.subject,
+                        html_body: record.html_body,
+                        text_body: record.text_body,
+                    },
+                    attempts: record
+                        .attempts
+                        .try_into()
+                        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn mark_sent(&mut self, id: &str, now: i64) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'sent', sent_at = $2, last_error = NULL, html_body = '', text_body = ''
+            WHERE id = $1
+            "#,
+            id,
+            now,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(EmailOutboxStoreError::EmailNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn retry_later(
+        &mut self,
+        id: &str,
+        retry_at: i64,
+        error: &str,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET next_attempt_at = $2, last_error = $3
+            WHERE id = $1 AND status = 'pending'
+            "#,
+            id,
+            retry_at,
+            error,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(EmailOutboxStoreError::EmailNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn dead_letter(&mut self, id: &str, error: &str) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'dead', last_error = $2, html_body = '', text_body = ''
+            WHERE id = $1
+            "#,
+            id,
+            error,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(EmailOutboxStoreError::EmailNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_status(&self, id: &str) -> Result<OutboxEmailStatus, EmailOutboxStoreError> {
+        let record = sqlx::query!("SELECT status FROM email_outbox WHERE id = $1", id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|_| EmailOutboxStoreError::UnexpectedError)?
+            .ok_or(EmailOutboxStoreError::EmailNotFound)?;
+
+        OutboxEmailStatus::parse(&record.status).map_err(|_| EmailOutboxStoreError::UnexpectedError)
+    }
+
+    async fn delete_finished(&mut self, before: i64) -> Result<u64, EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM email_outbox
+            WHERE status IN ('sent', 'dead') AND created_at < $1
+            "#,
+            before,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
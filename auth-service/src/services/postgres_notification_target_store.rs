@@ -0,0 +1,155 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    Email, LoginAttemptId, NotificationTarget, NotificationTargetStore,
+    NotificationTargetStoreError, TwoFAMethod,
+};
+
+pub struct PostgresNotificationTargetStore {
+    pool: PgPool,
+}
+
+impl PostgresNotificationTargetStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationTargetStore for PostgresNotificationTargetStore {
+    async fn set_pending_target(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+        target: NotificationTarget,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), NotificationTargetStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO notification_targets
+                (email, pending_two_fa_method, pending_target, pending_login_attempt_id)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (email) DO UPDATE
+            SET pending_two_fa_method = EXCLUDED.pending_two_fa_method,
+                pending_target = EXCLUDED.pending_target,
+                pending_login_attempt_id = EXCLUDED.pending_login_attempt_id
+            "#,
+            email.as_ref(),
+            two_fa_method.as_ref(),
+            target.as_ref(),
+            login_attempt_id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_pending_target(
+        &self,
+        email: &Email,
+    ) -> Result<(TwoFAMethod, NotificationTarget, LoginAttemptId), NotificationTargetStoreError>
+    {
+        let maybe_record = sqlx::query!(
+            r#"
+            SELECT pending_two_fa_method, pending_target, pending_login_attempt_id
+            FROM notification_targets
+            WHERE email = $1
+            "#,
+            email.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+
+        let (two_fa_method, target, login_attempt_id) = match maybe_record {
+            Some(record) => match (
+                record.pending_two_fa_method,
+                record.pending_target,
+                record.pending_login_attempt_id,
+            ) {
+                (Some(two_fa_method), Some(target), Some(login_attempt_id)) => {
+                    (two_fa_method, target, login_attempt_id)
+                }
+                _ => return Err(NotificationTargetStoreError::TargetNotFound),
+            },
+            None => return Err(NotificationTargetStoreError::TargetNotFound),
+        };
+
+        let two_fa_method = TwoFAMethod::parse(&two_fa_method)
+            .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+        let target = NotificationTarget::parse(two_fa_method, target)
+            .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+        let login_attempt_id = LoginAttemptId::parse(login_attempt_id)
+            .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+
+        Ok((two_fa_method, target, login_attempt_id))
+    }
+
+    async fn activate_pending_target(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), NotificationTargetStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE notification_targets
+            SET two_fa_method = pending_two_fa_method,
+                target = pending_target,
+                pending_two_fa_method = NULL,
+                pending_target = NULL,
+                pending_login_attempt_id = NULL
+            WHERE email = $1 AND pending_target IS NOT NULL AND pending_login_attempt_id = $2
+            "#,
+            email.as_ref(),
+            login_attempt_id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(NotificationTargetStoreError::TargetNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_target(
+        &self,
+        email: &Email,
+    ) -> Result<NotificationTarget, NotificationTargetStoreError> {
+        let maybe_record = sqlx::query!(
+            "SELECT two_fa_method, target FROM notification_targets WHERE email = $1",
+            email.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+
+        let (two_fa_method, target) = match maybe_record {
+            Some(record) => match (record.two_fa_method, record.target) {
+                (Some(two_fa_method), Some(target)) => (two_fa_method, target),
+                _ => return Err(NotificationTargetStoreError::TargetNotFound),
+            },
+            None => return Err(NotificationTargetStoreError::TargetNotFound),
+        };
+
+        let two_fa_method = TwoFAMethod::parse(&two_fa_method)
+            .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+        NotificationTarget::parse(two_fa_method, target)
+            .map_err(|_| NotificationTargetStoreError::UnexpectedError)
+    }
+
+    async fn remove_targets(&mut self, email: &Email) -> Result<(), NotificationTargetStoreError> {
+        sqlx::query!(
+            "DELETE FROM notification_targets WHERE email = $1",
+            email.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| NotificationTargetStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use sqlx::PgPool;
+
+use crate::domain::{Email, TotpSecret, TotpSecretStore, TotpSecretStoreError};
+
+pub struct PostgresTotpSecretStore {
+    pool: PgPool,
+}
+
+impl PostgresTotpSecretStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for PostgresTotpSecretStore {
+    async fn set_pending_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO totp_secrets (email, pending_secret)
+            VALUES ($1, $2)
+            ON CONFLICT (email) DO UPDATE SET pending_secret = EXCLUDED.pending_secret
+            "#,
+            email.as_ref(),
+            secret.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_pending_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        let maybe_record = sqlx::query!(
+            "SELECT pending_secret FROM totp_secrets WHERE email = $1",
+            email.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        match maybe_record.and_then(|record| record.pending_secret) {
+            Some(secret) => {
+                TotpSecret::parse(secret).map_err(|_| TotpSecretStoreError::UnexpectedError)
+            }
+            None => Err(TotpSecretStoreError::SecretNotFound),
+        }
+    }
+
+    async fn activate_pending_secret(
+        &mut self,
+        email: &Email,
+        step: u64,
+    ) -> Result<(), TotpSecretStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET secret = pending_secret, pending_secret = NULL, last_used_step = $2
+            WHERE email = $1 AND pending_secret IS NOT NULL
+            "#,
+            email.as_ref(),
+            step as i64,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(TotpSecretStoreError::SecretNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        let maybe_record = sqlx::query!(
+            "SELECT secret FROM totp_secrets WHERE email = $1",
+            email.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        match maybe_record.and_then(|record| record.secret) {
+            Some(secret) => {
+                TotpSecret::parse(secret).map_err(|_| TotpSecretStoreError::UnexpectedError)
+            }
+            None => Err(TotpSecretStoreError::SecretNotFound),
+        }
+    }
+
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError> {
+        // Compare and set in one statement so concurrent logins can't both use the same code
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET last_used_step = $2
+            WHERE email = $1
+              AND secret IS NOT NULL
+              AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            email.as_ref(),
+            step as i64,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        if result.rows_affected() > 0 {
+            return Ok(());
+        }
+
+        match self.get_secret(email).await {
+            Ok(_) => Err(TotpSecretStoreError::CodeAlreadyUsed),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::error::Error;
 
-use crate::domain::{User, UserStore, UserStoreError};
+use crate::domain::{TwoFAMethod, User, UserStore, UserStoreError};
 use argon2::{
     password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
     PasswordVerifier, Version,
@@ -31,12 +31,12 @@ impl UserStore for PostgresUserStore {
         let _ = sqlx::query!(
             r#"
             INSERT INTO public.users
-            (email, password_hash, requires_2fa)
+            (email, password_hash, two_fa_method)
             VALUES ($1, $2, $3)
             "#,
             user.email.as_ref(),
             password_hash.unwrap(),
-            user.require_2fa,
+            user.two_fa_method.as_ref(),
         )
         .execute(&self.pool)
         .await
@@ -60,8 +60,12 @@ impl UserStore for PostgresUserStore {
             Some(record) => {
                 let email = Email::parse(record.email).unwrap();
                 let password = Password::parse(record.password_hash).unwrap();
-                let require_2fa = record.requires_2fa;
-                Ok(User::new(email, password, require_2fa))
+                let two_fa_method = TwoFAMethod::parse(&record.two_fa_method)
+                    .map_err(|_| UserStoreError::UnexpectedError)?;
+                Ok(User {
+                    email_verified: record.email_verified,
+                    ..User::new(email, password, two_fa_method)
+                })
             }
             None => Err(UserStoreError::UserNotFound),
         }
@@ -127,6 +131,100 @@ impl UserStore for PostgresUserStore {
 
         Ok(())
     }
+
+    async fn get_token_generation(&self, email: &Email) -> Result<i32, UserStoreError> {
+        let maybe_record = sqlx::query!(
+            "SELECT token_generation FROM users WHERE email = $1",
+            email.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match maybe_record {
+            Some(record) => Ok(record.token_generation),
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn increment_token_generation(&mut self, email: &Email) -> Result<i32, UserStoreError> {
+        let maybe_record = sqlx::query!(
+            r#"
+            UPDATE users
+            SET token_generation = token_generation + 1
+            WHERE email = $1
+            RETURNING token_generation
+            "#,
+            email.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match maybe_record {
+            Some(record) => Ok(record.token_generation),
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET two_fa_method = $2 WHERE email = $1",
+            email.as_ref(),
+            two_fa_method.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.0)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            "UPDATE users SET password_hash = $2 WHERE email = $1",
+            email.as_ref(),
+            password_hash,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET email_verified = TRUE WHERE email = $1",
+            email.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
 }
 
 // Helper function to verify if a given password matches an expected hash
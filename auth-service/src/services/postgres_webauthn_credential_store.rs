@@ -0,0 +1,190 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    Email, WebAuthnCredential, WebAuthnCredentialStore, WebAuthnCredentialStoreError,
+};
+
+pub struct PostgresWebAuthnCredentialStore {
+    pool: PgPool,
+}
+
+impl PostgresWebAuthnCredentialStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebAuthnCredentialStore for PostgresWebAuthnCredentialStore {
+    async fn add_credential(
+        &mut self,
+        credential: WebAuthnCredential,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO webauthn_credentials (id, email, user_handle, public_key, sign_count, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            credential.id,
+            credential.email.as_ref(),
+            credential.user_handle,
+            credential.public_key,
+            i64::from(credential.sign_count),
+            credential.created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_error)
+                if db_error.constraint() == Some("webauthn_credentials_pkey") =>
+            {
+                WebAuthnCredentialStoreError::CredentialAlreadyExists
+            }
+            _ => WebAuthnCredentialStoreError::UnexpectedError,
+        })?;
+
+        Ok(())
+    }
+
+    async fn get_credential(
+        &self,
+        credential_id: &str,
+    ) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError> {
+        let record = sqlx::query!(
+            r#"
+            SELECT id, email, user_handle, public_key, sign_count, created_at
+            FROM webauthn_credentials
+            WHERE id = $1
+            "#,
+            credential_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?
+        .ok_or(WebAuthnCredentialStoreError::CredentialNotFound)?;
+
+        parse_credential(
+            record.id,
+            record.email,
+            record.user_handle,
+            record.public_key,
+            record.sign_count,
+            record.created_at,
+        )
+    }
+
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebAuthnCredential>, WebAuthnCredentialStoreError> {
+        let records = sqlx::query!(
+            r#"
+            SELECT id, email, user_handle, public_key, sign_count, created_at
+            FROM webauthn_credentials
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                parse_credential(
+                    record.id,
+                    record.email,
+                    record.user_handle,
+                    record.public_key,
+                    record.sign_count,
+                    record.created_at,
+                )
+            })
+            .collect()
+    }
+
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &str,
+        sign_count: u32,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE webauthn_credentials
+            SET sign_count = $2
+            WHERE id = $1
+            "#,
+            credential_id,
+            i64::from(sign_count),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(WebAuthnCredentialStoreError::CredentialNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn remove_credential(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM webauthn_credentials
+            WHERE id = $1
+            "#,
+            credential_id,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(WebAuthnCredentialStoreError::CredentialNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn remove_credentials(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM webauthn_credentials
+            WHERE email = $1
+            "#,
+            email.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+fn parse_credential(
+    id: String,
+    email: String,
+    user_handle: String,
+    public_key: Vec<u8>,
+    sign_count: i64,
+    created_at: i64,
+) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError> {
+    Ok(WebAuthnCredential {
+        id,
+        email: Email::parse(email).map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?,
+        user_handle,
+        public_key,
+        sign_count: sign_count
+            .try_into()
+            .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?,
+        created_at,
+    })
+}
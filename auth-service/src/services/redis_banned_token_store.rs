@@ -19,8 +19,8 @@ impl RedisBannedTokenStore {
 
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
-    async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
-        let key = get_key(&token);
+    async fn add_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError> {
+        let key = get_key(&jti);
 
         let value = true;
 
@@ -38,8 +38,8 @@ impl BannedTokenStore for RedisBannedTokenStore {
         Ok(())
     }
 
-    async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        let key = get_key(token);
+    async fn contains_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        let key = get_key(jti);
         let value = self.conn.write().await.exists(&key);
         match value {
             Ok(v) => Ok(v),
@@ -50,6 +50,6 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
 
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
 }
\ No newline at end of file
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    Email, PasswordReset, PasswordResetToken, PasswordResetTokenStore,
+    PasswordResetTokenStoreError, PASSWORD_RESET_EMAIL_WINDOW_SECONDS,
+    PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        token: &PasswordResetToken,
+        reset: PasswordReset,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let stored = StoredReset {
+            email: reset.email.as_ref().to_owned(),
+            token_generation: reset.token_generation,
+        };
+        let json = serde_json::to_string(&stored)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(token), json, PASSWORD_RESET_TOKEN_TTL_SECONDS)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<PasswordReset, PasswordResetTokenStoreError> {
+        let key = get_key(token);
+
+        let (json, _): (Option<String>, ()) = redis::pipe()
+            .atomic()
+            .get(&key)
+            .del(&key)
+            .query(&mut *self.conn.write().await)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        let json = json.ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+        let stored: StoredReset = serde_json::from_str(&json)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(PasswordReset {
+            email: Email::parse(stored.email)
+                .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?,
+            token_generation: stored.token_generation,
+        })
+    }
+
+    async fn record_reset_email(
+        &mut self,
+        email: &Email,
+        cooldown_secs: u64,
+        max_emails: u32,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let mut conn = self.conn.write().await;
+        let key = get_emails_key(email);
+        let now = Utc::now().timestamp();
+
+        // The key expires with the window, so a missing key starts a new one
+        let json: Option<String> = conn
+            .get(&key)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        let sent = match json {
+            Some(json) => {
+                let sent: StoredEmails = serde_json::from_str(&json)
+                    .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+                if sent.count >= max_emails {
+                    return Err(PasswordResetTokenStoreError::TooManyResetEmails);
+                }
+                let wait = sent.sent_at + cooldown_secs as i64 - now;
+                if wait > 0 {
+                    return Err(PasswordResetTokenStoreError::ResetEmailCooldown(
+                        wait as u64,
+                    ));
+                }
+                StoredEmails {
+                    window_started_at: sent.window_started_at,
+                    sent_at: now,
+                    count: sent.count + 1,
+                }
+            }
+            None => StoredEmails {
+                window_started_at: now,
+                sent_at: now,
+                count: 1,
+            },
+        };
+
+        let ttl = sent.window_started_at + PASSWORD_RESET_EMAIL_WINDOW_SECONDS as i64 - now;
+        let json = serde_json::to_string(&sent)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        let _: () = conn
+            .set_ex(&key, json, ttl.max(1) as u64)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredReset {
+    email: String,
+    token_generation: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredEmails {
+    window_started_at: i64,
+    sent_at: i64,
+    count: u32,
+}
+
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+const PASSWORD_RESET_EMAILS_PREFIX: &str = "password_reset_emails:";
+
+fn get_key(token: &PasswordResetToken) -> String {
+    format!("{}{}", PASSWORD_RESET_TOKEN_PREFIX, token.hash())
+}
+
+fn get_emails_key(email: &Email) -> String {
+    format!("{}{}", PASSWORD_RESET_EMAILS_PREFIX, email.as_ref())
+}
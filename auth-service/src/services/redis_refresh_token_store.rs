@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{RefreshTokenStore, RefreshTokenStoreError},
+    utils::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    async fn set_current_token(
+        &mut self,
+        family: &str,
+        token_id: &str,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let key = get_key(family);
+
+        let ttl: u64 = REFRESH_TOKEN_TTL_SECONDS
+            .try_into()
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(&key, token_id, ttl)
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_current_token(&self, family: &str) -> Result<String, RefreshTokenStoreError> {
+        let key = get_key(family);
+
+        let token_id: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(&key)
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        token_id.ok_or(RefreshTokenStoreError::FamilyNotFound)
+    }
+
+    async fn revoke_family(&mut self, family: &str) -> Result<(), RefreshTokenStoreError> {
+        let key = get_key(family);
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .del(&key)
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+const REFRESH_TOKEN_FAMILY_KEY_PREFIX: &str = "refresh_token_family:";
+
+fn get_key(family: &str) -> String {
+    format!("{}{}", REFRESH_TOKEN_FAMILY_KEY_PREFIX, family)
+}
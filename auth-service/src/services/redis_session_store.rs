@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, Session, SessionStore, SessionStoreError},
+    utils::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisSessionStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let session_key = get_session_key(&session.id);
+        let user_key = get_user_sessions_key(&session.email);
+
+        let stored_session = StoredSession {
+            email: session.email.as_ref().to_owned(),
+            created_at: session.created_at,
+            user_agent: session.user_agent,
+        };
+        let json = serde_json::to_string(&stored_session)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        // Sessions end when their refresh token family would expire
+        let ttl: u64 = REFRESH_TOKEN_TTL_SECONDS
+            .try_into()
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .set_ex(&session_key, json, ttl)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .sadd(&user_key, &session.id)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&user_key, REFRESH_TOKEN_TTL_SECONDS)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: &str) -> Result<Session, SessionStoreError> {
+        let json: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(get_session_key(session_id))
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let json = json.ok_or(SessionStoreError::SessionNotFound)?;
+        parse_session(session_id, &json)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let user_key = get_user_sessions_key(email);
+        let mut conn = self.conn.write().await;
+
+        let session_ids: Vec<String> = conn
+            .smembers(&user_key)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let mut sessions = Vec::with_capacity(session_ids.len());
+        for session_id in session_ids {
+            let json: Option<String> = conn
+                .get(get_session_key(&session_id))
+                .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+            match json {
+                Some(json) => sessions.push(parse_session(&session_id, &json)?),
+                // The session expired, so drop it from the user's index as well
+                None => {
+                    let _: () = conn
+                        .srem(&user_key, &session_id)
+                        .map_err(|_| SessionStoreError::UnexpectedError)?;
+                }
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    async fn refresh_session(&mut self, session_id: &str) -> Result<(), SessionStoreError> {
+        let session = self.get_session(session_id).await?;
+
+        // Rotating the refresh token restarts its lifetime, so the session has to outlive it
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .expire(get_session_key(session_id), REFRESH_TOKEN_TTL_SECONDS)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(get_user_sessions_key(&session.email), REFRESH_TOKEN_TTL_SECONDS)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn remove_session(&mut self, session_id: &str) -> Result<(), SessionStoreError> {
+        let session = match self.get_session(session_id).await {
+            Ok(session) => session,
+            Err(SessionStoreError::SessionNotFound) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .del(get_session_key(session_id))
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .srem(get_user_sessions_key(&session.email), session_id)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    email: String,
+    created_at: i64,
+    user_agent: Option<String>,
+}
+
+fn parse_session(session_id: &str, json: &str) -> Result<Session, SessionStoreError> {
+    let stored_session = serde_json::from_str::<StoredSession>(json)
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+    let email =
+        Email::parse(stored_session.email).map_err(|_| SessionStoreError::UnexpectedError)?;
+
+    Ok(Session {
+        id: session_id.to_owned(),
+        email,
+        created_at: stored_session.created_at,
+        user_agent: stored_session.user_agent,
+    })
+}
+
+const SESSION_KEY_PREFIX: &str = "session:";
+const USER_SESSIONS_KEY_PREFIX: &str = "user_sessions:";
+
+fn get_session_key(session_id: &str) -> String {
+    format!("{}{}", SESSION_KEY_PREFIX, session_id)
+}
+
+fn get_user_sessions_key(email: &Email) -> String {
+    format!("{}{}", USER_SESSIONS_KEY_PREFIX, email.as_ref())
+}
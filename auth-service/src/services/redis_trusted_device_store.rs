@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, TrustedDevice, TrustedDeviceStore, TrustedDeviceStoreError},
+    utils::TRUSTED_DEVICE_TTL_SECONDS,
+};
+
+pub struct RedisTrustedDeviceStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisTrustedDeviceStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for RedisTrustedDeviceStore {
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        let device_key = get_device_key(&device.id);
+        let user_key = get_user_devices_key(&device.email);
+
+        let stored_device = StoredTrustedDevice {
+            email: device.email.as_ref().to_owned(),
+            created_at: device.created_at,
+            user_agent: device.user_agent,
+        };
+        let json = serde_json::to_string(&stored_device)
+            .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+        // Devices are forgotten when their cookie would expire
+        let ttl: u64 = TRUSTED_DEVICE_TTL_SECONDS
+            .try_into()
+            .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(&device_key, json, ttl)
+            .sadd(&user_key, &device.id)
+            .expire(&user_key, TRUSTED_DEVICE_TTL_SECONDS)
+            .query(&mut *self.conn.write().await)
+            .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_device(&self, device_id: &str) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+        let json: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(get_device_key(device_id))
+            .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+        let json = json.ok_or(TrustedDeviceStoreError::DeviceNotFound)?;
+        parse_device(device_id, &json)
+    }
+
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let user_key = get_user_devices_key(email);
+        let mut conn = self.conn.write().await;
+
+        let device_ids: Vec<String> = conn
+            .smembers(&user_key)
+            .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+        let mut devices = Vec::with_capacity(device_ids.len());
+        for device_id in device_ids {
+            let json: Option<String> = conn
+                .get(get_device_key(&device_id))
+                .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+            match json {
+                Some(json) => devices.push(parse_device(&device_id, &json)?),
+                // The device expired, so drop it from the user's index as well
+                None => {
+                    let _: () = conn
+                        .srem(&user_key, &device_id)
+                        .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+                }
+            }
+        }
+
+        Ok(devices)
+    }
+
+    async fn remove_device(&mut self, device_id: &str) -> Result<(), TrustedDeviceStoreError> {
+        let device = match self.get_device(device_id).await {
+            Ok(device) => device,
+            Err(TrustedDeviceStoreError::DeviceNotFound) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let _: () = redis::pipe()
+            .atomic()
+            .del(get_device_key(device_id))
+            .srem(get_user_devices_key(&device.email), device_id)
+            .query(&mut *self.conn.write().await)
+            .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredTrustedDevice {
+    email: String,
+    created_at: i64,
+    user_agent: Option<String>,
+}
+
+fn parse_device(device_id: &str, json: &str) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+    let stored_device = serde_json::from_str::<StoredTrustedDevice>(json)
+        .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+    let email = Email::parse(stored_device.email)
+        .map_err(|_| TrustedDeviceStoreError::UnexpectedError)?;
+
+    Ok(TrustedDevice {
+        id: device_id.to_owned(),
+        email,
+        created_at: stored_device.created_at,
+        user_agent: stored_device.user_agent,
+    })
+}
+
+const TRUSTED_DEVICE_KEY_PREFIX: &str = "trusted_device:";
+const USER_TRUSTED_DEVICES_KEY_PREFIX: &str = "user_trusted_devices:";
+
+fn get_device_key(device_id: &str) -> String {
+    format!("{}{}", TRUSTED_DEVICE_KEY_PREFIX, device_id)
+}
+
+fn get_user_devices_key(email: &Email) -> String {
+    format!("{}{}", USER_TRUSTED_DEVICES_KEY_PREFIX, email.as_ref())
+}
@@ -1,21 +1,93 @@
 use std::sync::Arc;
 
+use chrono::Utc;
 use redis::{Commands, Connection};
+use ring::hmac;
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
 use crate::domain::{
     data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    Email,
+    Email, MAX_PENDING_LOGIN_ATTEMPTS, TWO_FA_CODE_REQUEST_WINDOW_SECONDS,
 };
 
 pub struct RedisTwoFACodeStore {
     conn: Arc<RwLock<Connection>>,
+    hash_key: hmac::Key,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    // All instances sharing the Redis database must use the same secret
+    pub fn new(conn: Arc<RwLock<Connection>>, secret: &[u8]) -> Self {
+        Self {
+            conn,
+            hash_key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+
+    async fn insert_attempt(
+        &mut self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code_hash: Option<String>,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+        let index_key = get_index_key(&email);
+
+        // The index outlives the codes it points to, so drop attempts that already expired
+        let index: Vec<(String, i64)> = conn
+            .hgetall(&index_key)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let mut pending = Vec::new();
+        for (id, created_at) in index {
+            if id == login_attempt_id.as_ref() {
+                continue;
+            }
+            let exists: bool = conn
+                .exists(get_key(&email, &id))
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+            if exists {
+                pending.push((created_at, id));
+            } else {
+                let _: () = conn
+                    .hdel(&index_key, &id)
+                    .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+            }
+        }
+
+        // Make room for the new attempt by dropping the oldest ones
+        pending.sort();
+        let excess = (pending.len() + 1).saturating_sub(MAX_PENDING_LOGIN_ATTEMPTS);
+        for (_, id) in pending.into_iter().take(excess) {
+            let _: () = redis::pipe()
+                .atomic()
+                .del(&[get_key(&email, &id), get_attempts_key(&email, &id)])
+                .hdel(&index_key, &id)
+                .query(&mut *conn)
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        }
+
+        let id = login_attempt_id.as_ref();
+        let entry = TwoFACodeEntry {
+            code_hash,
+            sent_at: Utc::now().timestamp(),
+            resends: 0,
+        };
+        let json =
+            serde_json::to_string(&entry).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // A new code starts without failed attempts
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(get_key(&email, id), json, TEN_MINUTES_IN_SECONDS)
+            .del(get_attempts_key(&email, id))
+            .hset(&index_key, id, Utc::now().timestamp_millis())
+            .expire(&index_key, TEN_MINUTES_IN_SECONDS as i64)
+            .query(&mut *conn)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
     }
 }
 
@@ -27,80 +99,330 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
-        let two_fa_tuple = TwoFATuple(login_attempt_id.as_ref().to_string(), code.as_ref().to_string());
-        let json = serde_json::to_string(&two_fa_tuple);
-        if json.is_err() {
-            return Err(TwoFACodeStoreError::UnexpectedError);
-        }
-        
-        let _: () = self
-            .conn
+        let code_hash = code.hash(&self.hash_key);
+        self.insert_attempt(email, login_attempt_id, Some(code_hash))
+            .await
+    }
+
+    async fn add_login_attempt(
+        &mut self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        self.insert_attempt(email, login_attempt_id, None).await
+    }
+
+    async fn remove_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let id = login_attempt_id.as_ref();
+
+        let _: () = redis::pipe()
+            .atomic()
+            .del(&[get_key(email, id), get_attempts_key(email, id)])
+            .hdel(get_index_key(email), id)
+            .query(&mut *self.conn.write().await)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    async fn contains_code(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<bool, TwoFACodeStoreError> {
+        self.conn
             .write()
             .await
-            .set_ex(
-                &key,
-                json.unwrap(),
-                TEN_MINUTES_IN_SECONDS,
-            )
+            .exists(get_key(email, login_attempt_id.as_ref()))
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+        let id = login_attempt_id.as_ref();
+
+        let (json, entry) = get_entry(&mut conn, email, login_attempt_id)?;
+        match &entry.code_hash {
+            Some(code_hash) if code.matches_hash(&self.hash_key, code_hash) => (),
+            _ => return Err(TwoFACodeStoreError::IncorrectCode),
+        }
+
+        // The code is only deleted if it is still the one that was checked. When
+        // another request consumed or replaced it in the meantime, this one fails.
+        let consumed: bool = redis::Script::new(CONSUME_CODE_SCRIPT)
+            .key(get_key(email, id))
+            .key(get_attempts_key(email, id))
+            .key(get_index_key(email))
+            .arg(&json)
+            .arg(id)
+            .invoke(&mut *conn)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        match consumed {
+            true => Ok(()),
+            false => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        }
+    }
+
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+        let id = login_attempt_id.as_ref();
+
+        let exists: bool = conn
+            .exists(get_key(email, id))
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-        
+        if !exists {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        // The counter expires along with the code it belongs to
+        let (failed_attempts, _): (u32, ()) = redis::pipe()
+            .atomic()
+            .incr(get_attempts_key(email, id), 1)
+            .expire(get_attempts_key(email, id), TEN_MINUTES_IN_SECONDS as i64)
+            .query(&mut *conn)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        if failed_attempts >= max_attempts {
+            let _: () = redis::pipe()
+                .atomic()
+                .del(&[get_key(email, id), get_attempts_key(email, id)])
+                .hdel(get_index_key(email), id)
+                .query(&mut *conn)
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
+    async fn replace_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        cooldown_secs: u64,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+        let id = login_attempt_id.as_ref();
+
+        let (_, entry) = get_entry(&mut conn, email, login_attempt_id)?;
+        if entry.resends >= max_resends {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        let now = Utc::now().timestamp();
+        let wait = entry.sent_at + cooldown_secs as i64 - now;
+        if wait > 0 {
+            return Err(TwoFACodeStoreError::ResendCooldown(wait as u64));
+        }
+
+        let entry = TwoFACodeEntry {
+            code_hash: Some(code.hash(&self.hash_key)),
+            sent_at: now,
+            resends: entry.resends + 1,
+        };
+        let json =
+            serde_json::to_string(&entry).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // The new code gets the full lifetime, failed attempts carry over to it
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(get_key(email, id), json, TEN_MINUTES_IN_SECONDS)
+            .expire(get_attempts_key(email, id), TEN_MINUTES_IN_SECONDS as i64)
+            .expire(get_index_key(email), TEN_MINUTES_IN_SECONDS as i64)
+            .query(&mut *conn)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn record_user_failure(
+        &mut self,
+        email: &Email,
+        max_failures: u32,
+        lockout_secs: u64,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let failures: u32 = redis::Script::new(RECORD_USER_FAILURE_SCRIPT)
+            .key(get_user_failures_key(email))
+            .arg(lockout_secs)
+            .invoke(&mut *self.conn.write().await)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        match failures >= max_failures {
+            true => Err(TwoFACodeStoreError::TooManyAttempts),
+            false => Ok(()),
+        }
+    }
+
+    async fn is_user_locked_out(
+        &self,
+        email: &Email,
+        max_failures: u32,
+    ) -> Result<bool, TwoFACodeStoreError> {
+        let failures: Option<u32> = self
+            .conn
+            .write()
+            .await
+            .get(get_user_failures_key(email))
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(failures.is_some_and(|failures| failures >= max_failures))
+    }
 
+    async fn clear_user_failures(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
         let _: () = self
             .conn
             .write()
             .await
-            .del(key)
+            .del(get_user_failures_key(email))
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
         Ok(())
     }
 
-    async fn get_code(
-        &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        // TODO:
-        // 1. Create a new key using the get_key helper function.
-        // 2. Call the get command on the Redis connection to get the value stored for the key. 
-        // Return TwoFACodeStoreError::LoginAttemptIdNotFound if the operation fails.
-        // If the operation succeeds, call serde_json::from_str to parse the JSON string into a TwoFATuple. 
-        // Then, parse the login attempt ID string and 2FA code string into a LoginAttemptId and TwoFACode type respectively.
-        // Return TwoFACodeStoreError::UnexpectedError if parsing fails.
-
-        let key = get_key(email);
-
-        let two_fa_tuple_json: String = self
+    async fn record_code_request(
+        &mut self,
+        key: &str,
+        cooldown_secs: u64,
+        max_requests: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+        let key = get_code_requests_key(key);
+        let now = Utc::now().timestamp();
+
+        // The key expires with the window, so a missing key starts a new count
+        let json: Option<String> = conn
+            .get(&key)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let count = match json {
+            Some(json) => {
+                let requests: CodeRequests = serde_json::from_str(&json)
+                    .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+                if requests.count >= max_requests {
+                    return Err(TwoFACodeStoreError::TooManyResends);
+                }
+                let wait = requests.last_request_at + cooldown_secs as i64 - now;
+                if wait > 0 {
+                    return Err(TwoFACodeStoreError::ResendCooldown(wait as u64));
+                }
+                requests.count
+            }
+            None => 0,
+        };
+
+        let json = serde_json::to_string(&CodeRequests {
+            last_request_at: now,
+            count: count + 1,
+        })
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let _: () = conn
+            .set_ex(&key, json, TWO_FA_CODE_REQUEST_WINDOW_SECONDS)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn clear_code_requests(&mut self, key: &str) -> Result<(), TwoFACodeStoreError> {
+        let _: () = self
             .conn
             .write()
             .await
-            .get(key)
-            .map_err(|_| TwoFACodeStoreError::LoginAttemptIdNotFound)?;
-        
-        let two_fa_tuple = serde_json::from_str::<TwoFATuple>(&two_fa_tuple_json);
-        if two_fa_tuple.is_err() {
-            return Err(TwoFACodeStoreError::UnexpectedError);
-        }
-        let two_fa_tuple = two_fa_tuple.unwrap();
-        let login_attempt_id = LoginAttemptId::parse(two_fa_tuple.0)
-            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-        let two_fa_code = TwoFACode::parse(two_fa_tuple.1)
+            .del(get_code_requests_key(key))
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-        
-        Ok((login_attempt_id, two_fa_code))
+        Ok(())
     }
 }
 
+// Returns the stored JSON along with the parsed entry
+fn get_entry(
+    conn: &mut Connection,
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+) -> Result<(String, TwoFACodeEntry), TwoFACodeStoreError> {
+    let json: Option<String> = conn
+        .get(get_key(email, login_attempt_id.as_ref()))
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+    let json = json.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+    let entry =
+        serde_json::from_str(&json).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+    Ok((json, entry))
+}
+
+#[derive(Serialize, Deserialize)]
+struct TwoFACodeEntry {
+    // None for login attempts that were never sent a code
+    code_hash: Option<String>,
+    // Unix time the code was sent at
+    sent_at: i64,
+    resends: u32,
+}
+
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct CodeRequests {
+    last_request_at: i64,
+    count: u32,
+}
+
+// Deletes the code, its failed attempts and its index entry if the code still holds
+// the expected value
+const CONSUME_CODE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('DEL', KEYS[1], KEYS[2])
+redis.call('HDEL', KEYS[3], ARGV[2])
+return 1
+"#;
+
+// Counts a failure, the first one of a count starts the lockout period
+const RECORD_USER_FAILURE_SCRIPT: &str = r#"
+local failures = redis.call('INCR', KEYS[1])
+if failures == 1 then
+    redis.call('EXPIRE', KEYS[1], ARGV[1])
+end
+return failures
+"#;
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_ATTEMPTS_PREFIX: &str = "two_fa_attempts:";
+const TWO_FA_LOGIN_ATTEMPTS_PREFIX: &str = "two_fa_login_attempts:";
+const TWO_FA_USER_FAILURES_PREFIX: &str = "two_fa_user_failures:";
+const TWO_FA_CODE_REQUESTS_PREFIX: &str = "two_fa_code_requests:";
+
+fn get_key(email: &Email, login_attempt_id: &str) -> String {
+    format!("{}{}:{}", TWO_FA_CODE_PREFIX, email.as_ref(), login_attempt_id)
+}
+
+fn get_attempts_key(email: &Email, login_attempt_id: &str) -> String {
+    format!("{}{}:{}", TWO_FA_ATTEMPTS_PREFIX, email.as_ref(), login_attempt_id)
+}
+
+// Hash of the user's pending login attempt ids and when they were created
+fn get_index_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_LOGIN_ATTEMPTS_PREFIX, email.as_ref())
+}
+
+fn get_user_failures_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_USER_FAILURES_PREFIX, email.as_ref())
+}
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
-}
\ No newline at end of file
+fn get_code_requests_key(key: &str) -> String {
+    format!("{}{}", TWO_FA_CODE_REQUESTS_PREFIX, key)
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    Email, VerificationEmailStore, VerificationEmailStoreError,
+    VERIFICATION_EMAIL_RESEND_WINDOW_SECONDS,
+};
+
+pub struct RedisVerificationEmailStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisVerificationEmailStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl VerificationEmailStore for RedisVerificationEmailStore {
+    async fn record_resend(
+        &mut self,
+        email: &Email,
+        cooldown_secs: u64,
+        max_resends: u32,
+    ) -> Result<(), VerificationEmailStoreError> {
+        let mut conn = self.conn.write().await;
+        let key = get_key(email);
+        let now = Utc::now().timestamp();
+
+        // The key expires with the window, so a missing key starts a new one
+        let json: Option<String> = conn
+            .get(&key)
+            .map_err(|_| VerificationEmailStoreError::UnexpectedError)?;
+        let resends = match json {
+            Some(json) => {
+                let resends: StoredResends = serde_json::from_str(&json)
+                    .map_err(|_| VerificationEmailStoreError::UnexpectedError)?;
+                if resends.count >= max_resends {
+                    return Err(VerificationEmailStoreError::TooManyResends);
+                }
+                let wait = resends.sent_at + cooldown_secs as i64 - now;
+                if wait > 0 {
+                    return Err(VerificationEmailStoreError::ResendCooldown(wait as u64));
+                }
+                StoredResends {
+                    window_started_at: resends.window_started_at,
+                    sent_at: now,
+                    count: resends.count + 1,
+                }
+            }
+            None => StoredResends {
+                window_started_at: now,
+                sent_at: now,
+                count: 1,
+            },
+        };
+
+        let ttl = resends.window_started_at + VERIFICATION_EMAIL_RESEND_WINDOW_SECONDS as i64 - now;
+        let json = serde_json::to_string(&resends)
+            .map_err(|_| VerificationEmailStoreError::UnexpectedError)?;
+        let _: () = conn
+            .set_ex(&key, json, ttl.max(1) as u64)
+            .map_err(|_| VerificationEmailStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredResends {
+    window_started_at: i64,
+    sent_at: i64,
+    count: u32,
+}
+
+const VERIFICATION_EMAIL_RESENDS_PREFIX: &str = "verification_email_resends:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", VERIFICATION_EMAIL_RESENDS_PREFIX, email.as_ref())
+}
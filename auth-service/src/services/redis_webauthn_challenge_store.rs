@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    Email, LoginAttemptId, PendingWebAuthnCeremony, WebAuthnCeremony, WebAuthnChallenge,
+    WebAuthnChallengeStore, WebAuthnChallengeStoreError, WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+pub struct RedisWebAuthnChallengeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisWebAuthnChallengeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for RedisWebAuthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        ceremony_id: &str,
+        pending: PendingWebAuthnCeremony,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let (ceremony, email, login_attempt_id, user_handle) = match pending.ceremony {
+            WebAuthnCeremony::Registration { email, user_handle } => (
+                REGISTRATION,
+                Some(email.as_ref().to_owned()),
+                None,
+                Some(user_handle),
+            ),
+            WebAuthnCeremony::SecondFactor {
+                email,
+                login_attempt_id,
+            } => (
+                SECOND_FACTOR,
+                Some(email.as_ref().to_owned()),
+                Some(login_attempt_id.as_ref().to_owned()),
+                None,
+            ),
+            WebAuthnCeremony::Passwordless => (PASSWORDLESS, None, None, None),
+        };
+
+        let stored = StoredCeremony {
+            challenge: pending.challenge.as_ref().to_owned(),
+            ceremony: ceremony.to_owned(),
+            email,
+            login_attempt_id,
+            user_handle,
+        };
+        let json = serde_json::to_string(&stored)
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(ceremony_id), json, WEBAUTHN_CHALLENGE_TTL_SECONDS)
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn take_challenge(
+        &mut self,
+        ceremony_id: &str,
+    ) -> Result<PendingWebAuthnCeremony, WebAuthnChallengeStoreError> {
+        let key = get_key(ceremony_id);
+
+        let (json, _): (Option<String>, ()) = redis::pipe()
+            .atomic()
+            .get(&key)
+            .del(&key)
+            .query(&mut *self.conn.write().await)
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let json = json.ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)?;
+        let stored: StoredCeremony = serde_json::from_str(&json)
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        parse_ceremony(stored).ok_or(WebAuthnChallengeStoreError::UnexpectedError)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredCeremony {
+    challenge: String,
+    ceremony: String,
+    email: Option<String>,
+    login_attempt_id: Option<String>,
+    #[serde(default)]
+    user_handle: Option<String>,
+}
+
+fn parse_ceremony(stored: StoredCeremony) -> Option<PendingWebAuthnCeremony> {
+    let email = stored.email.map(Email::parse).transpose().ok()?;
+    let login_attempt_id = stored
+        .login_attempt_id
+        .map(LoginAttemptId::parse)
+        .transpose()
+        .ok()?;
+
+    let ceremony = match (
+        stored.ceremony.as_str(),
+        email,
+        login_attempt_id,
+        stored.user_handle,
+    ) {
+        (REGISTRATION, Some(email), None, Some(user_handle)) => {
+            WebAuthnCeremony::Registration { email, user_handle }
+        }
+        (SECOND_FACTOR, Some(email), Some(login_attempt_id), None) => {
+            WebAuthnCeremony::SecondFactor {
+                email,
+                login_attempt_id,
+            }
+        }
+        (PASSWORDLESS, None, None, None) => WebAuthnCeremony::Passwordless,
+        _ => return None,
+    };
+
+    Some(PendingWebAuthnCeremony {
+        challenge: WebAuthnChallenge::parse(stored.challenge).ok()?,
+        ceremony,
+    })
+}
+
+const REGISTRATION: &str = "registration";
+const SECOND_FACTOR: &str = "second_factor";
+const PASSWORDLESS: &str = "passwordless";
+
+const WEBAUTHN_CHALLENGE_PREFIX: &str = "webauthn_challenge:";
+
+fn get_key(ceremony_id: &str) -> String {
+    format!("{}{}", WEBAUTHN_CHALLENGE_PREFIX, ceremony_id)
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::domain::{Email, EmailClient, EmailClientError, EmailContent};
+
+// How the connection to the SMTP server is secured
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmtpTls {
+    // Plain text, only meant for local development servers
+    None,
+    // Upgrades a plain connection, usually on port 587
+    StartTls,
+    // TLS from the first byte, usually on port 465
+    Implicit,
+}
+
+impl SmtpTls {
+    pub fn parse(tls: &str) -> Result<Self, String> {
+        match tls {
+            "none" => Ok(Self::None),
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Implicit),
+            _ => Err(format!("{} is not a valid SMTP TLS mode", tls)),
+        }
+    }
+}
+
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    // Username and password, if the server requires authentication
+    pub credentials: Option<(String, String)>,
+    pub sender: Mailbox,
+    pub timeout: Duration,
+}
+
+pub struct SmtpEmailClient {
+    sender: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    timeout: Duration,
+}
+
+impl SmtpEmailClient {
+    pub fn new(settings: SmtpSettings) -> Result<Self, String> {
+        let builder = match settings.tls {
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                &settings.host,
+            )),
+            SmtpTls::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+            }
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host),
+        }
+        .map_err(|e| format!("Invalid SMTP host {}: {}", settings.host, e))?;
+
+        let builder = builder.port(settings.port).timeout(Some(settings.timeout));
+        let builder = match settings.credentials {
+            Some((username, password)) => builder.credentials(Credentials::new(username, password)),
+            None => builder,
+        };
+
+        Ok(Self {
+            sender: settings.sender,
+            transport: builder.build(),
+            timeout: settings.timeout,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        content: &EmailContent,
+    ) -> Result<(), EmailClientError> {
+        let recipient = recipient
+            .as_ref()
+            .parse()
+            .map_err(|e| EmailClientError::InvalidEmail(format!("Invalid recipient: {}", e)))?;
+
+        let message = Message::builder()
+            .from(self.sender.clone())
+            .to(Mailbox::new(None, recipient))
+            .subject(&content.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                content.text_body.clone(),
+                content.html_body.clone(),
+            ))
+            .map_err(|e| EmailClientError::InvalidEmail(e.to_string()))?;
+
+        // The transport only limits how long connecting may take, a server that
+        // stops responding halfway through would hold up the request forever
+        tokio::time::timeout(self.timeout, self.transport.send(message))
+            .await
+            .map_err(|_| EmailClientError::Unavailable("Timed out sending email".to_owned()))?
+            .map_err(|e| match e.is_permanent() {
+                // 5xx replies, like an unknown recipient
+                true => EmailClientError::Rejected(e.to_string()),
+                false => EmailClientError::Unavailable(e.to_string()),
+            })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tls() {
+        assert_eq!(SmtpTls::parse("none"), Ok(SmtpTls::None));
+        assert_eq!(SmtpTls::parse("starttls"), Ok(SmtpTls::StartTls));
+        assert_eq!(SmtpTls::parse("tls"), Ok(SmtpTls::Implicit));
+        assert!(SmtpTls::parse("ssl").is_err());
+    }
+}
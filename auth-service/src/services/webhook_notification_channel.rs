@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use reqwest::header;
+use serde::Serialize;
+
+use crate::domain::{NotificationChannel, NotificationTarget};
+
+pub struct WebhookSettings {
+    pub url: String,
+    // Sent as a bearer token, so the gateway can tell the requests come from us
+    pub token: String,
+    pub timeout: Duration,
+}
+
+// Hands notifications to an SMS gateway or push provider that accepts them over
+// HTTP. The outbox worker isn't involved, codes are only useful for a few minutes.
+pub struct WebhookNotificationChannel {
+    http_client: reqwest::Client,
+    url: String,
+    token: String,
+}
+
+impl WebhookNotificationChannel {
+    pub fn new(settings: WebhookSettings) -> Result<Self, String> {
+        let http_client = reqwest::Client::builder()
+            .timeout(settings.timeout)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        Ok(Self {
+            http_client,
+            url: settings.url,
+            token: settings.token,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for WebhookNotificationChannel {
+    async fn send_notification(
+        &self,
+        recipient: &NotificationTarget,
+        content: &str,
+    ) -> Result<(), String> {
+        let response = self
+            .http_client
+            .post(&self.url)
+            .header(header::ACCEPT, "application/json")
+            .bearer_auth(&self.token)
+            .json(&NotificationRequest {
+                to: recipient.as_ref(),
+                message: content,
+            })
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        Err(format!(
+            "Notification webhook responded with {}: {}",
+            status, body
+        ))
+    }
+}
+
+#[derive(Serialize)]
+struct NotificationRequest<'a> {
+    to: &'a str,
+    message: &'a str,
+}
@@ -1,15 +1,136 @@
+use std::sync::{Arc, RwLock};
+
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::{decode, decode_header, encode, jwk::JwkSet, Header};
+use lazy_static::lazy_static;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::{BannedTokenStoreType, RefreshTokenStoreType, SessionStoreType, UserStoreType},
+    domain::{Email, Session},
+};
+
+use super::{
+    constants::{
+        load_jwt_signing_key, load_previous_jwt_signing_keys, JWT_AUDIENCE, JWT_COOKIE_NAME, JWT_ISSUER, JWT_REFRESH_COOKIE_NAME,
+        TRUSTED_DEVICE_COOKIE_NAME,
+    },
+    jwt_keys::{JwtKeyring, JwtSigningKey},
+};
+
+lazy_static! {
+    static ref JWT_KEYRING: RwLock<JwtKeyring> = RwLock::new(load_jwt_keyring());
+}
+
+fn load_jwt_keyring() -> JwtKeyring {
+    let mut keyring =
+        JwtKeyring::new(load_jwt_signing_key().expect("Failed to load JWT signing key"));
+    keyring.set_previous(
+        load_previous_jwt_signing_keys().expect("Failed to load previous JWT signing keys"),
+    );
+    keyring
+}
 
-use crate::{app_state::BannedTokenStoreType, domain::Email};
+// Reload the signing keys from the environment and key files and make the current
+// key the active one. Tokens signed with the key it replaces stay valid until they
+// expire, tokens signed with configured previous keys as long as they're configured.
+pub fn reload_jwt_signing_key() -> Result<(), String> {
+    dotenvy::dotenv_override().ok();
+    let key = load_jwt_signing_key()?;
+    let previous = load_previous_jwt_signing_keys()?;
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+    let mut keyring = JWT_KEYRING.write().unwrap_or_else(|e| e.into_inner());
+    keyring.rotate(key, MAX_TOKEN_TTL_SECONDS);
+    keyring.set_previous(previous);
+    Ok(())
+}
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+pub fn rotate_jwt_signing_key(key: JwtSigningKey) {
+    JWT_KEYRING
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .rotate(key, MAX_TOKEN_TTL_SECONDS);
+}
+
+// Public keys of the active and retiring signing keys
+pub fn jwt_public_keys() -> JwkSet {
+    JWT_KEYRING
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .public_keys()
+}
+
+fn active_signing_key() -> Arc<JwtSigningKey> {
+    JWT_KEYRING.read().unwrap_or_else(|e| e.into_inner()).active()
+}
+
+// Decode a JWT with the keyring key named by the `kid` in its header, requiring it
+// to be issued by this service for the given audience
+fn decode_with_keyring<T: DeserializeOwned>(
+    token: &str,
+    header: &Header,
+    audience: &str,
+) -> Result<T, jsonwebtoken::errors::Error> {
+    let keyring = JWT_KEYRING.read().unwrap_or_else(|e| e.into_inner());
+    decode_with(&keyring, token, header, audience)
+}
+
+fn decode_with<T: DeserializeOwned>(
+    keyring: &JwtKeyring,
+    token: &str,
+    header: &Header,
+    audience: &str,
+) -> Result<T, jsonwebtoken::errors::Error> {
+    let key = match &header.kid {
+        Some(kid) => keyring
+            .find(kid)
+            .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?,
+        // Tokens issued before key ids were introduced
+        None => keyring.active(),
+    };
+
+    let mut validation = key.validation();
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+    validation.set_audience(&[audience]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+
+    decode::<T>(token, key.decoding_key(), &validation).map(|data| data.claims)
+}
+
+// Register a new session for the user and create its auth and refresh cookies
+pub async fn start_session(
+    email: &Email,
+    token_generation: i32,
+    user_agent: Option<String>,
+    session_store: SessionStoreType,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<(Cookie<'static>, Cookie<'static>), GenerateTokenError> {
+    let session = Session::new(email.clone(), user_agent);
+    let session_id = session.id.clone();
+
+    session_store
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    let auth_cookie = generate_auth_cookie(email, &session_id, token_generation)?;
+    let refresh_cookie = start_refresh_token_family(email, &session_id, refresh_token_store).await?;
+
+    Ok((auth_cookie, refresh_cookie))
+}
+
+// Create cookie with a new JWT auth token for the given session
+pub fn generate_auth_cookie(
+    email: &Email,
+    session_id: &str,
+    token_generation: i32,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(email, session_id, token_generation)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -33,32 +154,290 @@ pub enum GenerateTokenError {
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
-// Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
+// Auth tokens aren't limited to parts of the API, so they all share this scope
+pub const AUTH_TOKEN_SCOPE: &str = "account";
+
+// Returns the issued-at and expiration timestamps for a token valid for `ttl_seconds`
+fn token_lifetime(ttl_seconds: i64) -> Result<(usize, usize), GenerateTokenError> {
+    let delta = chrono::Duration::try_seconds(ttl_seconds)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
+    let now = Utc::now();
+
     // Create JWT expiration time
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
 
-    // Cast exp to a usize, which is what Claims expects
+    // Cast timestamps to usize, which is what the claims expect
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
     let exp: usize = exp
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
-    let sub = email.as_ref().to_owned();
+    Ok((iat, exp))
+}
+
+// Create JWT auth token
+fn generate_auth_token(
+    email: &Email,
+    session_id: &str,
+    token_generation: i32,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS)?;
 
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub: email.as_ref().to_owned(),
+        exp,
+        iat,
+        nbf: iat,
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_AUDIENCE.to_owned(),
+        jti: Uuid::new_v4().to_string(),
+        sid: session_id.to_owned(),
+        generation: token_generation,
+    };
 
     create_token(&claims).map_err(GenerateTokenError::TokenError)
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
-pub async fn validate_token(token: &str, banned_token_store: BannedTokenStoreType) -> Result<Claims, jsonwebtoken::errors::Error> {
-    match banned_token_store.read().await.contains_token(token).await {
+// Start a new refresh token family and create a cookie with its first refresh token
+pub async fn start_refresh_token_family(
+    email: &Email,
+    family: &str,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token_id = Uuid::new_v4().to_string();
+
+    refresh_token_store
+        .write()
+        .await
+        .set_current_token(family, &token_id)
+        .await
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    generate_refresh_cookie(email, family, &token_id)
+}
+
+// Create cookie with a new JWT refresh token belonging to the given token family
+pub fn generate_refresh_cookie(
+    email: &Email,
+    family: &str,
+    token_id: &str,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_refresh_token(email, family, token_id)?;
+    Ok(create_refresh_cookie(token))
+}
+
+// Create refresh cookie and set the value to the passed-in token string
+fn create_refresh_cookie(token: String) -> Cookie<'static> {
+    Cookie::build((JWT_REFRESH_COOKIE_NAME, token))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .build()
+}
+
+// This value determines how long a JWT refresh token is valid for
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 604800; // 7 days
+
+// Marks refresh tokens in the JWT header so they can't be used as auth tokens
+const REFRESH_TOKEN_TYPE: &str = "refresh+jwt";
+
+// Create JWT refresh token
+fn generate_refresh_token(
+    email: &Email,
+    family: &str,
+    token_id: &str,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(REFRESH_TOKEN_TTL_SECONDS)?;
+
+    // Refresh tokens are only ever redeemed by the auth service itself
+    let claims = RefreshClaims {
+        sub: email.as_ref().to_owned(),
+        exp,
+        iat,
+        nbf: iat,
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_ISSUER.to_owned(),
+        jti: token_id.to_owned(),
+        family: family.to_owned(),
+    };
+
+    let key = active_signing_key();
+    let mut header = key.header();
+    header.typ = Some(REFRESH_TOKEN_TYPE.to_owned());
+
+    encode(&header, &claims, key.encoding_key()).map_err(GenerateTokenError::TokenError)
+}
+
+// Decode a JWT refresh token. Whether it is still the current token of its family
+// has to be checked against the refresh token store by the caller.
+pub fn decode_refresh_token(token: &str) -> Result<RefreshClaims, jsonwebtoken::errors::Error> {
+    let header = decode_header(token)?;
+    if header.typ.as_deref() != Some(REFRESH_TOKEN_TYPE) {
+        return Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidToken,
+        ));
+    }
+
+    decode_with_keyring::<RefreshClaims>(token, &header, &JWT_ISSUER)
+}
+
+// Create cookie with a new JWT trusted device token for the given device. The
+// cookie outlives the browser session so the device stays remembered.
+pub fn generate_trusted_device_cookie(
+    email: &Email,
+    device_id: &str,
+    token_generation: i32,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_trusted_device_token(email, device_id, token_generation)?;
+
+    Ok(Cookie::build((TRUSTED_DEVICE_COOKIE_NAME, token))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(TRUSTED_DEVICE_TTL_SECONDS))
+        .build())
+}
+
+// This value determines how long a device is remembered for
+pub const TRUSTED_DEVICE_TTL_SECONDS: i64 = 2592000; // 30 days
+
+// Marks trusted device tokens in the JWT header so they can't be used as auth tokens
+const TRUSTED_DEVICE_TOKEN_TYPE: &str = "trusted-device+jwt";
+
+// Create JWT trusted device token
+fn generate_trusted_device_token(
+    email: &Email,
+    device_id: &str,
+    token_generation: i32,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(TRUSTED_DEVICE_TTL_SECONDS)?;
+
+    // Like refresh tokens, these are only ever checked by the auth service itself
+    let claims = TrustedDeviceClaims {
+        sub: email.as_ref().to_owned(),
+        exp,
+        iat,
+        nbf: iat,
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_ISSUER.to_owned(),
+        jti: device_id.to_owned(),
+        generation: token_generation,
+    };
+
+    let key = active_signing_key();
+    let mut header = key.header();
+    header.typ = Some(TRUSTED_DEVICE_TOKEN_TYPE.to_owned());
+
+    encode(&header, &claims, key.encoding_key()).map_err(GenerateTokenError::TokenError)
+}
+
+// Decode a JWT trusted device token. Whether the device is still trusted has to be
+// checked against the trusted device store by the caller.
+pub fn decode_trusted_device_token(
+    token: &str,
+) -> Result<TrustedDeviceClaims, jsonwebtoken::errors::Error> {
+    let header = decode_header(token)?;
+    if header.typ.as_deref() != Some(TRUSTED_DEVICE_TOKEN_TYPE) {
+        return Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidToken,
+        ));
+    }
+
+    decode_with_keyring::<TrustedDeviceClaims>(token, &header, &JWT_ISSUER)
+}
+
+// This value determines how long an email verification link can be used
+pub const EMAIL_VERIFICATION_TTL_SECONDS: i64 = 86400; // 1 day
+
+// Every token type is signed with the keyring, so a rotated out key has to verify
+// tokens for as long as the longest lived of them
+const MAX_TOKEN_TTL_SECONDS: i64 = max_ttl(&[
+    TOKEN_TTL_SECONDS,
+    REFRESH_TOKEN_TTL_SECONDS,
+    TRUSTED_DEVICE_TTL_SECONDS,
+    EMAIL_VERIFICATION_TTL_SECONDS,
+]);
+
+const fn max_ttl(ttls: &[i64]) -> i64 {
+    let mut max = 0;
+    let mut i = 0;
+    while i < ttls.len() {
+        if ttls[i] > max {
+            max = ttls[i];
+        }
+        i += 1;
+    }
+    max
+}
+
+// Marks email verification tokens in the JWT header so no other token verifies an email
+const EMAIL_VERIFICATION_TOKEN_TYPE: &str = "email-verification+jwt";
+
+// Create JWT token for the link that verifies the user's email address
+pub fn generate_email_verification_token(email: &Email) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(EMAIL_VERIFICATION_TTL_SECONDS)?;
+
+    let claims = EmailVerificationClaims {
+        sub: email.as_ref().to_owned(),
+        exp,
+        iat,
+        nbf: iat,
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_ISSUER.to_owned(),
+        jti: Uuid::new_v4().to_string(),
+    };
+
+    let key = active_signing_key();
+    let mut header = key.header();
+    header.typ = Some(EMAIL_VERIFICATION_TOKEN_TYPE.to_owned());
+
+    encode(&header, &claims, key.encoding_key()).map_err(GenerateTokenError::TokenError)
+}
+
+// Decode a JWT email verification token. Verifying an email twice is harmless, so
+// tokens aren't tracked and stay usable until they expire.
+pub fn decode_email_verification_token(
+    token: &str,
+) -> Result<EmailVerificationClaims, jsonwebtoken::errors::Error> {
+    let header = decode_header(token)?;
+    if header.typ.as_deref() != Some(EMAIL_VERIFICATION_TOKEN_TYPE) {
+        return Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidToken,
+        ));
+    }
+
+    decode_with_keyring::<EmailVerificationClaims>(token, &header, &JWT_ISSUER)
+}
+
+// Check if JWT auth token is valid by decoding it using the JWT keyring, and that
+// neither the token, its session nor all of the user's tokens have been revoked
+pub async fn validate_token(
+    token: &str,
+    banned_token_store: BannedTokenStoreType,
+    session_store: SessionStoreType,
+    user_store: UserStoreType,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    // Refresh and trusted device tokens are signed with the same keys but must not grant access
+    let header = decode_header(token)?;
+    if matches!(
+        header.typ.as_deref(),
+        Some(REFRESH_TOKEN_TYPE | TRUSTED_DEVICE_TOKEN_TYPE)
+    ) {
+        return Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidToken,
+        ));
+    }
+
+    let claims = decode_with_keyring::<Claims>(token, &header, &JWT_AUDIENCE)?;
+
+    match banned_token_store.read().await.contains_token(&claims.jti).await {
         Ok(value) => {
             if value {
                 return Err(jsonwebtoken::errors::Error::from(
@@ -73,27 +452,76 @@ pub async fn validate_token(token: &str, banned_token_store: BannedTokenStoreTyp
         }
     }
 
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
+    let email = match session_store.read().await.get_session(&claims.sid).await {
+        Ok(session) if session.email.as_ref() == claims.sub => session.email,
+        _ => {
+            return Err(jsonwebtoken::errors::Error::from(
+                jsonwebtoken::errors::ErrorKind::InvalidToken,
+            ));
+        }
+    };
+
+    match user_store.read().await.get_token_generation(&email).await {
+        Ok(generation) if generation == claims.generation => Ok(claims),
+        _ => Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidToken,
+        )),
+    }
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
+// Create JWT auth token by encoding claims using the active JWT signing key
 fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
+    let key = active_signing_key();
+    encode(&key.header(), &claims, key.encoding_key())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    pub iss: String,
+    pub aud: String,
+    pub jti: String,
+    pub sid: String,
+    pub generation: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    pub iss: String,
+    pub aud: String,
+    pub jti: String,
+    pub family: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDeviceClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    pub iss: String,
+    pub aud: String,
+    // Id of the trusted device
+    pub jti: String,
+    pub generation: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailVerificationClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    pub iss: String,
+    pub aud: String,
+    pub jti: String,
 }
 
 #[cfg(test)]
@@ -102,14 +530,38 @@ mod tests {
 
     use tokio::sync::RwLock;
 
-    use crate::{domain::BannedTokenStore, services::HashsetBannedTokenStore};
+    use crate::{
+        domain::{
+            BannedTokenStore, Password, RefreshTokenStore, SessionStore, TwoFAMethod, User,
+            UserStore,
+        },
+        services::{
+            HashmapRefreshTokenStore, HashmapSessionStore, HashmapUserStore, HashsetBannedTokenStore,
+        },
+    };
 
     use super::*;
 
+    // Registers a session for the user in a fresh session store
+    async fn test_session(email: &Email) -> (Session, SessionStoreType) {
+        let session = Session::new(email.clone(), None);
+        let mut session_store = HashmapSessionStore::default();
+        session_store.add_session(session.clone()).await.unwrap();
+        (session, Arc::new(RwLock::new(session_store)))
+    }
+
+    // Creates a user store holding the user, whose token generation is still 0
+    async fn test_user_store(email: &Email) -> UserStoreType {
+        let password = Password::parse("password123".to_owned()).unwrap();
+        let mut user_store = HashmapUserStore::default();
+        user_store.add_user(User::new(email.clone(), password, TwoFAMethod::None)).await.unwrap();
+        Arc::new(RwLock::new(user_store))
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&email, "session_id", 0).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -131,17 +583,30 @@ mod tests {
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&email, "session_id", 0).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token: String = generate_auth_token(&email).unwrap();
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let token: String = generate_auth_token(&email, &session.id, 0).unwrap();
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            session_store,
+            user_store,
+        )
+        .await
+        .unwrap();
         assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.iss, *JWT_ISSUER);
+        assert_eq!(result.aud, *JWT_AUDIENCE);
+        assert!(result.iat <= result.nbf);
+        assert!(!result.jti.is_empty());
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -154,19 +619,375 @@ mod tests {
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (_, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
         assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let mut hs = HashsetBannedTokenStore::default();
-        hs.add_token(token.clone()).await.unwrap();
-        let banned_token_store = Arc::new(RwLock::new(hs));
-        let result = validate_token(&token, banned_token_store).await;
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let token = generate_auth_token(&email, &session.id, 0).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let claims = validate_token(
+            &token,
+            banned_token_store.clone(),
+            session_store.clone(),
+            user_store.clone(),
+        )
+        .await
+        .unwrap();
+        banned_token_store.write().await.add_token(claims.jti).await.unwrap();
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_session() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let token = generate_auth_token(&email, &session.id, 0).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        session_store.write().await.remove_session(&session.id).await.unwrap();
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_session_of_other_user() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+        let (session, session_store) = test_session(&other_email).await;
+        let user_store = test_user_store(&other_email).await;
+        let token = generate_auth_token(&email, &session.id, 0).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_after_token_generation_increment() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let token = generate_auth_token(&email, &session.id, 0).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        user_store.write().await.increment_token_generation(&email).await.unwrap();
+        let result = validate_token(
+            &token,
+            banned_token_store.clone(),
+            session_store.clone(),
+            user_store.clone(),
+        )
+        .await;
+        assert!(result.is_err());
+
+        let token = generate_auth_token(&email, &session.id, 1).unwrap();
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_start_session() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let session_store = Arc::new(RwLock::new(HashmapSessionStore::default()));
+        let refresh_token_store = Arc::new(RwLock::new(HashmapRefreshTokenStore::default()));
+        let user_store = test_user_store(&email).await;
+        let (auth_cookie, refresh_cookie) = start_session(
+            &email,
+            0,
+            Some("test-agent".to_owned()),
+            session_store.clone(),
+            refresh_token_store,
+        )
+        .await
+        .unwrap();
+
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let claims = validate_token(
+            auth_cookie.value(),
+            banned_token_store,
+            session_store.clone(),
+            user_store,
+        )
+        .await
+        .unwrap();
+        let refresh_claims = decode_refresh_token(refresh_cookie.value()).unwrap();
+        assert_eq!(claims.sid, refresh_claims.family);
+
+        let session = session_store.read().await.get_session(&claims.sid).await.unwrap();
+        assert_eq!(session.email, email);
+        assert_eq!(session.user_agent, Some("test-agent".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_auth_tokens_have_unique_ids() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let first = generate_auth_token(&email, &session.id, 0).unwrap();
+        let second = generate_auth_token(&email, &session.id, 0).unwrap();
+        let first = validate_token(
+            &first,
+            banned_token_store.clone(),
+            session_store.clone(),
+            user_store.clone(),
+        )
+        .await
+        .unwrap();
+        let second = validate_token(
+            &second,
+            banned_token_store,
+            session_store,
+            user_store,
+        )
+        .await
+        .unwrap();
+        assert_ne!(first.jti, second.jti);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_wrong_audience() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS).unwrap();
+        let claims = Claims {
+            sub: "test@example.com".to_owned(),
+            exp,
+            iat,
+            nbf: iat,
+            iss: JWT_ISSUER.to_owned(),
+            aud: "some-other-service".to_owned(),
+            jti: Uuid::new_v4().to_string(),
+            sid: session.id,
+            generation: 0,
+        };
+        let token = create_token(&claims).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_wrong_issuer() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS).unwrap();
+        let claims = Claims {
+            sub: "test@example.com".to_owned(),
+            exp,
+            iat,
+            nbf: iat,
+            iss: "some-other-issuer".to_owned(),
+            aud: JWT_AUDIENCE.to_owned(),
+            jti: Uuid::new_v4().to_string(),
+            sid: session.id,
+            generation: 0,
+        };
+        let token = create_token(&claims).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_before_not_before() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS).unwrap();
+        let claims = Claims {
+            sub: "test@example.com".to_owned(),
+            exp,
+            iat,
+            nbf: iat + 300,
+            iss: JWT_ISSUER.to_owned(),
+            aud: JWT_AUDIENCE.to_owned(),
+            jti: Uuid::new_v4().to_string(),
+            sid: session.id,
+            generation: 0,
+        };
+        let token = create_token(&claims).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_refresh_cookie() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let cookie = generate_refresh_cookie(&email, "family", "token_id").unwrap();
+        assert_eq!(cookie.name(), JWT_REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.value().split('.').count(), 3);
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    }
+
+    #[tokio::test]
+    async fn test_start_refresh_token_family() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let refresh_token_store = Arc::new(RwLock::new(HashmapRefreshTokenStore::default()));
+        let cookie = start_refresh_token_family(&email, "session_id", refresh_token_store.clone())
+            .await
+            .unwrap();
+
+        let claims = decode_refresh_token(cookie.value()).unwrap();
+        assert_eq!(claims.sub, "test@example.com");
+        assert_eq!(claims.family, "session_id");
+
+        let current_token_id = refresh_token_store
+            .read()
+            .await
+            .get_current_token(&claims.family)
+            .await
+            .unwrap();
+        assert_eq!(current_token_id, claims.jti);
+    }
+
+    #[tokio::test]
+    async fn test_decode_refresh_token_with_auth_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = generate_auth_token(&email, "session_id", 0).unwrap();
+        let result = decode_refresh_token(&token);
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_refresh_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let token = generate_refresh_token(&email, &session.id, "token_id").unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_trusted_device_cookie() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let cookie = generate_trusted_device_cookie(&email, "device_id", 2).unwrap();
+        assert_eq!(cookie.name(), TRUSTED_DEVICE_COOKIE_NAME);
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(
+            cookie.max_age(),
+            Some(time::Duration::seconds(TRUSTED_DEVICE_TTL_SECONDS))
+        );
+
+        let claims = decode_trusted_device_token(cookie.value()).unwrap();
+        assert_eq!(claims.sub, "test@example.com");
+        assert_eq!(claims.jti, "device_id");
+        assert_eq!(claims.generation, 2);
+    }
+
+    #[tokio::test]
+    async fn test_decode_trusted_device_token_with_other_tokens() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = generate_auth_token(&email, "session_id", 0).unwrap();
+        assert!(decode_trusted_device_token(&token).is_err());
+
+        let token = generate_refresh_token(&email, "family", "token_id").unwrap();
+        assert!(decode_trusted_device_token(&token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_trusted_device_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (session, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let token = generate_trusted_device_token(&email, &session.id, 0).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_email_verification_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = generate_email_verification_token(&email).unwrap();
+
+        let claims = decode_email_verification_token(&token).unwrap();
+        assert_eq!(claims.sub, "test@example.com");
+        assert_eq!(
+            claims.exp - claims.iat,
+            EMAIL_VERIFICATION_TTL_SECONDS as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_email_verification_token_with_other_tokens() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = generate_auth_token(&email, "session_id", 0).unwrap();
+        assert!(decode_email_verification_token(&token).is_err());
+
+        let token = generate_trusted_device_token(&email, "device_id", 0).unwrap();
+        assert!(decode_email_verification_token(&token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_email_verification_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (_, session_store) = test_session(&email).await;
+        let user_store = test_user_store(&email).await;
+        let token = generate_email_verification_token(&email).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store, user_store).await;
+        assert!(result.is_err());
+    }
+
+    // Rotates a keyring of its own, the global one is shared with tests running in parallel
+    #[tokio::test]
+    async fn test_decode_after_key_rotation() {
+        let mut keyring = JwtKeyring::new(JwtSigningKey::from_secret(Uuid::new_v4().as_bytes()));
+        let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS).unwrap();
+        let claims = Claims {
+            sub: "test@example.com".to_owned(),
+            exp,
+            iat,
+            nbf: iat,
+            iss: JWT_ISSUER.to_owned(),
+            aud: JWT_AUDIENCE.to_owned(),
+            jti: Uuid::new_v4().to_string(),
+            sid: "session_id".to_owned(),
+            generation: 0,
+        };
+        let sign = |keyring: &JwtKeyring| {
+            let key = keyring.active();
+            encode(&key.header(), &claims, key.encoding_key()).unwrap()
+        };
+
+        let token = sign(&keyring);
+        keyring.rotate(
+            JwtSigningKey::from_secret(Uuid::new_v4().as_bytes()),
+            MAX_TOKEN_TTL_SECONDS,
+        );
+        let new_token = sign(&keyring);
+
+        let kid = decode_header(&token).unwrap().kid.expect("token should carry a kid");
+        let new_kid = decode_header(&new_token).unwrap().kid.unwrap();
+        assert_ne!(kid, new_kid);
+
+        // Tokens signed by the retiring key are still accepted
+        for token in [token, new_token] {
+            let header = decode_header(&token).unwrap();
+            let result = decode_with::<Claims>(&keyring, &token, &header, &JWT_AUDIENCE);
+            assert_eq!(result.unwrap().sub, "test@example.com");
+        }
+
+        // Keys unknown to the keyring are not
+        let other = JwtKeyring::new(JwtSigningKey::from_secret(Uuid::new_v4().as_bytes()));
+        let token = sign(&other);
+        let header = decode_header(&token).unwrap();
+        assert!(decode_with::<Claims>(&keyring, &token, &header, &JWT_AUDIENCE).is_err());
+    }
+}
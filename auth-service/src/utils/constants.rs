@@ -1,23 +1,230 @@
 use axum::http;
 use dotenvy::dotenv;
+use jsonwebtoken::Algorithm;
 use lazy_static::lazy_static;
-use std::env as std_env;
+use lettre::message::Mailbox;
+use std::{env as std_env, fs, str::FromStr, time::Duration};
+
+use crate::{
+    domain::ServiceClients,
+    services::{EmailOutboxSettings, HttpEmailSettings, SmtpSettings, SmtpTls, WebhookSettings},
+    utils::EmailBranding,
+};
+
+use super::jwt_keys::JwtSigningKey;
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
-    pub static ref JWT_SECRET: String = set_token();
     pub static ref DATABASE_URL: String = set_database_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
     pub static ref ALLOWED_ORIGINS: Vec<http::HeaderValue> = set_allowed_origins();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_AUDIENCE: String = set_jwt_audience();
+    pub static ref SERVICE_CLIENTS: ServiceClients = set_service_clients();
+    pub static ref MAX_2FA_ATTEMPTS: u32 = set_max_2fa_attempts();
+    pub static ref TWO_FA_RESEND_COOLDOWN_SECS: u64 = set_two_fa_resend_cooldown_secs();
+    pub static ref MAX_2FA_RESENDS: u32 = set_max_2fa_resends();
+    pub static ref MAX_2FA_USER_FAILURES: u32 = set_max_2fa_user_failures();
+    pub static ref TWO_FA_CODE_SECRET: String = set_two_fa_code_secret();
+    pub static ref WEBAUTHN_RP_ID: String = set_webauthn_rp_id();
+    pub static ref WEBAUTHN_RP_NAME: String = set_webauthn_rp_name();
+    pub static ref WEBAUTHN_ORIGIN: String = set_webauthn_origin();
+    pub static ref EMAIL_CLIENT: String = set_email_client();
+    pub static ref SMS_CHANNEL: String = set_notification_channel(env::SMS_CHANNEL_ENV_VAR);
+    pub static ref PUSH_CHANNEL: String = set_notification_channel(env::PUSH_CHANNEL_ENV_VAR);
+    pub static ref EMAIL_BRANDING: EmailBranding = set_email_branding();
 }
 
-fn set_token() -> String {
-    dotenv().ok(); // Load environment variables
-    let secret = std_env::var(env::JWT_SECRET_ENV_VAR).expect("JWT_SCRET must be set.");
-    if secret.is_empty() {
-        panic!("JWT_SECRET must not be empty.");
+// Load the JWT signing key from the environment. This runs again whenever the key
+// is rotated, so it must not rely on values cached in lazy statics.
+pub fn load_jwt_signing_key() -> Result<JwtSigningKey, String> {
+    dotenv().ok();
+    let algorithm = std_env::var(env::JWT_ALGORITHM_ENV_VAR)
+        .unwrap_or(DEFAULT_JWT_ALGORITHM.to_owned());
+    let algorithm = Algorithm::from_str(&algorithm)
+        .map_err(|_| format!("{} is not a valid JWT algorithm.", algorithm))?;
+
+    // HMAC uses the shared JWT_SECRET; asymmetric algorithms read a private key
+    // that only the auth service holds.
+    if algorithm == Algorithm::HS256 {
+        let secret = std_env::var(env::JWT_SECRET_ENV_VAR)
+            .map_err(|_| "JWT_SECRET must be set.".to_owned())?;
+        if secret.is_empty() {
+            return Err("JWT_SECRET must not be empty.".to_owned());
+        }
+        return Ok(JwtSigningKey::from_secret(secret.as_bytes()));
     }
-    secret
+
+    let path = std_env::var(env::JWT_PRIVATE_KEY_PATH_ENV_VAR)
+        .map_err(|_| "JWT_PRIVATE_KEY_PATH must be set.".to_owned())?;
+    let pem = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    JwtSigningKey::from_pem(algorithm, &pem)
+        .map_err(|e| format!("Invalid JWT private key in {}: {:?}", path, e))
+}
+
+// Load the keys that signed tokens before the current one, so these stay valid
+// across restarts. Secrets and key files are comma separated lists, and the files
+// are read again whenever the signing key is reloaded.
+pub fn load_previous_jwt_signing_keys() -> Result<Vec<JwtSigningKey>, String> {
+    dotenv().ok();
+    let secrets = std_env::var(env::JWT_PREVIOUS_SECRETS_ENV_VAR).unwrap_or_default();
+    let paths = std_env::var(env::JWT_PREVIOUS_KEY_PATHS_ENV_VAR).unwrap_or_default();
+
+    let mut keys: Vec<JwtSigningKey> = split_list(&secrets)
+        .map(|secret| JwtSigningKey::from_secret(secret.as_bytes()))
+        .collect();
+    for path in split_list(&paths) {
+        let pem = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let key = JwtSigningKey::from_any_pem(&pem)
+            .map_err(|e| format!("Invalid JWT private key in {}: {:?}", path, e))?;
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+// Compose passes unset variables on as empty strings
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+// Load the settings of the SMTP server emails are sent through. Only needed when
+// EMAIL_CLIENT is "smtp", so the other deployments don't have to set them.
+pub fn load_smtp_settings() -> Result<SmtpSettings, String> {
+    dotenv().ok();
+    let host = std_env::var(env::SMTP_HOST_ENV_VAR)
+        .map_err(|_| "SMTP_HOST must be set.".to_owned())?;
+    let tls = std_env::var(env::SMTP_TLS_ENV_VAR).unwrap_or(DEFAULT_SMTP_TLS.to_owned());
+    let tls = SmtpTls::parse(&tls)?;
+
+    let port = match std_env::var(env::SMTP_PORT_ENV_VAR) {
+        Ok(port) => port
+            .parse()
+            .map_err(|_| "SMTP_PORT must be a port number.".to_owned())?,
+        Err(_) => match tls {
+            SmtpTls::None => 25,
+            SmtpTls::StartTls => 587,
+            SmtpTls::Implicit => 465,
+        },
+    };
+
+    // Compose passes unset variables on as empty strings
+    let credentials = match (
+        std_env::var(env::SMTP_USERNAME_ENV_VAR).ok().filter(|v| !v.is_empty()),
+        std_env::var(env::SMTP_PASSWORD_ENV_VAR).ok().filter(|v| !v.is_empty()),
+    ) {
+        (Some(username), Some(password)) => Some((username, password)),
+        (None, None) => None,
+        _ => return Err("SMTP_USERNAME and SMTP_PASSWORD must be set together.".to_owned()),
+    };
+
+    let sender = std_env::var(env::EMAIL_SENDER_ENV_VAR)
+        .map_err(|_| "EMAIL_SENDER must be set.".to_owned())?;
+    let sender = sender
+        .parse::<Mailbox>()
+        .map_err(|_| format!("{} is not a valid sender address.", sender))?;
+
+    let timeout = match std_env::var(env::SMTP_TIMEOUT_SECS_ENV_VAR) {
+        Ok(secs) => secs
+            .parse()
+            .map_err(|_| "SMTP_TIMEOUT_SECS must be a number of seconds.".to_owned())?,
+        Err(_) => DEFAULT_SMTP_TIMEOUT_SECS,
+    };
+
+    Ok(SmtpSettings {
+        host,
+        port,
+        tls,
+        credentials,
+        sender,
+        timeout: Duration::from_secs(timeout),
+    })
+}
+
+// Load the settings of the email API, needed when EMAIL_CLIENT is "http"
+pub fn load_http_email_settings() -> Result<HttpEmailSettings, String> {
+    dotenv().ok();
+    // Compose passes unset variables on as empty strings
+    let base_url = std_env::var(env::EMAIL_API_BASE_URL_ENV_VAR)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .ok_or("EMAIL_API_BASE_URL must be set.".to_owned())?;
+    let token = std_env::var(env::EMAIL_API_TOKEN_ENV_VAR)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .ok_or("EMAIL_API_TOKEN must be set.".to_owned())?;
+    let sender = std_env::var(env::EMAIL_SENDER_ENV_VAR)
+        .map_err(|_| "EMAIL_SENDER must be set.".to_owned())?;
+
+    let timeout = match std_env::var(env::EMAIL_API_TIMEOUT_SECS_ENV_VAR) {
+        Ok(secs) => secs
+            .parse()
+            .map_err(|_| "EMAIL_API_TIMEOUT_SECS must be a number of seconds.".to_owned())?,
+        Err(_) => DEFAULT_EMAIL_API_TIMEOUT_SECS,
+    };
+    let max_retries = match std_env::var(env::EMAIL_API_MAX_RETRIES_ENV_VAR) {
+        Ok(retries) => retries
+            .parse()
+            .map_err(|_| "EMAIL_API_MAX_RETRIES must be a number.".to_owned())?,
+        Err(_) => DEFAULT_EMAIL_API_MAX_RETRIES,
+    };
+
+    Ok(HttpEmailSettings {
+        base_url,
+        token,
+        sender,
+        timeout: Duration::from_secs(timeout),
+        max_retries,
+        retry_delay: Duration::from_millis(EMAIL_API_RETRY_DELAY_MILLIS),
+        max_retry_delay: Duration::from_secs(EMAIL_API_MAX_RETRY_DELAY_SECS),
+    })
+}
+
+// Load the webhook SMS codes are sent through, needed when SMS_CHANNEL is "webhook"
+pub fn load_sms_webhook_settings() -> Result<WebhookSettings, String> {
+    load_webhook_settings(env::SMS_WEBHOOK_URL_ENV_VAR, env::SMS_WEBHOOK_TOKEN_ENV_VAR)
+}
+
+// Load the webhook push codes are sent through, needed when PUSH_CHANNEL is "webhook"
+pub fn load_push_webhook_settings() -> Result<WebhookSettings, String> {
+    load_webhook_settings(env::PUSH_WEBHOOK_URL_ENV_VAR, env::PUSH_WEBHOOK_TOKEN_ENV_VAR)
+}
+
+fn load_webhook_settings(url_var: &str, token_var: &str) -> Result<WebhookSettings, String> {
+    dotenv().ok();
+    let url = std_env::var(url_var).map_err(|_| format!("{} must be set.", url_var))?;
+    let token = std_env::var(token_var).map_err(|_| format!("{} must be set.", token_var))?;
+
+    Ok(WebhookSettings {
+        url,
+        token,
+        timeout: Duration::from_secs(NOTIFICATION_WEBHOOK_TIMEOUT_SECS),
+    })
+}
+
+// Load how the outbox worker delivers queued emails
+pub fn load_email_outbox_settings() -> Result<EmailOutboxSettings, String> {
+    dotenv().ok();
+    let max_attempts = match std_env::var(env::EMAIL_OUTBOX_MAX_ATTEMPTS_ENV_VAR) {
+        Ok(attempts) => attempts
+            .parse()
+            .map_err(|_| "EMAIL_OUTBOX_MAX_ATTEMPTS must be a number.".to_owned())?,
+        Err(_) => DEFAULT_EMAIL_OUTBOX_MAX_ATTEMPTS,
+    };
+    if max_attempts == 0 {
+        return Err("EMAIL_OUTBOX_MAX_ATTEMPTS must be at least 1.".to_owned());
+    }
+
+    Ok(EmailOutboxSettings {
+        poll_interval: Duration::from_millis(EMAIL_OUTBOX_POLL_INTERVAL_MILLIS),
+        batch_size: EMAIL_OUTBOX_BATCH_SIZE,
+        max_attempts,
+        retry_delay: Duration::from_secs(EMAIL_OUTBOX_RETRY_DELAY_SECS),
+        max_retry_delay: Duration::from_secs(EMAIL_OUTBOX_MAX_RETRY_DELAY_SECS),
+        lease: Duration::from_secs(EMAIL_OUTBOX_LEASE_SECS),
+        retention: Duration::from_secs(EMAIL_OUTBOX_RETENTION_SECS),
+        cleanup_interval: Duration::from_secs(EMAIL_OUTBOX_CLEANUP_INTERVAL_SECS),
+    })
 }
 
 fn set_database_url() -> String {
@@ -45,16 +252,216 @@ fn set_allowed_origins() -> Vec<http::HeaderValue> {
     allowed
 }
 
+fn set_jwt_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ISSUER_ENV_VAR).unwrap_or(DEFAULT_JWT_ISSUER.to_owned())
+}
+
+fn set_jwt_audience() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_AUDIENCE_ENV_VAR).unwrap_or(DEFAULT_JWT_AUDIENCE.to_owned())
+}
+
+fn set_service_clients() -> ServiceClients {
+    dotenv().ok();
+    let clients = std_env::var(env::SERVICE_CLIENTS_ENV_VAR).unwrap_or("".to_string());
+    ServiceClients::parse(&clients).expect("SERVICE_CLIENTS must be a list of client_id:client_secret pairs.")
+}
+
+fn set_max_2fa_attempts() -> u32 {
+    dotenv().ok();
+    match std_env::var(env::MAX_2FA_ATTEMPTS_ENV_VAR) {
+        Ok(attempts) => attempts
+            .parse()
+            .ok()
+            .filter(|&attempts| attempts > 0)
+            .expect("MAX_2FA_ATTEMPTS must be a positive number."),
+        Err(_) => DEFAULT_MAX_2FA_ATTEMPTS,
+    }
+}
+
+fn set_two_fa_resend_cooldown_secs() -> u64 {
+    dotenv().ok();
+    match std_env::var(env::TWO_FA_RESEND_COOLDOWN_SECS_ENV_VAR) {
+        Ok(secs) => secs
+            .parse()
+            .expect("TWO_FA_RESEND_COOLDOWN_SECS must be a number of seconds."),
+        Err(_) => DEFAULT_TWO_FA_RESEND_COOLDOWN_SECS,
+    }
+}
+
+fn set_max_2fa_resends() -> u32 {
+    dotenv().ok();
+    match std_env::var(env::MAX_2FA_RESENDS_ENV_VAR) {
+        Ok(resends) => resends
+            .parse()
+            .expect("MAX_2FA_RESENDS must be a number."),
+        Err(_) => DEFAULT_MAX_2FA_RESENDS,
+    }
+}
+
+fn set_max_2fa_user_failures() -> u32 {
+    dotenv().ok();
+    match std_env::var(env::MAX_2FA_USER_FAILURES_ENV_VAR) {
+        Ok(failures) => failures
+            .parse()
+            .ok()
+            .filter(|&failures| failures > 0)
+            .expect("MAX_2FA_USER_FAILURES must be a positive number."),
+        Err(_) => DEFAULT_MAX_2FA_USER_FAILURES,
+    }
+}
+
+fn set_two_fa_code_secret() -> String {
+    dotenv().ok();
+    let secret = std_env::var(env::TWO_FA_CODE_SECRET_ENV_VAR)
+        .expect("TWO_FA_CODE_SECRET must be set.");
+    if secret.is_empty() {
+        panic!("TWO_FA_CODE_SECRET must not be empty.");
+    }
+    secret
+}
+
+// Passkeys are bound to this domain, browsers refuse ids the origin isn't part of
+fn set_webauthn_rp_id() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_RP_ID_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_RP_ID.to_owned())
+}
+
+fn set_webauthn_rp_name() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_RP_NAME_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_RP_NAME.to_owned())
+}
+
+fn set_webauthn_origin() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_ORIGIN_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_ORIGIN.to_owned())
+}
+
+fn set_email_client() -> String {
+    dotenv().ok();
+    std_env::var(env::EMAIL_CLIENT_ENV_VAR).unwrap_or(DEFAULT_EMAIL_CLIENT.to_owned())
+}
+
+fn set_notification_channel(var: &str) -> String {
+    dotenv().ok();
+    std_env::var(var).unwrap_or(DEFAULT_NOTIFICATION_CHANNEL.to_owned())
+}
+
+// Links in emails point at EMAIL_BRAND_URL. Only emails that are never sent
+// may fall back to localhost.
+fn set_email_branding() -> EmailBranding {
+    dotenv().ok();
+    // Compose passes unset variables on as empty strings
+    let var = |name| std_env::var(name).ok().filter(|v: &String| !v.is_empty());
+
+    let url = match var(env::EMAIL_BRAND_URL_ENV_VAR) {
+        Some(url) => url.trim_end_matches('/').to_owned(),
+        None if EMAIL_CLIENT.as_str() == "mock" => DEFAULT_EMAIL_BRAND_URL.to_owned(),
+        None => panic!("EMAIL_BRAND_URL must be set unless EMAIL_CLIENT is mock."),
+    };
+
+    EmailBranding {
+        name: var(env::EMAIL_BRAND_NAME_ENV_VAR).unwrap_or(DEFAULT_EMAIL_BRAND_NAME.to_owned()),
+        url,
+        color: var(env::EMAIL_BRAND_COLOR_ENV_VAR).unwrap_or(DEFAULT_EMAIL_BRAND_COLOR.to_owned()),
+        support_email: var(env::EMAIL_SUPPORT_ADDRESS_ENV_VAR),
+    }
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str =  "JWT_SECRET";
+    pub const JWT_ALGORITHM_ENV_VAR: &str = "JWT_ALGORITHM";
+    pub const JWT_PRIVATE_KEY_PATH_ENV_VAR: &str = "JWT_PRIVATE_KEY_PATH";
+    pub const JWT_PREVIOUS_SECRETS_ENV_VAR: &str = "JWT_PREVIOUS_SECRETS";
+    pub const JWT_PREVIOUS_KEY_PATHS_ENV_VAR: &str = "JWT_PREVIOUS_KEY_PATHS";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_AUDIENCE_ENV_VAR: &str = "JWT_AUDIENCE";
     pub const DATABASE_URL_ENV_VAR: &str =  "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
     pub const ALLOWED_ORIGINS_VAR: &str = "ALLOWED_ORIGINS";
+    pub const SERVICE_CLIENTS_ENV_VAR: &str = "SERVICE_CLIENTS";
+    pub const MAX_2FA_ATTEMPTS_ENV_VAR: &str = "MAX_2FA_ATTEMPTS";
+    pub const TWO_FA_RESEND_COOLDOWN_SECS_ENV_VAR: &str = "TWO_FA_RESEND_COOLDOWN_SECS";
+    pub const MAX_2FA_RESENDS_ENV_VAR: &str = "MAX_2FA_RESENDS";
+    pub const MAX_2FA_USER_FAILURES_ENV_VAR: &str = "MAX_2FA_USER_FAILURES";
+    pub const TWO_FA_CODE_SECRET_ENV_VAR: &str = "TWO_FA_CODE_SECRET";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_RP_NAME_ENV_VAR: &str = "WEBAUTHN_RP_NAME";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const EMAIL_CLIENT_ENV_VAR: &str = "EMAIL_CLIENT";
+    pub const EMAIL_SENDER_ENV_VAR: &str = "EMAIL_SENDER";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const SMTP_TIMEOUT_SECS_ENV_VAR: &str = "SMTP_TIMEOUT_SECS";
+    pub const EMAIL_API_BASE_URL_ENV_VAR: &str = "EMAIL_API_BASE_URL";
+    pub const EMAIL_API_TOKEN_ENV_VAR: &str = "EMAIL_API_TOKEN";
+    pub const EMAIL_API_TIMEOUT_SECS_ENV_VAR: &str = "EMAIL_API_TIMEOUT_SECS";
+    pub const EMAIL_API_MAX_RETRIES_ENV_VAR: &str = "EMAIL_API_MAX_RETRIES";
+    pub const SMS_CHANNEL_ENV_VAR: &str = "SMS_CHANNEL";
+    pub const SMS_WEBHOOK_URL_ENV_VAR: &str = "SMS_WEBHOOK_URL";
+    pub const SMS_WEBHOOK_TOKEN_ENV_VAR: &str = "SMS_WEBHOOK_TOKEN";
+    pub const PUSH_CHANNEL_ENV_VAR: &str = "PUSH_CHANNEL";
+    pub const PUSH_WEBHOOK_URL_ENV_VAR: &str = "PUSH_WEBHOOK_URL";
+    pub const PUSH_WEBHOOK_TOKEN_ENV_VAR: &str = "PUSH_WEBHOOK_TOKEN";
+    pub const EMAIL_OUTBOX_MAX_ATTEMPTS_ENV_VAR: &str = "EMAIL_OUTBOX_MAX_ATTEMPTS";
+    pub const EMAIL_BRAND_NAME_ENV_VAR: &str = "EMAIL_BRAND_NAME";
+    pub const EMAIL_BRAND_URL_ENV_VAR: &str = "EMAIL_BRAND_URL";
+    pub const EMAIL_BRAND_COLOR_ENV_VAR: &str = "EMAIL_BRAND_COLOR";
+    pub const EMAIL_SUPPORT_ADDRESS_ENV_VAR: &str = "EMAIL_SUPPORT_ADDRESS";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
 pub const JWT_REFRESH_COOKIE_NAME: &str = "jwt_refresh";
+pub const TRUSTED_DEVICE_COOKIE_NAME: &str = "trusted_device";
+pub const DEFAULT_JWT_ALGORITHM: &str = "HS256";
+pub const DEFAULT_JWT_ISSUER: &str = "auth-service";
+pub const DEFAULT_JWT_AUDIENCE: &str = "app-service";
+pub const DEFAULT_MAX_2FA_ATTEMPTS: u32 = 5;
+pub const DEFAULT_TWO_FA_RESEND_COOLDOWN_SECS: u64 = 30;
+pub const DEFAULT_MAX_2FA_RESENDS: u32 = 3;
+pub const DEFAULT_MAX_2FA_USER_FAILURES: u32 = 10;
+// Codes a signed-in user can ask for until the ones asked for expired. Fewer than
+// MAX_PENDING_LOGIN_ATTEMPTS, so some of those are always left for logging in.
+pub const MAX_2FA_CODE_REQUESTS: u32 = 3;
+// How long a user stays locked out after too many wrong TOTP or recovery codes
+pub const TWO_FA_LOCKOUT_SECS: u64 = 900;
+pub const VERIFICATION_EMAIL_RESEND_COOLDOWN_SECS: u64 = 60;
+// Per address and day
+pub const MAX_VERIFICATION_EMAIL_RESENDS: u32 = 5;
+pub const PASSWORD_RESET_EMAIL_COOLDOWN_SECS: u64 = 60;
+// Per address and day
+pub const MAX_PASSWORD_RESET_EMAILS: u32 = 5;
+pub const DEFAULT_WEBAUTHN_RP_ID: &str = "localhost";
+pub const DEFAULT_WEBAUTHN_RP_NAME: &str = "Auth Service";
+pub const DEFAULT_WEBAUTHN_ORIGIN: &str = "http://localhost:3000";
+pub const DEFAULT_EMAIL_CLIENT: &str = "mock";
+pub const DEFAULT_SMTP_TLS: &str = "starttls";
+pub const DEFAULT_SMTP_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_EMAIL_API_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_EMAIL_API_MAX_RETRIES: u32 = 3;
+pub const EMAIL_API_RETRY_DELAY_MILLIS: u64 = 500;
+// Keeps all retries of a send within the outbox worker's lease
+pub const EMAIL_API_MAX_RETRY_DELAY_SECS: u64 = 10;
+// SMS and push 2FA are refused unless a channel is configured
+pub const DEFAULT_NOTIFICATION_CHANNEL: &str = "none";
+pub const NOTIFICATION_WEBHOOK_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_EMAIL_OUTBOX_MAX_ATTEMPTS: u32 = 8;
+pub const EMAIL_OUTBOX_POLL_INTERVAL_MILLIS: u64 = 1000;
+pub const EMAIL_OUTBOX_BATCH_SIZE: u32 = 20;
+pub const EMAIL_OUTBOX_RETRY_DELAY_SECS: u64 = 5;
+pub const EMAIL_OUTBOX_MAX_RETRY_DELAY_SECS: u64 = 600;
+// Longer than a send can take, including the email API client's own retries
+pub const EMAIL_OUTBOX_LEASE_SECS: u64 = 120;
+pub const EMAIL_OUTBOX_RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+pub const EMAIL_OUTBOX_CLEANUP_INTERVAL_SECS: u64 = 60 * 60;
+pub const DEFAULT_EMAIL_BRAND_NAME: &str = "Auth Service";
+pub const DEFAULT_EMAIL_BRAND_URL: &str = "http://localhost:3000";
+pub const DEFAULT_EMAIL_BRAND_COLOR: &str = "#2563eb";
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
@@ -0,0 +1,167 @@
+use askama::Template;
+
+use crate::domain::EmailContent;
+
+// Every email the service sends
+#[derive(Debug)]
+pub enum EmailMessage {
+    TwoFACode(String),
+    // Link that confirms the address belongs to the user
+    VerifyEmail(String),
+    ResetPassword(String),
+    SecurityNotice(SecurityNotice),
+}
+
+// Changes to an account the owner is told about, in case someone else made them
+#[derive(Clone, Copy, Debug)]
+pub enum SecurityNotice {
+    TwoFAEnabled,
+    TwoFADisabled,
+    PasskeyAdded,
+    PasswordChanged,
+    RecoveryCodesRegenerated,
+}
+
+// How emails present the product
+#[derive(Clone, Debug)]
+pub struct EmailBranding {
+    pub name: String,
+    pub url: String,
+    // CSS color of the header and buttons
+    pub color: String,
+    pub support_email: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "emails/email.html")]
+struct HtmlEmail<'a> {
+    subject: &'a str,
+    branding: &'a EmailBranding,
+    message: &'a EmailMessage,
+}
+
+#[derive(Template)]
+#[template(path = "emails/email.txt")]
+struct TextEmail<'a> {
+    branding: &'a EmailBranding,
+    message: &'a EmailMessage,
+}
+
+impl EmailMessage {
+    pub fn subject(&self, branding: &EmailBranding) -> String {
+        match self {
+            Self::TwoFACode(_) => format!("Your {} login code", branding.name),
+            Self::VerifyEmail(_) => format!("Verify your {} email address", branding.name),
+            Self::ResetPassword(_) => format!("Reset your {} password", branding.name),
+            Self::SecurityNotice(_) => format!("Security alert for your {} account", branding.name),
+        }
+    }
+
+    pub fn render(&self, branding: &EmailBranding) -> Result<EmailContent, askama::Error> {
+        let subject = self.subject(branding);
+
+        let html_body = HtmlEmail {
+            subject: &subject,
+            branding,
+            message: self,
+        }
+        .render()?;
+        let text_body = TextEmail {
+            branding,
+            message: self,
+        }
+        .render()?;
+
+        Ok(EmailContent {
+            subject,
+            html_body,
+            text_body,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn branding() -> EmailBranding {
+        EmailBranding {
+            name: "Acme".to_owned(),
+            url: "https://acme.example.com".to_owned(),
+            color: "#ff0000".to_owned(),
+            support_email: Some("help@acme.example.com".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_render_two_fa_code() {
+        let content = EmailMessage::TwoFACode("123456".to_owned())
+            .render(&branding())
+            .unwrap();
+
+        assert_eq!(content.subject, "Your Acme login code");
+        assert!(content.html_body.contains("123456"));
+        assert!(content.html_body.contains("#ff0000"));
+        assert!(content.text_body.starts_with("Acme\n"));
+        assert!(content.text_body.contains("    123456\n"));
+        assert!(content
+            .text_body
+            .contains("Questions? Contact help@acme.example.com."));
+    }
+
+    #[test]
+    fn test_render_links() {
+        let link = "https://acme.example.com/verify-email?token=a&b".to_owned();
+        let content = EmailMessage::VerifyEmail(link.clone())
+            .render(&branding())
+            .unwrap();
+
+        // Escaped in the HTML body only
+        assert!(content.html_body.contains("token=a&amp;b"));
+        assert!(content.text_body.contains(&link));
+
+        let content = EmailMessage::ResetPassword(link.clone())
+            .render(&branding())
+            .unwrap();
+        assert_eq!(content.subject, "Reset your Acme password");
+        assert!(content.text_body.contains(&link));
+    }
+
+    #[test]
+    fn test_render_security_notice() {
+        let mut branding = branding();
+        branding.support_email = None;
+
+        let content = EmailMessage::SecurityNotice(SecurityNotice::PasskeyAdded)
+            .render(&branding)
+            .unwrap();
+
+        assert!(content
+            .html_body
+            .contains("A new passkey was added to your account."));
+        assert!(content
+            .text_body
+            .contains("A new passkey was added to your account.\n"));
+        assert!(!content.text_body.contains("Questions?"));
+
+        let content = EmailMessage::SecurityNotice(SecurityNotice::TwoFAEnabled)
+            .render(&branding)
+            .unwrap();
+        assert!(content
+            .text_body
+            .contains("Two-factor authentication was turned on for your account.\n"));
+    }
+
+    #[test]
+    fn test_html_escapes_branding() {
+        let mut branding = branding();
+        branding.name = "<b>Acme</b>".to_owned();
+
+        let content = EmailMessage::SecurityNotice(SecurityNotice::TwoFADisabled)
+            .render(&branding)
+            .unwrap();
+
+        assert!(!content.html_body.contains("<b>Acme</b>"));
+        assert!(content.text_body.starts_with("<b>Acme</b>\n"));
+    }
+}
@@ -0,0 +1,368 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use percent_encoding::percent_decode_str;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, ClientCredentials, Email},
+};
+
+use super::{
+    auth::{validate_token, Claims},
+    constants::JWT_COOKIE_NAME,
+};
+
+// The raw JWT auth token of a request. Clients that can't use cookies send it as
+// `Authorization: Bearer <jwt>`, which takes precedence over the `jwt` cookie.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthToken(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthToken
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(value) = parts.headers.get(header::AUTHORIZATION) {
+            let value = value.to_str().map_err(|_| AuthAPIError::InvalidToken)?;
+            return match value.split_once(' ') {
+                Some((scheme, token))
+                    if scheme.eq_ignore_ascii_case("Bearer") && !token.trim().is_empty() =>
+                {
+                    Ok(AuthToken(token.trim().to_owned()))
+                }
+                _ => Err(AuthAPIError::InvalidToken),
+            };
+        }
+
+        CookieJar::from_headers(&parts.headers)
+            .get(JWT_COOKIE_NAME)
+            .map(|cookie| AuthToken(cookie.value().to_owned()))
+            .ok_or(AuthAPIError::MissingToken)
+    }
+}
+
+// The user a request was made by, taken from its validated auth token. Protected
+// handlers take this as an argument instead of validating the token themselves.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub email: Email,
+    pub claims: Claims,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        // Already validated by the `require_auth` middleware
+        if let Some(user) = parts.extensions.get::<AuthenticatedUser>() {
+            return Ok(user.clone());
+        }
+
+        let AuthToken(token) = AuthToken::from_request_parts(parts, state).await?;
+
+        let claims = validate_token(
+            &token,
+            state.banned_token_store.clone(),
+            state.session_store.clone(),
+            state.user_store.clone(),
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        let email = Email::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+
+        Ok(AuthenticatedUser { email, claims })
+    }
+}
+
+// Credentials of a backend service, sent as `Authorization: Basic <client_id:client_secret>`.
+// Whether they belong to a registered service is up to the route to check.
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientCredentials
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let value = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(AuthAPIError::InvalidClientCredentials)?;
+
+        let encoded = match value.split_once(' ') {
+            Some((scheme, encoded)) if scheme.eq_ignore_ascii_case("Basic") => encoded.trim(),
+            _ => return Err(AuthAPIError::InvalidClientCredentials),
+        };
+
+        let decoded = STANDARD
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(AuthAPIError::InvalidClientCredentials)?;
+
+        let (client_id, client_secret) = decoded
+            .split_once(':')
+            .ok_or(AuthAPIError::InvalidClientCredentials)?;
+
+        Ok(ClientCredentials {
+            client_id: form_urldecode(client_id)?,
+            client_secret: form_urldecode(client_secret)?,
+        })
+    }
+}
+
+// RFC 6749 section 2.3.1 has clients form-urlencode their id and secret before
+// joining them, so a colon in either can't be mistaken for the separator
+fn form_urldecode(value: &str) -> Result<String, AuthAPIError> {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8()
+        .map(|value| value.into_owned())
+        .map_err(|_| AuthAPIError::InvalidClientCredentials)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::Request;
+    use tokio::sync::RwLock;
+
+    use crate::{
+        domain::{Password, ServiceClients, TwoFAMethod, User, UserStore},
+        services::{
+            HashmapEmailOutboxStore, HashmapNotificationTargetStore,
+            HashmapPasswordResetTokenStore, HashmapRecoveryCodeStore, HashmapRefreshTokenStore,
+            HashmapSessionStore, HashmapTotpSecretStore, HashmapTrustedDeviceStore,
+            HashmapTwoFACodeStore, HashmapUserStore, HashmapVerificationEmailStore,
+            HashmapWebAuthnChallengeStore, HashmapWebAuthnCredentialStore,
+            HashsetBannedTokenStore, MockNotificationChannel,
+        },
+        utils::start_session,
+    };
+
+    use super::*;
+
+    // Creates app state with a single user and returns it along with an auth token of the user
+    async fn test_state(email: &Email) -> (AppState, String) {
+        let password = Password::parse("password123".to_owned()).unwrap();
+        let mut user_store = HashmapUserStore::default();
+        user_store
+            .add_user(User::new(email.clone(), password, TwoFAMethod::None))
+            .await
+            .unwrap();
+
+        let state = AppState::new(
+            Arc::new(RwLock::new(user_store)),
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+            Arc::new(RwLock::new(HashmapTwoFACodeStore::default())),
+            Arc::new(RwLock::new(HashmapRefreshTokenStore::default())),
+            Arc::new(RwLock::new(HashmapSessionStore::default())),
+            Arc::new(RwLock::new(HashmapTotpSecretStore::default())),
+            Arc::new(RwLock::new(HashmapRecoveryCodeStore::default())),
+            Arc::new(RwLock::new(HashmapTrustedDeviceStore::default())),
+            Arc::new(RwLock::new(HashmapNotificationTargetStore::default())),
+            Arc::new(RwLock::new(HashmapWebAuthnChallengeStore::default())),
+            Arc::new(RwLock::new(HashmapWebAuthnCredentialStore::default())),
+            Arc::new(RwLock::new(HashmapPasswordResetTokenStore::default())),
+            Arc::new(RwLock::new(HashmapVerificationEmailStore::default())),
+            Arc::new(RwLock::new(HashmapEmailOutboxStore::default())),
+            Some(Arc::new(MockNotificationChannel::default())),
+            Some(Arc::new(MockNotificationChannel::default())),
+            Arc::new(ServiceClients::default()),
+        );
+
+        let (auth_cookie, _) = start_session(
+            email,
+            0,
+            None,
+            state.session_store.clone(),
+            state.refresh_token_store.clone(),
+        )
+        .await
+        .unwrap();
+
+        (state, auth_cookie.value().to_owned())
+    }
+
+    #[tokio::test]
+    async fn test_extract_authenticated_user() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (state, token) = test_state(&email).await;
+
+        let request = Request::builder()
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+        let user = AuthenticatedUser::from_request_parts(&mut parts, &state)
+            .await
+            .unwrap();
+
+        assert_eq!(user.email, email);
+        assert_eq!(user.claims.sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_extract_authenticated_user_with_invalid_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (state, _) = test_state(&email).await;
+
+        let (mut parts, _) = Request::builder().body(()).unwrap().into_parts();
+        let result = AuthenticatedUser::from_request_parts(&mut parts, &state).await;
+        assert!(matches!(result, Err(AuthAPIError::MissingToken)));
+
+        let request = Request::builder()
+            .header(header::AUTHORIZATION, "Bearer invalid_token")
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+        let result = AuthenticatedUser::from_request_parts(&mut parts, &state).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+    }
+
+    async fn extract(request: Request<()>) -> Result<AuthToken, AuthAPIError> {
+        let (mut parts, _) = request.into_parts();
+        AuthToken::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_extract_bearer_token() {
+        let request = Request::builder()
+            .header(header::AUTHORIZATION, "Bearer token")
+            .body(())
+            .unwrap();
+        assert_eq!(extract(request).await.ok(), Some(AuthToken("token".to_owned())));
+
+        let request = Request::builder()
+            .header(header::AUTHORIZATION, "bearer token")
+            .body(())
+            .unwrap();
+        assert_eq!(extract(request).await.ok(), Some(AuthToken("token".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn test_extract_cookie_token() {
+        let request = Request::builder()
+            .header(header::COOKIE, format!("{}=token", JWT_COOKIE_NAME))
+            .body(())
+            .unwrap();
+        assert_eq!(extract(request).await.ok(), Some(AuthToken("token".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token_takes_precedence_over_cookie() {
+        let request = Request::builder()
+            .header(header::AUTHORIZATION, "Bearer header_token")
+            .header(header::COOKIE, format!("{}=cookie_token", JWT_COOKIE_NAME))
+            .body(())
+            .unwrap();
+        assert_eq!(
+            extract(request).await.ok(),
+            Some(AuthToken("header_token".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_missing_token() {
+        let request = Request::builder().body(()).unwrap();
+        assert!(matches!(extract(request).await, Err(AuthAPIError::MissingToken)));
+    }
+
+    #[tokio::test]
+    async fn test_extract_malformed_authorization_header() {
+        for value in ["token", "Basic dXNlcjpwYXNz", "Bearer ", "Bearer"] {
+            let request = Request::builder()
+                .header(header::AUTHORIZATION, value)
+                .body(())
+                .unwrap();
+            assert!(
+                matches!(extract(request).await, Err(AuthAPIError::InvalidToken)),
+                "Failed for header: {}",
+                value
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_client_credentials() {
+        let request = Request::builder()
+            .header(
+                header::AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode("app-service:secret")),
+            )
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+        let credentials = ClientCredentials::from_request_parts(&mut parts, &())
+            .await
+            .ok();
+        assert_eq!(
+            credentials,
+            Some(ClientCredentials {
+                client_id: "app-service".to_owned(),
+                client_secret: "secret".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_urlencoded_client_credentials() {
+        let request = Request::builder()
+            .header(
+                header::AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode("app%3Aservice:s%2Bcret+1")),
+            )
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+        let credentials = ClientCredentials::from_request_parts(&mut parts, &())
+            .await
+            .ok();
+        assert_eq!(
+            credentials,
+            Some(ClientCredentials {
+                client_id: "app:service".to_owned(),
+                client_secret: "s+cret 1".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_invalid_client_credentials() {
+        let values = [
+            None,
+            Some("Bearer token".to_owned()),
+            Some("Basic not-base64".to_owned()),
+            Some(format!("Basic {}", STANDARD.encode("no-separator"))),
+            Some(format!("Basic {}", STANDARD.encode("app-service:%FF"))),
+        ];
+
+        for value in values {
+            let mut builder = Request::builder();
+            if let Some(value) = &value {
+                builder = builder.header(header::AUTHORIZATION, value);
+            }
+            let (mut parts, _) = builder.body(()).unwrap().into_parts();
+            let result = ClientCredentials::from_request_parts(&mut parts, &()).await;
+            assert!(
+                matches!(result, Err(AuthAPIError::InvalidClientCredentials)),
+                "Failed for header: {:?}",
+                value
+            );
+        }
+    }
+}
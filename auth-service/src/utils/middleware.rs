@@ -0,0 +1,15 @@
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::Response,
+};
+
+use super::extractors::AuthenticatedUser;
+
+// Rejects requests without a valid auth token. Apply it to a group of routes with
+// `route_layer(middleware::from_fn_with_state(state, require_auth))`; handlers behind
+// it can still take an `AuthenticatedUser` without validating the token again.
+pub async fn require_auth(user: AuthenticatedUser, mut request: Request, next: Next) -> Response {
+    request.extensions_mut().insert(user);
+    next.run(request).await
+}
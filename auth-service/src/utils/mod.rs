@@ -1,5 +1,13 @@
 pub mod constants;
 pub mod auth;
+pub mod jwt_keys;
+pub mod extractors;
+pub mod middleware;
+pub mod email_templates;
 
 pub use constants::*;
-pub use auth::*;
\ No newline at end of file
+pub use auth::*;
+pub use jwt_keys::*;
+pub use extractors::*;
+pub use middleware::*;
+pub use email_templates::*;
\ No newline at end of file
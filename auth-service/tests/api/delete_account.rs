@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use auth_service::{
-    domain::{Email, MockUserStore, Password, User, UserStoreError},
+    domain::{Email, MockUserStore, Password, TwoFAMethod, User, UserStoreError},
     ErrorResponse,
 };
 use test_helpers::api_test;
@@ -112,7 +112,7 @@ async fn should_return_500_when_db_layer_add_user_returns_unexpectd_error() {
     let password = "password123";
     let expected_email = Email::parse(random_email.clone()).unwrap();
     
-    let user = User::new(expected_email.clone(), Password::parse(password.to_string()).unwrap(), true);
+    let user = User::new(expected_email.clone(), Password::parse(password.to_string()).unwrap(), TwoFAMethod::Email);
     mock_user_store
         .expect_delete_user()
         .withf(move |email, password| *email == user.email && *password == user.password)
@@ -1,21 +1,41 @@
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 use auth_service::{
+    domain::{Email, EmailContent, ServiceClients},
     app_state::{
-        AppState, BannedTokenStoreType, EmailClientType, TwoFACodeStoreType, UserStoreType
-    }, get_postgres_pool, get_redis_client, services::{HashmapTwoFACodeStore, HashsetBannedTokenStore, MockEmailClient, PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore}, utils::{test, DATABASE_URL, REDIS_HOST_NAME}, Application
+        AppState, BannedTokenStoreType, NotificationTargetStoreType, PasswordResetTokenStoreType, RecoveryCodeStoreType, RefreshTokenStoreType, ServiceClientsType, SessionStoreType, TotpSecretStoreType, TrustedDeviceStoreType, TwoFACodeStoreType, UserStoreType, WebAuthnChallengeStoreType, WebAuthnCredentialStoreType
+    }, get_postgres_pool, get_redis_client, services::{EmailOutboxSettings, EmailOutboxWorker, HashmapEmailOutboxStore, HashmapNotificationTargetStore, HashmapPasswordResetTokenStore, HashmapRecoveryCodeStore, HashmapRefreshTokenStore, HashmapSessionStore, HashmapTotpSecretStore, HashmapTrustedDeviceStore, HashmapTwoFACodeStore, HashmapVerificationEmailStore, HashmapWebAuthnChallengeStore, HashmapWebAuthnCredentialStore, HashsetBannedTokenStore, MockEmailClient, MockNotificationChannel, PostgresEmailOutboxStore, PostgresNotificationTargetStore, PostgresRecoveryCodeStore, PostgresTotpSecretStore, PostgresUserStore, PostgresWebAuthnCredentialStore, RedisBannedTokenStore, RedisPasswordResetTokenStore, RedisRefreshTokenStore, RedisSessionStore, RedisTrustedDeviceStore, RedisTwoFACodeStore, RedisVerificationEmailStore, RedisWebAuthnChallengeStore}, utils::{test, DATABASE_URL, REDIS_HOST_NAME}, Application
 };
 use reqwest::cookie::Jar;
 use sqlx::{postgres::{PgConnectOptions, PgPoolOptions}, Connection, Executor, PgConnection, PgPool};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+// Registered as a service client so tests can call service-to-service endpoints
+pub const TEST_CLIENT_ID: &str = "test-client";
+pub const TEST_CLIENT_SECRET: &str = "test-secret";
+const TEST_TWO_FA_CODE_SECRET: &[u8] = b"test-2fa-code-secret";
+
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
+    pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
-    pub email_client: EmailClientType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub totp_secret_store: TotpSecretStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub trusted_device_store: TrustedDeviceStoreType,
+    pub notification_target_store: NotificationTargetStoreType,
+    pub webauthn_challenge_store: WebAuthnChallengeStoreType,
+    pub webauthn_credential_store: WebAuthnCredentialStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub email_client: Arc<MockEmailClient>,
+    // Not running in the background, tests deliver queued emails with deliver_emails
+    pub email_outbox_worker: Arc<EmailOutboxWorker>,
+    pub sms_channel: Arc<MockNotificationChannel>,
+    pub push_channel: Arc<MockNotificationChannel>,
     pub http_client: reqwest::Client,
     pub db_name: Option<String>,
 }
@@ -26,17 +46,51 @@ impl TestApp {
         let pg_pool = configure_postgresql(db_name.clone()).await;
         let redis_conn = Arc::new(RwLock::new(configure_redis()));
 
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
         // let user_store = Arc::new(RwLock::new(HashmapUserStore::default()));
         let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
-        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn)));
-        let email_client = Arc::new(MockEmailClient {});
+        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(
+            redis_conn.clone(),
+            TEST_TWO_FA_CODE_SECRET,
+        )));
+        let refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone())));
+        let session_store = Arc::new(RwLock::new(RedisSessionStore::new(redis_conn.clone())));
+        let totp_secret_store = Arc::new(RwLock::new(PostgresTotpSecretStore::new(pg_pool.clone())));
+        let recovery_code_store = Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+        let notification_target_store = Arc::new(RwLock::new(PostgresNotificationTargetStore::new(pg_pool.clone())));
+        let webauthn_credential_store = Arc::new(RwLock::new(PostgresWebAuthnCredentialStore::new(pg_pool.clone())));
+        let email_outbox = Arc::new(RwLock::new(PostgresEmailOutboxStore::new(pg_pool)));
+        let trusted_device_store = Arc::new(RwLock::new(RedisTrustedDeviceStore::new(redis_conn.clone())));
+        let webauthn_challenge_store = Arc::new(RwLock::new(RedisWebAuthnChallengeStore::new(redis_conn.clone())));
+        let password_reset_token_store = Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(redis_conn.clone())));
+        let verification_email_store = Arc::new(RwLock::new(RedisVerificationEmailStore::new(redis_conn)));
+        let email_client = Arc::new(MockEmailClient::default());
+        let email_outbox_worker = Arc::new(EmailOutboxWorker::new(
+            email_outbox.clone(),
+            email_client.clone(),
+            test_email_outbox_settings(),
+        ));
+        let sms_channel = Arc::new(MockNotificationChannel::default());
+        let push_channel = Arc::new(MockNotificationChannel::default());
 
         let app_state: AppState = AppState::new(
-            user_store,
+            user_store.clone(),
             banned_token_store.clone(),
             two_fa_code_store.clone(),
-            email_client.clone(),
+            refresh_token_store.clone(),
+            session_store.clone(),
+            totp_secret_store.clone(),
+            recovery_code_store.clone(),
+            trusted_device_store.clone(),
+            notification_target_store.clone(),
+            webauthn_challenge_store.clone(),
+            webauthn_credential_store.clone(),
+            password_reset_token_store.clone(),
+            verification_email_store,
+            email_outbox.clone(),
+            Some(sms_channel.clone()),
+            Some(push_channel.clone()),
+            test_service_clients(),
         );
 
         let app = Application::build(app_state, test::APP_ADDRESS)
@@ -59,9 +113,22 @@ impl TestApp {
         TestApp {
             address,
             cookie_jar,
+            user_store,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            session_store,
+            totp_secret_store,
+            recovery_code_store,
+            trusted_device_store,
+            notification_target_store,
+            webauthn_challenge_store,
+            webauthn_credential_store,
+            password_reset_token_store,
             email_client,
+            email_outbox_worker,
+            sms_channel,
+            push_channel,
             http_client,
             db_name: Some(db_name),
         }
@@ -70,12 +137,43 @@ impl TestApp {
     pub async fn with_user_store(user_store: UserStoreType) -> Self {
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
         let two_fa_code_store = Arc::new(RwLock::new(HashmapTwoFACodeStore::default()));
-        let email_client = Arc::new(MockEmailClient {});
+        let refresh_token_store = Arc::new(RwLock::new(HashmapRefreshTokenStore::default()));
+        let session_store = Arc::new(RwLock::new(HashmapSessionStore::default()));
+        let totp_secret_store = Arc::new(RwLock::new(HashmapTotpSecretStore::default()));
+        let recovery_code_store = Arc::new(RwLock::new(HashmapRecoveryCodeStore::default()));
+        let trusted_device_store = Arc::new(RwLock::new(HashmapTrustedDeviceStore::default()));
+        let notification_target_store = Arc::new(RwLock::new(HashmapNotificationTargetStore::default()));
+        let webauthn_challenge_store = Arc::new(RwLock::new(HashmapWebAuthnChallengeStore::default()));
+        let webauthn_credential_store = Arc::new(RwLock::new(HashmapWebAuthnCredentialStore::default()));
+        let password_reset_token_store = Arc::new(RwLock::new(HashmapPasswordResetTokenStore::default()));
+        let verification_email_store = Arc::new(RwLock::new(HashmapVerificationEmailStore::default()));
+        let email_outbox = Arc::new(RwLock::new(HashmapEmailOutboxStore::default()));
+        let email_client = Arc::new(MockEmailClient::default());
+        let email_outbox_worker = Arc::new(EmailOutboxWorker::new(
+            email_outbox.clone(),
+            email_client.clone(),
+            test_email_outbox_settings(),
+        ));
+        let sms_channel = Arc::new(MockNotificationChannel::default());
+        let push_channel = Arc::new(MockNotificationChannel::default());
         let app_state: AppState = AppState::new(
-            user_store,
+            user_store.clone(),
             banned_token_store.clone(),
             two_fa_code_store.clone(),
-            email_client.clone(),
+            refresh_token_store.clone(),
+            session_store.clone(),
+            totp_secret_store.clone(),
+            recovery_code_store.clone(),
+            trusted_device_store.clone(),
+            notification_target_store.clone(),
+            webauthn_challenge_store.clone(),
+            webauthn_credential_store.clone(),
+            password_reset_token_store.clone(),
+            verification_email_store,
+            email_outbox.clone(),
+            Some(sms_channel.clone()),
+            Some(push_channel.clone()),
+            test_service_clients(),
         );
 
         let app = Application::build(app_state, test::APP_ADDRESS)
@@ -98,14 +196,37 @@ impl TestApp {
         TestApp {
             address,
             cookie_jar,
+            user_store,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            session_store,
+            totp_secret_store,
+            recovery_code_store,
+            trusted_device_store,
+            notification_target_store,
+            webauthn_challenge_store,
+            webauthn_credential_store,
+            password_reset_token_store,
             email_client,
+            email_outbox_worker,
+            sms_channel,
+            push_channel,
             http_client,
             db_name: None,
         }
     }
 
+    // Runs the outbox worker once, returning how many queued emails it tried to send
+    pub async fn deliver_emails(&self) -> usize {
+        // Emails retried by an earlier call only come due in a later millisecond
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        self.email_outbox_worker
+            .deliver_due()
+            .await
+            .expect("Failed to deliver queued emails")
+    }
+
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
             .get(format!("{}/", &self.address))
@@ -114,6 +235,14 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -158,6 +287,23 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_logout_with_bearer_token(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout", &self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -170,6 +316,117 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_resend_2fa_code<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-2fa-code", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_verify_email(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/verify-email", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-verification-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_forgot_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/forgot-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/reset-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Delivers queued emails and returns all the user got, oldest first
+    pub async fn emails(&self, email: &str) -> Vec<EmailContent> {
+        self.deliver_emails().await;
+        self.email_client
+            .emails(&Email::parse(email.to_owned()).unwrap())
+    }
+
+    // Delivers queued emails and returns the latest link to `path` the user got.
+    // Emails queued together can go out in any order, so all of them are searched.
+    pub async fn emailed_link(&self, email: &str, path: &str) -> String {
+        let emails = self.emails(email).await;
+
+        let prefix = format!("{}?token=", path);
+        emails
+            .iter()
+            .rev()
+            .flat_map(|content| content.text_body.split_whitespace())
+            .find(|word| word.contains(prefix.as_str()))
+            .map(str::to_owned)
+            .expect("No link with a token in the emails")
+    }
+
+    async fn emailed_link_token(&self, email: &str, path: &str) -> String {
+        let link = self.emailed_link(email, path).await;
+        let (_, token) = link.split_once("?token=").unwrap();
+        token.to_owned()
+    }
+
+    // Opens an emailed link on the test app, whatever address the emails point to
+    pub async fn follow_link(&self, link: &str) -> reqwest::Response {
+        let url = reqwest::Url::parse(link).expect("Invalid link");
+        self.http_client
+            .get(format!("{}{}", &self.address, url.path()))
+            .query(&url.query_pairs().collect::<Vec<_>>())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn email_verification_token(&self, email: &str) -> String {
+        self.emailed_link_token(email, "/verify-email").await
+    }
+
+    pub async fn password_reset_token(&self, email: &str) -> String {
+        self.emailed_link_token(email, "/reset-password").await
+    }
+
+    // Follows the link in the verification email, so the user can log in
+    pub async fn verify_email(&self, email: &str) {
+        let token = self.email_verification_token(email).await;
+        let response = self.get_verify_email(&token).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -182,13 +439,258 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_introspect(
+        &self,
+        token: &str,
+        client_id: &str,
+        client_secret: &str,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/introspect", &self.address))
+            .basic_auth(client_id, Some(client_secret))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh_token(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh-token", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh_token_body<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/refresh-token", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, session_id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, session_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_trusted_devices(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/trusted-devices", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_trusted_device(&self, device_id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/trusted-devices/{}", &self.address, device_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_request_2fa_code(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/request-2fa-code", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_request_2fa_code_with<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/request-2fa-code", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_enable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/enable-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/disable-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_enroll_totp(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/enroll-totp", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_totp<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/confirm-totp", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/recovery-codes", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/recovery-codes", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_register_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/register/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_webauthn_credentials(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/webauthn/credentials", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_webauthn_credential(&self, credential_id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!(
+                "{}/webauthn/credentials/{}",
+                &self.address, credential_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/webauthn/login/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_start_with<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn clean_up(&self) {
-        if self.db_name.is_some() {
-            delete_database(self.db_name.as_ref().unwrap().as_ref()).await;
+        if let Some(db_name) = &self.db_name {
+            delete_database(db_name).await;
         }
     }
 }
 
+fn test_service_clients() -> ServiceClientsType {
+    let clients = format!("{}:{}", TEST_CLIENT_ID, TEST_CLIENT_SECRET);
+    Arc::new(ServiceClients::parse(&clients).expect("Failed to parse service clients"))
+}
+
+// Retries straight away so tests don't have to wait out the backoff
+fn test_email_outbox_settings() -> EmailOutboxSettings {
+    EmailOutboxSettings {
+        poll_interval: Duration::from_millis(100),
+        batch_size: 20,
+        max_attempts: 3,
+        retry_delay: Duration::ZERO,
+        max_retry_delay: Duration::ZERO,
+        lease: Duration::from_secs(30),
+        retention: Duration::from_secs(3600),
+        cleanup_interval: Duration::from_secs(3600),
+    }
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
@@ -0,0 +1,188 @@
+use std::time::{Duration, Instant};
+
+use auth_service::{
+    domain::{Email, EmailClient, EmailClientError, EmailContent},
+    services::{HttpEmailClient, HttpEmailSettings, EMAIL_API_TOKEN_HEADER},
+};
+use wiremock::{
+    matchers::{body_json, header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const TOKEN: &str = "server-token";
+const MAX_RETRIES: u32 = 2;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+fn client(server: &MockServer, timeout: Duration) -> HttpEmailClient {
+    HttpEmailClient::new(HttpEmailSettings {
+        base_url: server.uri(),
+        token: TOKEN.to_owned(),
+        sender: "no-reply@example.com".to_owned(),
+        timeout,
+        max_retries: MAX_RETRIES,
+        retry_delay: Duration::from_millis(10),
+        max_retry_delay: MAX_RETRY_DELAY,
+    })
+    .unwrap()
+}
+
+async fn send_email(client: &HttpEmailClient) -> Result<(), EmailClientError> {
+    let recipient = Email::parse("user@example.com".to_owned()).unwrap();
+    let content = EmailContent {
+        subject: "Your login code".to_owned(),
+        html_body: "<p>123456</p>".to_owned(),
+        text_body: "123456".to_owned(),
+    };
+    client.send_email(&recipient, &content).await
+}
+
+#[tokio::test]
+async fn should_send_email_to_api() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .and(header(EMAIL_API_TOKEN_HEADER, TOKEN))
+        .and(header("Content-Type", "application/json"))
+        .and(header("Accept", "application/json"))
+        .and(body_json(serde_json::json!({
+            "From": "no-reply@example.com",
+            "To": "user@example.com",
+            "Subject": "Your login code",
+            "HtmlBody": "<p>123456</p>",
+            "TextBody": "123456",
+            "MessageStream": "outbound",
+        })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = send_email(&client(&server, Duration::from_secs(5))).await;
+    assert_eq!(result, Ok(()));
+}
+
+#[tokio::test]
+async fn should_retry_when_api_is_unavailable() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = send_email(&client(&server, Duration::from_secs(5))).await;
+    assert_eq!(result, Ok(()));
+}
+
+#[tokio::test]
+async fn should_give_up_after_max_retries() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(u64::from(MAX_RETRIES) + 1)
+        .mount(&server)
+        .await;
+
+    let result = send_email(&client(&server, Duration::from_secs(5))).await;
+    assert!(matches!(result, Err(EmailClientError::Unavailable(_))));
+}
+
+#[tokio::test]
+async fn should_cap_delay_between_retries() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(41)
+        .mount(&server)
+        .await;
+
+    // Doubling the delay for every retry would wait for years
+    let client = HttpEmailClient::new(HttpEmailSettings {
+        base_url: server.uri(),
+        token: TOKEN.to_owned(),
+        sender: "no-reply@example.com".to_owned(),
+        timeout: Duration::from_secs(5),
+        max_retries: 40,
+        retry_delay: Duration::from_millis(1),
+        max_retry_delay: Duration::from_millis(5),
+    })
+    .unwrap();
+
+    let result = send_email(&client).await;
+    assert!(matches!(result, Err(EmailClientError::Unavailable(_))));
+}
+
+#[tokio::test]
+async fn should_wait_as_long_as_api_asks() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let started = Instant::now();
+    let result = send_email(&client(&server, Duration::from_secs(5))).await;
+    assert_eq!(result, Ok(()));
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn should_give_up_if_api_asks_to_wait_too_long() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "3600"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = send_email(&client(&server, Duration::from_secs(5))).await;
+    assert!(matches!(result, Err(EmailClientError::Unavailable(_))));
+}
+
+#[tokio::test]
+async fn should_not_retry_rejected_email() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+            "ErrorCode": 300,
+            "Message": "Invalid email request",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = send_email(&client(&server, Duration::from_secs(5))).await;
+    assert!(matches!(result, Err(EmailClientError::Rejected(_))));
+}
+
+#[tokio::test]
+async fn should_time_out_slow_responses() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .expect(u64::from(MAX_RETRIES) + 1)
+        .mount(&server)
+        .await;
+
+    let result = send_email(&client(&server, Duration::from_millis(100))).await;
+    assert!(matches!(result, Err(EmailClientError::Unavailable(_))));
+}
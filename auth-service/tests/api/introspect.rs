@@ -0,0 +1,127 @@
+use auth_service::{
+    routes::IntrospectionResponse,
+    utils::{AUTH_TOKEN_SCOPE, JWT_COOKIE_NAME},
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp, TEST_CLIENT_ID, TEST_CLIENT_SECRET};
+
+// Returns the email and auth token of a newly logged in user
+async fn signup_and_login(app: &TestApp) -> (String, String) {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    (random_email, auth_cookie.value().to_owned())
+}
+
+#[api_test]
+async fn should_return_401_if_client_not_registered() {
+    let (_, token) = signup_and_login(&app).await;
+
+    let test_cases = [
+        (TEST_CLIENT_ID, "wrong-secret"),
+        ("unknown-client", TEST_CLIENT_SECRET),
+    ];
+
+    for (client_id, client_secret) in test_cases {
+        let response = app.post_introspect(&token, client_id, client_secret).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid client credentials".to_owned()
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_401_if_client_credentials_missing() {
+    let (_, token) = signup_and_login(&app).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/introspect", &app.address))
+        .form(&[("token", token)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_claims_of_active_token() {
+    let (email, token) = signup_and_login(&app).await;
+
+    let response = app
+        .post_introspect(&token, TEST_CLIENT_ID, TEST_CLIENT_SECRET)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse");
+
+    assert!(introspection.active);
+    assert_eq!(introspection.sub, Some(email));
+    assert_eq!(introspection.token_type, Some("Bearer".to_owned()));
+    assert_eq!(introspection.scope, Some(AUTH_TOKEN_SCOPE.to_owned()));
+    assert!(introspection.exp.is_some());
+    assert!(introspection.iat.is_some());
+    assert!(introspection.sid.is_some());
+}
+
+#[api_test]
+async fn should_return_inactive_for_invalid_or_revoked_token() {
+    let (_, token) = signup_and_login(&app).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for token in [token.as_str(), "invalid_token"] {
+        let response = app
+            .post_introspect(token, TEST_CLIENT_ID, TEST_CLIENT_SECRET)
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        let body = response
+            .json::<serde_json::Value>()
+            .await
+            .expect("Could not deserialize response body");
+
+        // Inactive responses must not disclose anything about the token
+        assert_eq!(body, serde_json::json!({ "active": false }));
+    }
+}
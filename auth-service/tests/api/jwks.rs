@@ -0,0 +1,22 @@
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn should_return_200_with_jwk_set() {
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwk_set = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    // Only public keys may ever be published, each identified by the kid tokens carry
+    for jwk in jwk_set.keys {
+        assert!(!matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)));
+        assert!(jwk.common.key_id.is_some());
+    }
+}
@@ -1,4 +1,4 @@
-use auth_service::{domain::{Email}, routes::TwoFactorAuthResponse, utils::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::{domain::{Email, LoginAttemptId}, routes::{TokenAuthResponse, TwoFactorAuthResponse}, utils::JWT_COOKIE_NAME, ErrorResponse};
 use test_helpers::api_test;
 
 use crate::helpers::{get_random_email, TestApp};
@@ -17,6 +17,8 @@ async fn should_return_422_if_malformed_credentials() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let test_cases = [
         serde_json::json!({
             "password": "password123",
@@ -53,6 +55,8 @@ async fn should_return_400_if_invalid_input() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let test_cases = vec![
         ("invalid_email", "password123"),
         (random_email.as_str(), "invalid"),
@@ -99,6 +103,8 @@ async fn should_return_401_if_incorrect_credentials() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
      let test_cases = vec![
         (random_email.as_str(), "wrong-password"),
         ("wrong@email.com", "password123"),
@@ -144,6 +150,8 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -161,6 +169,51 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     assert!(!auth_cookie.value().is_empty());
 }
 
+#[api_test]
+async fn should_return_token_in_body_if_requested() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "returnToken": true,
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME);
+
+    assert!(auth_cookie.is_none());
+
+    let token = response
+        .json::<TokenAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenAuthResponse")
+        .token;
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
 #[api_test]
 async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let random_email = get_random_email();
@@ -176,6 +229,8 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email.clone(),
         "password": "password123",
@@ -191,13 +246,108 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
         .expect("Could not deserialize response body to TwoFactorAuthResponse");
 
     assert_eq!(json_body.message, "2FA required".to_owned());
+    assert_eq!(json_body.two_fa_method, "email".to_owned());
 
     let two_fa_code_store = app.two_fa_code_store.read().await;
     let email = Email::parse(random_email).unwrap();
-    let code_tuple = two_fa_code_store
-        .get_code(&email)
+    let login_attempt_id = LoginAttemptId::parse(json_body.login_attempt_id).unwrap();
+
+    assert_eq!(
+        two_fa_code_store
+            .contains_code(&email, &login_attempt_id)
+            .await,
+        Ok(true)
+    );
+}
+
+#[api_test]
+async fn should_email_2fa_code_as_html_and_text() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email.clone(),
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
         .await
-        .expect("Failed to get 2FA code");
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(app.deliver_emails().await, 1);
+    let email = app
+        .email_client
+        .last_email(&Email::parse(random_email.clone()).unwrap())
+        .expect("No 2FA email was sent");
+    assert_eq!(email.subject, "Your Auth Service login code");
+
+    let code = email
+        .text_body
+        .lines()
+        .map(str::trim)
+        .find(|line| line.len() == 6 && line.chars().all(|c| c.is_ascii_digit()))
+        .expect("No 2FA code in the email")
+        .to_owned();
+    assert!(email.html_body.contains(&code));
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_deliver_2fa_email_after_email_outage() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    // Logging in doesn't wait for the email to be sent
+    app.email_client.set_unavailable(true);
+    let login_body = serde_json::json!({
+        "email": random_email.clone(),
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
 
-    assert_eq!(code_tuple.0.as_ref(), json_body.login_attempt_id);
+    let recipient = Email::parse(random_email).unwrap();
+    assert_eq!(app.deliver_emails().await, 1);
+    let email = app.email_client.last_email(&recipient).unwrap();
+    assert_eq!(email.subject, "Verify your Auth Service email address");
+
+    app.email_client.set_unavailable(false);
+    assert_eq!(app.deliver_emails().await, 1);
+    let email = app
+        .email_client
+        .last_email(&recipient)
+        .expect("No 2FA email was sent");
+    assert_eq!(email.subject, "Your Auth Service login code");
+
+    // Sent emails are not sent again
+    assert_eq!(app.deliver_emails().await, 0);
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use auth_service::{ErrorResponse, utils::JWT_COOKIE_NAME};
+use auth_service::{ErrorResponse, routes::TokenAuthResponse, utils::{validate_token, JWT_COOKIE_NAME}};
 use reqwest::Url;
 use test_helpers::api_test;
 
@@ -74,6 +74,8 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -92,6 +94,15 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let token = auth_cookie.value();
 
+    let claims = validate_token(
+        token,
+        app.banned_token_store.clone(),
+        app.session_store.clone(),
+        app.user_store.clone(),
+    )
+        .await
+        .expect("Failed to validate token");
+
     let response = app.post_logout().await;
 
     assert_eq!(response.status().as_u16(), 200);
@@ -105,13 +116,68 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let banned_token_store = app.banned_token_store.read().await;
     let contains_token = banned_token_store
-        .contains_token(token)
+        .contains_token(&claims.jti)
         .await
         .expect("Failed to check if token is banned");
 
     assert!(contains_token);
 }
 
+#[api_test]
+async fn should_return_200_if_valid_bearer_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "returnToken": true,
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .json::<TokenAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenAuthResponse")
+        .token;
+
+    let response = app.post_logout_with_bearer_token(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The token is revoked
+    let response = app.post_logout_with_bearer_token(&token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_malformed_authorization_header() {
+    let response = app
+        .http_client
+        .post(format!("{}/logout", &app.address))
+        .header("Authorization", "Basic dXNlcjpwYXNz")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
 #[api_test]
 async fn should_return_400_if_logout_called_twice_in_a_row() {
     let random_email = get_random_email();
@@ -126,6 +192,8 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
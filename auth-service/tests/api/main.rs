@@ -2,7 +2,24 @@ mod helpers;
 mod root;
 mod login;
 mod logout;
+mod logout_all;
 mod signup;
 mod delete_account;
 mod verify_2fa;
-mod verify_token;
\ No newline at end of file
+mod resend_2fa_code;
+mod verify_token;
+mod introspect;
+mod refresh_token;
+mod jwks;
+mod sessions;
+mod totp;
+mod recovery_codes;
+mod two_fa;
+mod trusted_devices;
+mod notification_channels;
+mod webauthn;
+mod smtp_email_client;
+mod http_email_client;
+mod webhook_notification_channel;
+mod verify_email;
+mod reset_password;
\ No newline at end of file
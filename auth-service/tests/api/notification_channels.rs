@@ -0,0 +1,324 @@
+use auth_service::{
+    domain::{
+        Email, LoginAttemptId, NotificationTarget, NotificationTargetStoreError, TwoFACode,
+        TwoFAMethod, RECOVERY_CODE_COUNT,
+    },
+    routes::{RecoveryCodesResponse, Request2FACodeResponse, TwoFactorAuthResponse},
+    services::MockNotificationChannel,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Codes are limited per phone number and device, and the Redis store is shared
+// between tests, so every test uses its own
+fn random_phone_number() -> String {
+    format!("+1555{:07}", rand::random::<u32>() % 10_000_000)
+}
+
+fn random_push_token() -> String {
+    format!("device-token-{}", Uuid::new_v4())
+}
+
+fn login_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+    })
+}
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    random_email
+}
+
+// The code of the last notification sent to the target
+fn sent_code(channel: &MockNotificationChannel, method: TwoFAMethod, target: &str) -> String {
+    let target = NotificationTarget::parse(method, target.to_owned()).unwrap();
+    let notification = channel
+        .last_notification(&target)
+        .expect("No notification was sent");
+
+    notification
+        .rsplit(' ')
+        .next()
+        .expect("Notification has no code")
+        .to_owned()
+}
+
+async fn request_2fa_code(app: &TestApp, body: &serde_json::Value) -> String {
+    let response = app.post_request_2fa_code_with(body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<Request2FACodeResponse>()
+        .await
+        .expect("Could not deserialize response body to Request2FACodeResponse")
+        .login_attempt_id
+}
+
+async fn enable_2fa(
+    app: &TestApp,
+    method: &str,
+    login_attempt_id: &str,
+    code: &str,
+) -> reqwest::Response {
+    app.post_enable_2fa(&serde_json::json!({
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+        "twoFAMethod": method,
+    }))
+    .await
+}
+
+async fn error_message(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+// Logs in and returns the login attempt id, checking the method the client is asked for
+async fn login_with_2fa(app: &TestApp, email: &str, method: &str) -> String {
+    let response = app.post_login(&login_body(email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(json_body.two_fa_method, method.to_owned());
+
+    json_body.login_attempt_id
+}
+
+#[api_test]
+async fn should_log_in_with_code_sent_by_sms() {
+    let phone_number = random_phone_number();
+    let random_email = signup_and_login(&app).await;
+
+    let login_attempt_id = request_2fa_code(
+        &app,
+        &serde_json::json!({ "twoFAMethod": "sms", "target": phone_number }),
+    )
+    .await;
+    let code = sent_code(&app.sms_channel, TwoFAMethod::Sms, &phone_number);
+
+    let response = enable_2fa(&app, "sms", &login_attempt_id, &code).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse");
+    assert_eq!(json_body.recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let target = app
+        .notification_target_store
+        .read()
+        .await
+        .get_target(&Email::parse(random_email.clone()).unwrap())
+        .await
+        .expect("Phone number was not confirmed");
+    assert_eq!(target.as_ref(), phone_number);
+
+    let login_attempt_id = login_with_2fa(&app, &random_email, "sms").await;
+    let login_code = sent_code(&app.sms_channel, TwoFAMethod::Sms, &phone_number);
+    assert_ne!(login_code, code);
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": login_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_only_confirm_target_with_code_sent_to_it() {
+    let phone_number = random_phone_number();
+    let random_email = signup_and_login(&app).await;
+
+    let login_attempt_id = request_2fa_code(
+        &app,
+        &serde_json::json!({ "twoFAMethod": "sms", "target": phone_number }),
+    )
+    .await;
+    let code = sent_code(&app.sms_channel, TwoFAMethod::Sms, &phone_number);
+
+    // A code sent to the user's inbox belongs to another login attempt. Stores only
+    // keep hashed codes, and another code can't be requested right away, so the
+    // attempt is added with a code known to the test.
+    let other_login_attempt_id = LoginAttemptId::default();
+    let other_code = TwoFACode::default();
+    app.two_fa_code_store
+        .write()
+        .await
+        .add_code(
+            Email::parse(random_email).unwrap(),
+            other_login_attempt_id.clone(),
+            other_code.clone(),
+        )
+        .await
+        .expect("Failed to set 2FA code");
+    let other_login_attempt_id = other_login_attempt_id.as_ref();
+
+    let response = enable_2fa(&app, "sms", other_login_attempt_id, other_code.as_ref()).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        error_message(response).await,
+        "No phone number or push device awaiting confirmation".to_owned()
+    );
+
+    let response = enable_2fa(&app, "sms", &login_attempt_id, &code).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_log_in_with_code_sent_by_push() {
+    let push_token = random_push_token();
+    let random_email = signup_and_login(&app).await;
+
+    let login_attempt_id = request_2fa_code(
+        &app,
+        &serde_json::json!({ "twoFAMethod": "push", "target": push_token }),
+    )
+    .await;
+    let code = sent_code(&app.push_channel, TwoFAMethod::Push, &push_token);
+
+    let response = enable_2fa(&app, "push", &login_attempt_id, &code).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = login_with_2fa(&app, &random_email, "push").await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": sent_code(&app.push_channel, TwoFAMethod::Push, &push_token),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_400_if_target_invalid() {
+    let phone_number = random_phone_number();
+    signup_and_login(&app).await;
+
+    let bodies = [
+        serde_json::json!({ "twoFAMethod": "sms", "target": "5551234567" }),
+        serde_json::json!({ "twoFAMethod": "sms" }),
+        serde_json::json!({ "twoFAMethod": "push", "target": "" }),
+        serde_json::json!({ "twoFAMethod": "pigeon", "target": phone_number }),
+    ];
+
+    for body in bodies {
+        let response = app.post_request_2fa_code_with(&body).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", body);
+    }
+
+    let response = app
+        .post_request_2fa_code_with(&serde_json::json!({ "twoFAMethod": "totp" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        error_message(response).await,
+        "2FA codes are only sent for email, SMS and push 2FA".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_400_if_sms_enabled_without_pending_phone_number() {
+    signup_and_login(&app).await;
+
+    // The code goes to the user's inbox, which doesn't prove they own a phone number
+    let login_attempt_id =
+        request_2fa_code(&app, &serde_json::json!({ "twoFAMethod": "email" })).await;
+
+    let response = enable_2fa(&app, "sms", &login_attempt_id, "123456").await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        error_message(response).await,
+        "No phone number or push device awaiting confirmation".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_send_code_for_disabling_2fa_by_sms() {
+    let phone_number = random_phone_number();
+    let random_email = signup_and_login(&app).await;
+
+    let login_attempt_id = request_2fa_code(
+        &app,
+        &serde_json::json!({ "twoFAMethod": "sms", "target": phone_number }),
+    )
+    .await;
+    let code = sent_code(&app.sms_channel, TwoFAMethod::Sms, &phone_number);
+    let response = enable_2fa(&app, "sms", &login_attempt_id, &code).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Without a body the code goes to the phone number 2FA is set up with
+    let response = app.post_request_2fa_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = response
+        .json::<Request2FACodeResponse>()
+        .await
+        .expect("Could not deserialize response body to Request2FACodeResponse")
+        .login_attempt_id;
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": sent_code(&app.sms_channel, TwoFAMethod::Sms, &phone_number),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The phone number is forgotten along with 2FA
+    let result = app
+        .notification_target_store
+        .read()
+        .await
+        .get_target(&Email::parse(random_email).unwrap())
+        .await;
+    assert_eq!(result, Err(NotificationTargetStoreError::TargetNotFound));
+}
+
+#[api_test]
+async fn should_limit_codes_sent_to_a_target() {
+    let phone_number = random_phone_number();
+    signup_and_login(&app).await;
+    let body = serde_json::json!({ "twoFAMethod": "sms", "target": phone_number });
+
+    request_2fa_code(&app, &body).await;
+
+    let response = app.post_request_2fa_code_with(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+
+    // Nor can another account send codes to the same phone number
+    signup_and_login(&app).await;
+    let response = app.post_request_2fa_code_with(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
@@ -0,0 +1,249 @@
+use auth_service::{
+    domain::{Email, RECOVERY_CODE_COUNT},
+    routes::{
+        RecoveryCodesResponse, RemainingRecoveryCodesResponse, SignupResponse,
+        TwoFactorAuthResponse,
+    },
+    utils::{DEFAULT_MAX_2FA_USER_FAILURES, JWT_COOKIE_NAME},
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Signs up a user with 2FA and returns their email and recovery codes
+async fn signup_with_2fa(app: &TestApp) -> (String, Vec<String>) {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let json_body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+
+    (random_email, json_body.recovery_codes)
+}
+
+async fn login_with_recovery_code(
+    app: &TestApp,
+    email: &str,
+    recovery_code: &str,
+) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let verify_2fa_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": json_body.login_attempt_id,
+        "2FACode": recovery_code,
+    });
+
+    app.post_verify_2fa(&verify_2fa_body).await
+}
+
+fn password_body() -> serde_json::Value {
+    serde_json::json!({ "password": "password123" })
+}
+
+async fn remaining_recovery_codes(app: &TestApp) -> usize {
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<RemainingRecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RemainingRecoveryCodesResponse")
+        .remaining
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_recovery_codes(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_log_in_with_recovery_code_only_once() {
+    let (email, recovery_codes) = signup_with_2fa(&app).await;
+
+    let response = login_with_recovery_code(&app, &email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    assert_eq!(remaining_recovery_codes(&app).await, RECOVERY_CODE_COUNT - 1);
+
+    let response = login_with_recovery_code(&app, &email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Codes are accepted regardless of case
+    let response =
+        login_with_recovery_code(&app, &email, &recovery_codes[1].to_uppercase()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(remaining_recovery_codes(&app).await, RECOVERY_CODE_COUNT - 2);
+}
+
+#[api_test]
+async fn should_return_401_if_recovery_code_unknown() {
+    let (email, _) = signup_with_2fa(&app).await;
+
+    let response = login_with_recovery_code(&app, &email, "abcde-fghjk-mnpqr").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let count = app
+        .recovery_code_store
+        .read()
+        .await
+        .count_codes(&Email::parse(email).unwrap())
+        .await;
+    assert_eq!(count, Ok(RECOVERY_CODE_COUNT));
+}
+
+#[api_test]
+async fn should_lock_out_user_after_too_many_wrong_codes_across_login_attempts() {
+    let (email, recovery_codes) = signup_with_2fa(&app).await;
+
+    // Each guess starts a new login attempt, so only the count kept per user stops them
+    for _ in 1..DEFAULT_MAX_2FA_USER_FAILURES {
+        let response = login_with_recovery_code(&app, &email, "abcde-fghjk-mnpqr").await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = login_with_recovery_code(&app, &email, "abcde-fghjk-mnpqr").await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    // Even a valid code is refused while the user is locked out, and it isn't used up
+    let response = login_with_recovery_code(&app, &email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many incorrect 2FA codes, please try again later".to_owned()
+    );
+
+    let count = app
+        .recovery_code_store
+        .read()
+        .await
+        .count_codes(&Email::parse(email).unwrap())
+        .await;
+    assert_eq!(count, Ok(RECOVERY_CODE_COUNT));
+}
+
+#[api_test]
+async fn should_invalidate_old_codes_when_regenerated() {
+    let (email, old_recovery_codes) = signup_with_2fa(&app).await;
+
+    let response = login_with_recovery_code(&app, &email, &old_recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse");
+
+    assert_eq!(json_body.recovery_codes.len(), RECOVERY_CODE_COUNT);
+    assert_eq!(remaining_recovery_codes(&app).await, RECOVERY_CODE_COUNT);
+
+    let response = login_with_recovery_code(&app, &email, &old_recovery_codes[1]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login_with_recovery_code(&app, &email, &json_body.recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let emails = app.emails(&email).await;
+    assert!(emails
+        .iter()
+        .any(|email| email.text_body.contains("New recovery codes were generated")));
+}
+
+#[api_test]
+async fn should_return_401_if_regenerated_with_incorrect_password() {
+    let (email, recovery_codes) = signup_with_2fa(&app).await;
+
+    let response = login_with_recovery_code(&app, &email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_recovery_codes(&serde_json::json!({ "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(remaining_recovery_codes(&app).await, RECOVERY_CODE_COUNT - 1);
+}
+
+#[api_test]
+async fn should_return_400_if_regenerated_without_2fa() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let json_body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+    assert!(json_body.recovery_codes.is_empty());
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "2FA is not enabled".to_owned()
+    );
+}
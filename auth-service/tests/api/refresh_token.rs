@@ -0,0 +1,245 @@
+use auth_service::{
+    domain::RefreshTokenStoreError,
+    routes::TokenAuthResponse,
+    utils::{decode_refresh_token, JWT_COOKIE_NAME, JWT_REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use reqwest::Url;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Returns the auth token and refresh token issued on login
+async fn signup_and_login(app: &TestApp) -> (String, String) {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    (auth_token, refresh_token)
+}
+
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            JWT_REFRESH_COOKIE_NAME, token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[api_test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_invalid_refresh_token() {
+    set_refresh_cookie(&app, "invalid");
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_auth_token_used_as_refresh_token() {
+    let (auth_token, _) = signup_and_login(&app).await;
+
+    set_refresh_cookie(&app, &auth_token);
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_200_and_rotate_refresh_token() {
+    let (_, refresh_token) = signup_and_login(&app).await;
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let refresh_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found");
+
+    assert!(!refresh_cookie.value().is_empty());
+    assert_ne!(refresh_cookie.value(), refresh_token);
+
+    // The rotated refresh token can be used again
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_revoke_token_family_if_refresh_token_reused() {
+    let (_, old_refresh_token) = signup_and_login(&app).await;
+
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    // Replay the already rotated refresh token
+    set_refresh_cookie(&app, &old_refresh_token);
+
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The whole family is revoked, including the latest refresh token
+    set_refresh_cookie(&app, &new_refresh_token);
+
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let claims = decode_refresh_token(&new_refresh_token).expect("Failed to decode refresh token");
+    let result = app
+        .refresh_token_store
+        .read()
+        .await
+        .get_current_token(&claims.family)
+        .await;
+
+    assert_eq!(result, Err(RefreshTokenStoreError::FamilyNotFound));
+}
+
+#[api_test]
+async fn should_return_401_if_refresh_token_used_after_logout() {
+    let (_, refresh_token) = signup_and_login(&app).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found");
+
+    assert!(refresh_cookie.value().is_empty());
+
+    set_refresh_cookie(&app, &refresh_token);
+
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_refresh_tokens_in_body_without_cookies() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "returnToken": true,
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.cookies().count(), 0);
+
+    let tokens = response
+        .json::<TokenAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenAuthResponse");
+
+    let response = app
+        .post_refresh_token_body(&serde_json::json!({ "refreshToken": tokens.refresh_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.cookies().count(), 0);
+
+    let refreshed = response
+        .json::<TokenAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenAuthResponse");
+    assert_ne!(refreshed.refresh_token, tokens.refresh_token);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": refreshed.token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The rotated refresh token can be used again
+    let response = app
+        .post_refresh_token_body(&serde_json::json!({ "refreshToken": refreshed.refresh_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
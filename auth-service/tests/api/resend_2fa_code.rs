@@ -0,0 +1,127 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId, TwoFACode},
+    routes::TwoFactorAuthResponse,
+    utils::DEFAULT_TWO_FA_RESEND_COOLDOWN_SECS,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Signs up a user with email 2FA, logs in and returns the login attempt id
+async fn login_with_2fa(app: &TestApp, email: &str) -> String {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+#[api_test]
+async fn should_return_422_if_malformed_input() {
+    let body = serde_json::json!({
+        "email": get_random_email(),
+    });
+    let response = app.post_resend_2fa_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_input() {
+    let body = serde_json::json!({
+        "email": get_random_email(),
+        "loginAttemptId": "invalid_login_attempt_id",
+    });
+    let response = app.post_resend_2fa_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_401_if_login_attempt_unknown() {
+    let random_email = get_random_email();
+    login_with_2fa(&app, &random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": LoginAttemptId::default().as_ref(),
+    });
+    let response = app.post_resend_2fa_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_429_with_wait_time_during_cooldown() {
+    let random_email = get_random_email();
+    let login_attempt_id = login_with_2fa(&app, &random_email).await;
+
+    // Stores only keep hashed codes, so the attempt gets a code known to the test
+    let code = TwoFACode::default();
+    app.two_fa_code_store
+        .write()
+        .await
+        .add_code(
+            Email::parse(random_email.clone()).unwrap(),
+            LoginAttemptId::parse(login_attempt_id.clone()).unwrap(),
+            code.clone(),
+        )
+        .await
+        .expect("Failed to set 2FA code");
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+    });
+    let response = app.post_resend_2fa_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after_header = response
+        .headers()
+        .get("retry-after")
+        .expect("No Retry-After header found")
+        .to_str()
+        .unwrap()
+        .parse::<u64>()
+        .unwrap();
+
+    let json_body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+
+    assert_eq!(
+        json_body.error,
+        "Please wait before requesting another 2FA code".to_owned()
+    );
+    assert_eq!(json_body.retry_after, Some(retry_after_header));
+    assert!(retry_after_header > 0 && retry_after_header <= DEFAULT_TWO_FA_RESEND_COOLDOWN_SECS);
+
+    // The code sent at login is still valid
+    let verify_2fa_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code.as_ref(),
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
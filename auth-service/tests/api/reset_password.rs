@@ -0,0 +1,318 @@
+use auth_service::{
+    domain::{Email, PasswordReset, PasswordResetToken},
+    routes::ResetPasswordResponse,
+    utils::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": password,
+    });
+    app.post_login(&login_body).await
+}
+
+// Asks for a reset link and returns the token in it
+async fn forgot_password(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    app.password_reset_token(email).await
+}
+
+async fn reset_password(app: &TestApp, token: &str, password: &str) -> reqwest::Response {
+    app.post_reset_password(&serde_json::json!({
+        "token": token,
+        "password": password,
+    }))
+    .await
+}
+
+async fn assert_invalid_reset_link(response: reqwest::Response) {
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid or expired password reset link".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_email_reset_link() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    app.verify_email(&random_email).await;
+
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    assert_eq!(app.deliver_emails().await, 1);
+    let email = app
+        .email_client
+        .last_email(&Email::parse(random_email).unwrap())
+        .expect("No reset email was sent");
+    assert_eq!(email.subject, "Reset your Auth Service password");
+    assert!(email.html_body.contains("/reset-password?token="));
+}
+
+#[api_test]
+async fn should_reset_password_through_emailed_link() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    app.verify_email(&random_email).await;
+
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let link = app.emailed_link(&random_email, "/reset-password").await;
+    let response = app.follow_link(&link).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+
+    // The page's form sends the token from the link along with the new password
+    let page = response.text().await.unwrap();
+    assert!(page.contains(r#"id="reset-form""#));
+    let (_, rest) = page
+        .split_once(r#"name="token" value=""#)
+        .expect("No token in the form");
+    let (token, _) = rest.split_once('"').unwrap();
+
+    let response = reset_password(&app, token, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login(&app, &random_email, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_400_if_emailed_link_is_invalid() {
+    let response = app
+        .follow_link("http://localhost:3000/reset-password?token=invalid")
+        .await;
+    assert_invalid_reset_link(response).await;
+}
+
+#[api_test]
+async fn should_return_202_without_email_if_unknown_email() {
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    assert_eq!(app.deliver_emails().await, 0);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_email() {
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": "invalid" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_422_if_malformed_input() {
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "address": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = app
+        .post_reset_password(&serde_json::json!({ "password": "new-password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[api_test]
+async fn should_reset_password_and_revoke_tokens() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    app.verify_email(&random_email).await;
+
+    let response = login(&app, &random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let old_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let token = forgot_password(&app, &random_email).await;
+    let response = reset_password(&app, &token, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<ResetPasswordResponse>()
+            .await
+            .expect("Could not deserialize response body to ResetPasswordResponse")
+            .message,
+        "Password updated".to_owned()
+    );
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login(&app, &random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = login(&app, &random_email, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(app.deliver_emails().await, 1);
+    let email = app
+        .email_client
+        .last_email(&Email::parse(random_email).unwrap())
+        .expect("No security notice was sent");
+    assert_eq!(email.subject, "Security alert for your Auth Service account");
+}
+
+#[api_test]
+async fn should_return_400_if_token_used_twice() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    app.verify_email(&random_email).await;
+
+    let token = forgot_password(&app, &random_email).await;
+    let response = reset_password(&app, &token, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = reset_password(&app, &token, "other-password123").await;
+    assert_invalid_reset_link(response).await;
+
+    let response = login(&app, &random_email, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_token() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    let token = forgot_password(&app, &random_email).await;
+
+    let other_token = "A".repeat(token.len());
+    for token in ["invalid", "", other_token.as_str()] {
+        let response = reset_password(&app, token, "new-password123").await;
+        assert_invalid_reset_link(response).await;
+    }
+}
+
+#[api_test]
+async fn should_keep_token_if_invalid_password() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    app.verify_email(&random_email).await;
+    let token = forgot_password(&app, &random_email).await;
+
+    let response = reset_password(&app, &token, "short").await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid credentials".to_owned()
+    );
+
+    let response = reset_password(&app, &token, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_invalidate_older_links_after_reset() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    app.verify_email(&random_email).await;
+
+    let first_token = forgot_password(&app, &random_email).await;
+
+    // Another link can't be asked for until the cooldown passed
+    let email = Email::parse(random_email).unwrap();
+    let token_generation = app
+        .user_store
+        .read()
+        .await
+        .get_token_generation(&email)
+        .await
+        .unwrap();
+    let second_token = PasswordResetToken::default();
+    app.password_reset_token_store
+        .write()
+        .await
+        .add_token(
+            &second_token,
+            PasswordReset {
+                email,
+                token_generation,
+            },
+        )
+        .await
+        .unwrap();
+
+    let response = reset_password(&app, second_token.as_ref(), "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = reset_password(&app, &first_token, "other-password123").await;
+    assert_invalid_reset_link(response).await;
+}
+
+#[api_test]
+async fn should_verify_email_when_password_reset() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let token = forgot_password(&app, &random_email).await;
+    let response = reset_password(&app, &token, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login(&app, &random_email, "new-password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_limit_reset_emails_even_if_unknown_email() {
+    let body = serde_json::json!({ "email": get_random_email() });
+
+    let response = app.post_forgot_password(&body).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let response = app.post_forgot_password(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Please wait before requesting another password reset email".to_owned()
+    );
+}
@@ -0,0 +1,152 @@
+use auth_service::{routes::ListSessionsResponse, utils::JWT_COOKIE_NAME, ErrorResponse};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    random_email
+}
+
+// Logs in and returns the auth token of the new session
+async fn login(app: &TestApp, email: &str) -> String {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_owned()
+}
+
+async fn list_sessions(app: &TestApp) -> ListSessionsResponse {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<ListSessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to ListSessionsResponse")
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.delete_session("session_id").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_list_active_sessions() {
+    let email = signup(&app).await;
+    login(&app, &email).await;
+    login(&app, &email).await;
+
+    let sessions = list_sessions(&app).await.sessions;
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+}
+
+#[api_test]
+async fn should_not_list_sessions_of_other_users() {
+    let other_email = signup(&app).await;
+    login(&app, &other_email).await;
+
+    let email = signup(&app).await;
+    login(&app, &email).await;
+
+    let sessions = list_sessions(&app).await.sessions;
+
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+#[api_test]
+async fn should_revoke_session() {
+    let email = signup(&app).await;
+    let old_token = login(&app, &email).await;
+    login(&app, &email).await;
+
+    let sessions = list_sessions(&app).await.sessions;
+    let old_session = sessions
+        .iter()
+        .find(|session| !session.current)
+        .expect("No other session found");
+
+    let response = app.delete_session(&old_session.id).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    // Tokens of the revoked session fail verification
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let sessions = list_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+#[api_test]
+async fn should_return_401_after_revoking_current_session() {
+    let email = signup(&app).await;
+    login(&app, &email).await;
+
+    let sessions = list_sessions(&app).await.sessions;
+
+    let response = app.delete_session(&sessions[0].id).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The refresh token family of the session is revoked as well
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_404_if_session_of_other_user() {
+    let other_email = signup(&app).await;
+    login(&app, &other_email).await;
+    let other_session = list_sessions(&app).await.sessions.remove(0);
+
+    let email = signup(&app).await;
+    login(&app, &email).await;
+
+    for session_id in [other_session.id.as_str(), "unknown_session"] {
+        let response = app.delete_session(session_id).await;
+        assert_eq!(response.status().as_u16(), 404);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Session not found".to_owned()
+        );
+    }
+}
@@ -2,7 +2,9 @@ use std::sync::Arc;
 
 use auth_service::{
     ErrorResponse,
-    domain::{Email, MockUserStore, Password, User, UserStoreError},
+    domain::{
+        Email, MockUserStore, Password, TwoFAMethod, User, UserStoreError, RECOVERY_CODE_COUNT,
+    },
     routes::SignupResponse,
 };
 use test_helpers::api_test;
@@ -67,17 +69,14 @@ async fn should_return_201_if_valid_input() {
         body
     );
 
-    let expected_response = SignupResponse {
-        message: "User created successfully!".to_owned(),
-    };
+    let json_body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to UserBody");
 
-    assert_eq!(
-        response
-            .json::<SignupResponse>()
-            .await
-            .expect("Could not deserialize response body to UserBody"),
-        expected_response,
-    );
+    assert_eq!(json_body.message, "User created successfully!".to_owned());
+    // Users signing up with 2FA get recovery codes
+    assert_eq!(json_body.recovery_codes.len(), RECOVERY_CODE_COUNT);
 }
 
 #[api_test]
@@ -200,12 +199,12 @@ async fn should_return_500_when_db_layer_add_user_returns_unexpectd_error() {
     let user = User::new(
         expected_email.clone(),
         Password::parse(password.to_string()).unwrap(),
-        true,
+        TwoFAMethod::Email,
     );
     mock_user_store
         .expect_add_user()
         .withf(move |u| {
-            u.email == user.email && u.password == u.password && u.require_2fa == user.require_2fa
+            u.email == user.email && u.password == u.password && u.two_fa_method == user.two_fa_method
         })
         .once()
         .returning(|_u| Box::pin(async { Err(UserStoreError::UnexpectedError) }));
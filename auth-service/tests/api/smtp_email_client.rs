@@ -0,0 +1,209 @@
+use std::{sync::Arc, time::Duration};
+
+use auth_service::{
+    domain::{Email, EmailClient, EmailClientError, EmailContent},
+    services::{SmtpEmailClient, SmtpSettings, SmtpTls},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::Mutex,
+};
+
+// What the stand-in received for one email
+#[derive(Clone, Debug, Default)]
+struct CapturedEmail {
+    // Decoded AUTH PLAIN response, "\0username\0password"
+    auth: Option<String>,
+    from: String,
+    to: Vec<String>,
+    data: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Behavior {
+    Accept,
+    RejectRecipients,
+    // Accepts connections but never greets the client
+    Hang,
+}
+
+// Minimal SMTP server that records the emails it is sent
+struct SmtpStandIn {
+    port: u16,
+    emails: Arc<Mutex<Vec<CapturedEmail>>>,
+}
+
+impl SmtpStandIn {
+    async fn start(behavior: Behavior) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let emails = Arc::new(Mutex::new(Vec::new()));
+
+        let captured = emails.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, behavior, captured.clone()));
+            }
+        });
+
+        Self { port, emails }
+    }
+
+    fn client(&self, credentials: Option<(&str, &str)>, timeout: Duration) -> SmtpEmailClient {
+        SmtpEmailClient::new(SmtpSettings {
+            host: "127.0.0.1".to_owned(),
+            port: self.port,
+            tls: SmtpTls::None,
+            credentials: credentials.map(|(user, pass)| (user.to_owned(), pass.to_owned())),
+            sender: "Auth Service <no-reply@example.com>".parse().unwrap(),
+            timeout,
+        })
+        .unwrap()
+    }
+}
+
+async fn serve(
+    stream: tokio::net::TcpStream,
+    behavior: Behavior,
+    emails: Arc<Mutex<Vec<CapturedEmail>>>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    if behavior == Behavior::Hang {
+        while let Ok(Some(_)) = lines.next_line().await {}
+        return;
+    }
+
+    writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+    let mut email = CapturedEmail::default();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let command = line.to_ascii_uppercase();
+        let reply: &[u8] = if command.starts_with("EHLO") {
+            b"250-localhost\r\n250 AUTH PLAIN LOGIN\r\n"
+        } else if let Some(response) = line.strip_prefix("AUTH PLAIN ") {
+            let decoded = STANDARD.decode(response).unwrap();
+            email.auth = Some(String::from_utf8(decoded).unwrap());
+            b"235 Authentication succeeded\r\n"
+        } else if command.starts_with("MAIL FROM:") {
+            email.from = address(&line);
+            b"250 OK\r\n"
+        } else if command.starts_with("RCPT TO:") {
+            match behavior {
+                Behavior::RejectRecipients => b"550 No such user\r\n",
+                _ => {
+                    email.to.push(address(&line));
+                    b"250 OK\r\n"
+                }
+            }
+        } else if command == "DATA" {
+            writer
+                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                .await
+                .unwrap();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line == "." {
+                    break;
+                }
+                email.data.push_str(&line);
+                email.data.push('\n');
+            }
+            emails.lock().await.push(std::mem::take(&mut email));
+            b"250 Queued\r\n"
+        } else if command == "QUIT" {
+            writer.write_all(b"221 Bye\r\n").await.unwrap();
+            return;
+        } else {
+            b"250 OK\r\n"
+        };
+        writer.write_all(reply).await.unwrap();
+    }
+}
+
+fn address(line: &str) -> String {
+    let start = line.find('<').map_or(0, |i| i + 1);
+    let end = line.find('>').unwrap_or(line.len());
+    line[start..end].to_owned()
+}
+
+fn content() -> EmailContent {
+    EmailContent {
+        subject: "Your login code".to_owned(),
+        html_body: "<p>123456</p>".to_owned(),
+        text_body: "123456".to_owned(),
+    }
+}
+
+fn recipient() -> Email {
+    Email::parse("user@example.com".to_owned()).unwrap()
+}
+
+#[tokio::test]
+async fn should_deliver_email_to_smtp_server() {
+    let server = SmtpStandIn::start(Behavior::Accept).await;
+    let client = server.client(None, Duration::from_secs(5));
+
+    let result = client.send_email(&recipient(), &content()).await;
+    assert_eq!(result, Ok(()));
+
+    let emails = server.emails.lock().await;
+    assert_eq!(emails.len(), 1);
+
+    let email = &emails[0];
+    assert_eq!(email.auth, None);
+    assert_eq!(email.from, "no-reply@example.com");
+    assert_eq!(email.to, vec!["user@example.com".to_owned()]);
+    assert!(email
+        .data
+        .contains("From: \"Auth Service\" <no-reply@example.com>"));
+    assert!(email.data.contains("To: user@example.com"));
+    assert!(email.data.contains("Subject: Your login code"));
+    // Both bodies, so clients that don't show HTML fall back to the text
+    assert!(email.data.contains("Content-Type: multipart/alternative"));
+    assert!(email
+        .data
+        .contains("Content-Type: text/plain; charset=utf-8"));
+    assert!(email
+        .data
+        .contains("Content-Type: text/html; charset=utf-8"));
+    assert!(email.data.contains("\n<p>123456</p>\n"));
+}
+
+#[tokio::test]
+async fn should_authenticate_with_credentials() {
+    let server = SmtpStandIn::start(Behavior::Accept).await;
+    let client = server.client(Some(("mailer", "secret")), Duration::from_secs(5));
+
+    let result = client.send_email(&recipient(), &content()).await;
+    assert_eq!(result, Ok(()));
+
+    let emails = server.emails.lock().await;
+    assert_eq!(emails[0].auth.as_deref(), Some("\0mailer\0secret"));
+}
+
+#[tokio::test]
+async fn should_return_error_if_recipient_is_rejected() {
+    let server = SmtpStandIn::start(Behavior::RejectRecipients).await;
+    let client = server.client(None, Duration::from_secs(5));
+
+    let result = client.send_email(&recipient(), &content()).await;
+    assert!(matches!(result, Err(EmailClientError::Rejected(_))));
+    assert!(server.emails.lock().await.is_empty());
+}
+
+#[tokio::test]
+async fn should_time_out_if_server_does_not_respond() {
+    let server = SmtpStandIn::start(Behavior::Hang).await;
+    let client = server.client(None, Duration::from_secs(1));
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(10),
+        client.send_email(&recipient(), &content()),
+    )
+    .await
+    .expect("The SMTP client did not time out");
+    assert!(matches!(result, Err(EmailClientError::Unavailable(_))));
+}
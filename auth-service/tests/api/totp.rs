@@ -0,0 +1,247 @@
+use auth_service::{
+    domain::{Email, TotpSecret, TotpSecretStoreError, RECOVERY_CODE_COUNT},
+    routes::{EnrollTotpResponse, RecoveryCodesResponse, TwoFactorAuthResponse},
+    ErrorResponse,
+};
+use chrono::Utc;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    random_email
+}
+
+fn login_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+    })
+}
+
+async fn enroll(app: &TestApp) -> TotpSecret {
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    TotpSecret::parse(json_body.secret).expect("Enrollment returned an invalid secret")
+}
+
+fn now() -> u64 {
+    Utc::now().timestamp() as u64
+}
+
+// Logs in with the password and returns the login attempt id of the second step
+async fn login_with_totp(app: &TestApp, email: &str) -> String {
+    let response = app.post_login(&login_body(email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(json_body.two_fa_method, "totp".to_owned());
+
+    json_body.login_attempt_id
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_otpauth_uri_and_keep_secret_pending() {
+    let random_email = signup_and_login(&app).await;
+
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    assert!(json_body.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(json_body
+        .otpauth_uri
+        .contains(&format!("secret={}", json_body.secret)));
+
+    let email = Email::parse(random_email).unwrap();
+    let totp_secret_store = app.totp_secret_store.read().await;
+
+    let pending_secret = totp_secret_store
+        .get_pending_secret(&email)
+        .await
+        .expect("Failed to get pending secret");
+    assert_eq!(pending_secret.as_ref(), json_body.secret);
+
+    assert_eq!(
+        totp_secret_store.get_secret(&email).await,
+        Err(TotpSecretStoreError::SecretNotFound)
+    );
+}
+
+#[api_test]
+async fn should_return_400_if_confirmed_without_enrollment() {
+    signup_and_login(&app).await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "No TOTP enrollment in progress".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_confirmation_code_incorrect() {
+    let random_email = signup_and_login(&app).await;
+    let secret = enroll(&app).await;
+
+    // A code from ten minutes in the future is outside the accepted window
+    let code = secret.generate_code(now() + 600);
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": code.as_ref() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Logging in still doesn't require 2FA
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_require_totp_code_after_confirmation() {
+    let random_email = signup_and_login(&app).await;
+    let secret = enroll(&app).await;
+
+    let code = secret.generate_code(now());
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": code.as_ref() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse");
+    assert_eq!(json_body.recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let login_attempt_id = login_with_totp(&app, &random_email).await;
+
+    // The confirmation code can't be used to log in
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Codes of the next time step are accepted to allow for clock drift
+    let code = secret.generate_code(now() + 30);
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_totp_code_replayed() {
+    let random_email = signup_and_login(&app).await;
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": secret.generate_code(now()).as_ref() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let code = secret.generate_code(now() + 30);
+
+    let login_attempt_id = login_with_totp(&app, &random_email).await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = login_with_totp(&app, &random_email).await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_409_if_2fa_already_enabled() {
+    let random_email = signup_and_login(&app).await;
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": secret.generate_code(now()).as_ref() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let emails = app.emails(&random_email).await;
+    assert!(emails.iter().any(|email| email
+        .text_body
+        .contains("Two-factor authentication was turned on")));
+
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    // Can't swap the secret of the existing factor either
+    let response = app
+        .post_confirm_totp(
+            &serde_json::json!({ "code": secret.generate_code(now() + 30).as_ref() }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+}
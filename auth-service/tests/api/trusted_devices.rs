@@ -0,0 +1,216 @@
+use auth_service::{
+    domain::TrustedDeviceStoreError,
+    routes::{ListTrustedDevicesResponse, SignupResponse, TwoFactorAuthResponse},
+    utils::TRUSTED_DEVICE_COOKIE_NAME,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn login_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+    })
+}
+
+// Signs up a user with 2FA and returns their recovery codes
+async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+
+    response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes
+}
+
+// Logs in with a recovery code as second factor, optionally remembering the device
+async fn login_with_2fa(
+    app: &TestApp,
+    email: &str,
+    recovery_code: &str,
+    remember_device: bool,
+) -> reqwest::Response {
+    let response = app.post_login(&login_body(email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": recovery_code,
+            "rememberDevice": remember_device,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+}
+
+async fn trusted_devices(app: &TestApp) -> ListTrustedDevicesResponse {
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<ListTrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to ListTrustedDevicesResponse")
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.delete_trusted_device("device_id").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_skip_2fa_on_remembered_device() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_2fa(&app, &random_email, &recovery_codes[0], true).await;
+
+    let device_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == TRUSTED_DEVICE_COOKIE_NAME)
+        .expect("No trusted device cookie found");
+    assert!(!device_cookie.value().is_empty());
+    assert!(device_cookie.max_age().is_some());
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = trusted_devices(&app).await;
+    assert_eq!(json_body.devices.len(), 1);
+    assert!(json_body.devices[0].current);
+}
+
+#[api_test]
+async fn should_require_2fa_if_device_not_remembered() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_2fa(&app, &random_email, &recovery_codes[0], false).await;
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != TRUSTED_DEVICE_COOKIE_NAME));
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    assert!(trusted_devices(&app).await.devices.is_empty());
+}
+
+#[api_test]
+async fn should_require_2fa_after_device_revoked() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    login_with_2fa(&app, &random_email, &recovery_codes[0], true).await;
+
+    let device_id = trusted_devices(&app).await.devices[0].id.clone();
+
+    let response = app.delete_trusted_device(&device_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let result = app.trusted_device_store.read().await.get_device(&device_id).await;
+    assert_eq!(result, Err(TrustedDeviceStoreError::DeviceNotFound));
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_require_2fa_after_logout_all() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    login_with_2fa(&app, &random_email, &recovery_codes[0], true).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_forget_devices_when_2fa_disabled() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    login_with_2fa(&app, &random_email, &recovery_codes[0], true).await;
+
+    let device_id = trusted_devices(&app).await.devices[0].id.clone();
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "2FACode": recovery_codes[1],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let result = app.trusted_device_store.read().await.get_device(&device_id).await;
+    assert_eq!(result, Err(TrustedDeviceStoreError::DeviceNotFound));
+    assert!(trusted_devices(&app).await.devices.is_empty());
+}
+
+#[api_test]
+async fn should_return_404_if_device_belongs_to_another_user() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    login_with_2fa(&app, &random_email, &recovery_codes[0], true).await;
+
+    let device_id = trusted_devices(&app).await.devices[0].id.clone();
+
+    let other_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": other_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&other_email).await;
+
+    let response = app.post_login(&login_body(&other_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_trusted_device(&device_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Trusted device not found".to_owned()
+    );
+
+    // The device of the other user is still trusted
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,257 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId, TwoFACode, RECOVERY_CODE_COUNT},
+    routes::{
+        RecoveryCodesResponse, RemainingRecoveryCodesResponse, Request2FACodeResponse,
+        SignupResponse, TwoFactorAuthResponse,
+    },
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn login_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+    })
+}
+
+// Signs up and logs in, using a recovery code as second factor if 2FA is required.
+// Returns the recovery codes that are left.
+async fn signup_and_login(app: &TestApp, email: &str, requires_2fa: bool) -> Vec<String> {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+
+    let mut recovery_codes = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes;
+
+    let response = app.post_login(&login_body(email)).await;
+    if !requires_2fa {
+        assert_eq!(response.status().as_u16(), 200);
+        return recovery_codes;
+    }
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": recovery_codes.remove(0),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    recovery_codes
+}
+
+// Asks for a 2FA code and returns its login attempt id along with the code.
+// Stores only keep hashed codes, so the attempt gets a code known to the test.
+async fn request_2fa_code(app: &TestApp, email: &str) -> (String, String) {
+    let response = app.post_request_2fa_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = response
+        .json::<Request2FACodeResponse>()
+        .await
+        .expect("Could not deserialize response body to Request2FACodeResponse")
+        .login_attempt_id;
+
+    let code = TwoFACode::default();
+    app.two_fa_code_store
+        .write()
+        .await
+        .add_code(
+            Email::parse(email.to_owned()).unwrap(),
+            LoginAttemptId::parse(login_attempt_id.clone()).unwrap(),
+            code.clone(),
+        )
+        .await
+        .expect("Failed to set 2FA code");
+
+    (login_attempt_id, code.as_ref().to_owned())
+}
+
+fn wrong_code(code: &str) -> String {
+    match code {
+        "123456" => "654321".to_owned(),
+        _ => "123456".to_owned(),
+    }
+}
+
+async fn error_message(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.post_request_2fa_code().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_enable_2fa(&serde_json::json!({
+            "loginAttemptId": LoginAttemptId::default().as_ref(),
+            "2FACode": "123456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "2FACode": "123456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_enable_email_2fa_with_emailed_code() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email, false).await;
+
+    let (login_attempt_id, code) = request_2fa_code(&app, &random_email).await;
+
+    let response = app
+        .post_enable_2fa(&serde_json::json!({
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code(&code),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_enable_2fa(&serde_json::json!({
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse");
+    assert_eq!(json_body.recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(json_body.two_fa_method, "email".to_owned());
+
+    // The session is still valid, but 2FA can't be enabled twice
+    let (login_attempt_id, code) = request_2fa_code(&app, &random_email).await;
+    let response = app
+        .post_enable_2fa(&serde_json::json!({
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(error_message(response).await, "2FA is already enabled".to_owned());
+}
+
+#[api_test]
+async fn should_disable_2fa_with_password_and_recovery_code() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_and_login(&app, &random_email, true).await;
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "wrongpassword",
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Recovery codes are removed along with 2FA
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<RemainingRecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RemainingRecoveryCodesResponse");
+    assert_eq!(json_body.remaining, 0);
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "2FACode": recovery_codes[1],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error_message(response).await, "2FA is not enabled".to_owned());
+}
+
+#[api_test]
+async fn should_disable_email_2fa_with_emailed_code() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email, true).await;
+
+    let (login_attempt_id, code) = request_2fa_code(&app, &random_email).await;
+
+    // An emailed code is only accepted along with its login attempt
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code(&code),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -1,6 +1,8 @@
 use auth_service::{
-    domain::{Email, LoginAttemptId, TwoFACode},
-    utils::JWT_COOKIE_NAME,
+    domain::{Email, LoginAttemptId, TwoFACode, MAX_PENDING_LOGIN_ATTEMPTS},
+    routes::{TokenAuthResponse, TwoFactorAuthResponse},
+    utils::{DEFAULT_MAX_2FA_ATTEMPTS, JWT_COOKIE_NAME},
+    ErrorResponse,
 };
 use test_helpers::api_test;
 use uuid::Uuid;
@@ -48,6 +50,8 @@ async fn should_return_401_if_incorrect_credentials() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let email = Email::parse(random_email.to_string()).unwrap();
     let login_attempt_id = LoginAttemptId::parse(Uuid::new_v4().to_string()).unwrap();
     let code = TwoFACode::parse("123456".to_string()).unwrap();
@@ -85,6 +89,8 @@ async fn should_return_401_if_old_code() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let email = Email::parse(random_email.to_string()).unwrap();
     let login_attempt_id = LoginAttemptId::parse(Uuid::new_v4().to_string()).unwrap();
     let old_code = TwoFACode::parse("000000".to_string()).unwrap();
@@ -131,6 +137,8 @@ async fn should_return_200_if_correct_code() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let email = Email::parse(random_email.to_string()).unwrap();
     let login_attempt_id = LoginAttemptId::parse(Uuid::new_v4().to_string()).unwrap();
     let code = TwoFACode::parse("000000".to_string()).unwrap();
@@ -161,6 +169,56 @@ async fn should_return_200_if_correct_code() {
     assert!(!auth_cookie.value().is_empty());
 }
 
+#[api_test]
+async fn should_return_token_in_body_if_requested() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let email = Email::parse(random_email.to_string()).unwrap();
+    let login_attempt_id = LoginAttemptId::parse(Uuid::new_v4().to_string()).unwrap();
+    let code = TwoFACode::parse("000000".to_string()).unwrap();
+
+    app.two_fa_code_store
+        .write()
+        .await
+        .add_code(email, login_attempt_id.clone(), code)
+        .await
+        .expect("Failed to add 2FA code");
+
+    let verify_2fa_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id.as_ref(),
+        "2FACode": "000000",
+        "returnToken": true,
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME);
+
+    assert!(auth_cookie.is_none());
+
+    let token = response
+        .json::<TokenAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenAuthResponse")
+        .token;
+
+    assert!(!token.is_empty());
+}
+
 #[api_test]
 async fn should_return_401_if_same_code_twice() {
     let random_email = get_random_email();
@@ -173,6 +231,8 @@ async fn should_return_401_if_same_code_twice() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let email = Email::parse(random_email.to_string()).unwrap();
     let login_attempt_id = LoginAttemptId::parse(Uuid::new_v4().to_string()).unwrap();
     let code = TwoFACode::parse("000000".to_string()).unwrap();
@@ -205,3 +265,216 @@ async fn should_return_401_if_same_code_twice() {
 
     assert_eq!(response.status().as_u16(), 401);
 }
+
+#[api_test]
+async fn should_return_429_and_invalidate_code_after_too_many_incorrect_codes() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let email = Email::parse(random_email.to_string()).unwrap();
+    let login_attempt_id = LoginAttemptId::parse(Uuid::new_v4().to_string()).unwrap();
+    let code = TwoFACode::parse("000000".to_string()).unwrap();
+
+    let mut two_fa_code_store = app.two_fa_code_store.write().await;
+    if two_fa_code_store
+        .add_code(email, login_attempt_id.clone(), code)
+        .await
+        .is_err()
+    {
+        panic!("test failed");
+    };
+    drop(two_fa_code_store);
+
+    let incorrect_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id.as_ref(),
+        "2FACode": "111111",
+    });
+
+    for _ in 1..DEFAULT_MAX_2FA_ATTEMPTS {
+        let response = app.post_verify_2fa(&incorrect_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_verify_2fa(&incorrect_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many incorrect 2FA codes, please log in again".to_owned()
+    );
+
+    // The correct code no longer works, the user has to log in again
+    let verify_2fa_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id.as_ref(),
+        "2FACode": "000000",
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+// Logs in with the password and returns the login attempt id and its 2FA code.
+// Stores only keep hashed codes, so the attempt gets a code known to the test.
+async fn login_with_2fa(app: &TestApp, email: &str) -> (String, String) {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code = TwoFACode::default();
+    app.two_fa_code_store
+        .write()
+        .await
+        .add_code(
+            Email::parse(email.to_owned()).unwrap(),
+            LoginAttemptId::parse(login_attempt_id.clone()).unwrap(),
+            code.clone(),
+        )
+        .await
+        .expect("Failed to set 2FA code");
+
+    (login_attempt_id, code.as_ref().to_owned())
+}
+
+#[api_test]
+async fn should_verify_concurrent_login_attempts_independently() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let (first_attempt_id, first_code) = login_with_2fa(&app, &random_email).await;
+    let (second_attempt_id, second_code) = login_with_2fa(&app, &random_email).await;
+
+    // A code only works for the login attempt it was sent for
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": first_attempt_id,
+            "2FACode": second_code,
+        }))
+        .await;
+    if first_code != second_code {
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": second_attempt_id,
+            "2FACode": second_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": first_attempt_id,
+            "2FACode": first_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_for_oldest_login_attempt_over_cap() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let mut login_attempts = Vec::new();
+    for _ in 0..=MAX_PENDING_LOGIN_ATTEMPTS {
+        login_attempts.push(login_with_2fa(&app, &random_email).await);
+    }
+
+    let (oldest_attempt_id, oldest_code) = &login_attempts[0];
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": oldest_attempt_id,
+            "2FACode": oldest_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let (latest_attempt_id, latest_code) = &login_attempts[MAX_PENDING_LOGIN_ATTEMPTS];
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": latest_attempt_id,
+            "2FACode": latest_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_accept_code_only_once_when_verified_concurrently() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let (login_attempt_id, code) = login_with_2fa(&app, &random_email).await;
+
+    let verify_2fa_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    });
+    let (first, second) = tokio::join!(
+        app.post_verify_2fa(&verify_2fa_body),
+        app.post_verify_2fa(&verify_2fa_body),
+    );
+
+    let mut statuses = [first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+    assert_eq!(statuses, [200, 401]);
+}
\ No newline at end of file
@@ -0,0 +1,227 @@
+use auth_service::{
+    domain::Email, routes::VerifyEmailResponse, utils::VERIFICATION_EMAIL_RESEND_COOLDOWN_SECS,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn login(app: &TestApp, email: &str) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    app.post_login(&login_body).await
+}
+
+#[api_test]
+async fn should_email_verification_link_on_signup() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    assert_eq!(app.deliver_emails().await, 1);
+    let email = app
+        .email_client
+        .last_email(&Email::parse(random_email).unwrap())
+        .expect("No verification email was sent");
+    assert_eq!(email.subject, "Verify your Auth Service email address");
+    assert!(email.html_body.contains("/verify-email?token="));
+}
+
+#[api_test]
+async fn should_return_403_if_email_not_verified() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let response = login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email address not verified".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_before_403_if_incorrect_password() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_allow_login_after_email_verified() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let token = app.email_verification_token(&random_email).await;
+    let response = app.get_verify_email(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<VerifyEmailResponse>()
+            .await
+            .expect("Could not deserialize response body to VerifyEmailResponse")
+            .message,
+        "Email address verified".to_owned()
+    );
+
+    let response = login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Following the link again does no harm
+    let response = app.get_verify_email(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_token() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    let token = app.email_verification_token(&random_email).await;
+
+    let tampered = format!("{}x", token);
+    for token in ["invalid", "", tampered.as_str()] {
+        let response = app.get_verify_email(token).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for token: {}", token);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid or expired verification link".to_owned()
+        );
+    }
+
+    let response = login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[api_test]
+async fn should_return_400_if_account_deleted() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    let token = app.email_verification_token(&random_email).await;
+
+    let delete_account_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+    let response = app.delete_account(&delete_account_body).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.get_verify_email(&token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_resend_verification_email() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    assert_eq!(app.deliver_emails().await, 1);
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    app.verify_email(&random_email).await;
+    let response = login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_429_with_wait_time_during_cooldown() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    let body = serde_json::json!({ "email": random_email });
+
+    let response = app.post_resend_verification_email(&body).await;
+    assert_eq!(response.status().as_u16(), 202);
+    assert_eq!(app.deliver_emails().await, 2);
+
+    let response = app.post_resend_verification_email(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after_header = response
+        .headers()
+        .get("retry-after")
+        .expect("No Retry-After header found")
+        .to_str()
+        .unwrap()
+        .parse::<u64>()
+        .unwrap();
+
+    let json_body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+
+    assert_eq!(
+        json_body.error,
+        "Please wait before requesting another verification email".to_owned()
+    );
+    assert_eq!(json_body.retry_after, Some(retry_after_header));
+    assert!(
+        retry_after_header > 0 && retry_after_header <= VERIFICATION_EMAIL_RESEND_COOLDOWN_SECS
+    );
+    assert_eq!(app.deliver_emails().await, 0);
+
+    // Unknown addresses are throttled the same way
+    let body = serde_json::json!({ "email": get_random_email() });
+    app.post_resend_verification_email(&body).await;
+    let response = app.post_resend_verification_email(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[api_test]
+async fn should_return_202_without_email_if_unknown_or_verified() {
+    let verified_email = get_random_email();
+    signup(&app, &verified_email).await;
+    app.verify_email(&verified_email).await;
+
+    for email in [verified_email, get_random_email()] {
+        let response = app
+            .post_resend_verification_email(&serde_json::json!({ "email": email }))
+            .await;
+        assert_eq!(response.status().as_u16(), 202);
+    }
+
+    assert_eq!(app.deliver_emails().await, 0);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_email() {
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": "invalid" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_422_if_malformed_input() {
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "address": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+}
\ No newline at end of file
@@ -0,0 +1,639 @@
+use auth_service::{
+    domain::{Email, WebAuthnChallengeStoreError, WebAuthnCredential},
+    routes::{
+        FinishPasskeyRegistrationResponse, ListPasskeysResponse, SignupResponse,
+        StartPasskeyLoginResponse, StartPasskeyRegistrationResponse, TwoFactorAuthResponse,
+    },
+    utils::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::Value;
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+const ORIGIN: &str = "http://localhost:3000";
+const RP_ID: &str = "localhost";
+
+// Authenticator data flags
+const USER_PRESENT: u8 = 0x01;
+const USER_VERIFIED: u8 = 0x04;
+const ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// Answers WebAuthn ceremonies like a platform authenticator holding one passkey
+struct SoftwareAuthenticator {
+    key_pair: EcdsaKeyPair,
+    credential_id: String,
+    sign_count: u32,
+    origin: String,
+    flags: u8,
+}
+
+impl SoftwareAuthenticator {
+    fn new() -> Self {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+
+        Self {
+            key_pair,
+            credential_id: URL_SAFE_NO_PAD.encode(uuid::Uuid::new_v4().as_bytes()),
+            sign_count: 0,
+            origin: ORIGIN.to_owned(),
+            flags: USER_PRESENT | USER_VERIFIED,
+        }
+    }
+
+    fn client_data(&self, ceremony: &str, challenge: &str) -> Vec<u8> {
+        serde_json::json!({
+            "type": ceremony,
+            "challenge": challenge,
+            "origin": self.origin,
+            "crossOrigin": false,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn authenticator_data(&self, flags: u8) -> Vec<u8> {
+        let mut data = digest::digest(&digest::SHA256, RP_ID.as_bytes())
+            .as_ref()
+            .to_vec();
+        data.push(flags);
+        data.extend_from_slice(&self.sign_count.to_be_bytes());
+        data
+    }
+
+    // Response to `navigator.credentials.create()` with "none" attestation
+    fn create(&self, ceremony_id: &str, challenge: &str) -> serde_json::Value {
+        let public_key = self.key_pair.public_key().as_ref();
+        let cose_key = Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (Value::Integer(3.into()), Value::Integer((-7).into())),
+            (Value::Integer((-1).into()), Value::Integer(1.into())),
+            (
+                Value::Integer((-2).into()),
+                Value::Bytes(public_key[1..33].to_vec()),
+            ),
+            (
+                Value::Integer((-3).into()),
+                Value::Bytes(public_key[33..].to_vec()),
+            ),
+        ]);
+        let credential_id = URL_SAFE_NO_PAD.decode(&self.credential_id).unwrap();
+
+        let mut auth_data = self.authenticator_data(self.flags | ATTESTED_CREDENTIAL_DATA);
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&credential_id);
+        ciborium::into_writer(&cose_key, &mut auth_data).unwrap();
+
+        let attestation_object = Value::Map(vec![
+            (
+                Value::Text("fmt".to_owned()),
+                Value::Text("none".to_owned()),
+            ),
+            (Value::Text("attStmt".to_owned()), Value::Map(vec![])),
+            (Value::Text("authData".to_owned()), Value::Bytes(auth_data)),
+        ]);
+        let mut attestation_bytes = Vec::new();
+        ciborium::into_writer(&attestation_object, &mut attestation_bytes).unwrap();
+
+        serde_json::json!({
+            "ceremonyId": ceremony_id,
+            "credential": {
+                "id": self.credential_id,
+                "type": "public-key",
+                "response": {
+                    "clientDataJSON": URL_SAFE_NO_PAD.encode(self.client_data("webauthn.create", challenge)),
+                    "attestationObject": URL_SAFE_NO_PAD.encode(attestation_bytes),
+                },
+            },
+        })
+    }
+
+    // Response to `navigator.credentials.get()`, counting the signature
+    fn get(&mut self, ceremony_id: &str, challenge: &str) -> serde_json::Value {
+        self.sign_count += 1;
+
+        let client_data = self.client_data("webauthn.get", challenge);
+        let auth_data = self.authenticator_data(self.flags);
+
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(digest::digest(&digest::SHA256, &client_data).as_ref());
+        let signature = self.key_pair.sign(&SystemRandom::new(), &signed).unwrap();
+
+        serde_json::json!({
+            "ceremonyId": ceremony_id,
+            "credential": {
+                "id": self.credential_id,
+                "type": "public-key",
+                "response": {
+                    "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                    "authenticatorData": URL_SAFE_NO_PAD.encode(auth_data),
+                    "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+                },
+            },
+        })
+    }
+}
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    let body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+
+    let response = app.post_login(&login_body(email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+fn login_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+    })
+}
+
+fn password_body() -> serde_json::Value {
+    serde_json::json!({ "password": "password123" })
+}
+
+// Registers the authenticator's passkey for the logged in user
+async fn register_passkey(app: &TestApp, authenticator: &SoftwareAuthenticator) {
+    let response = app.post_webauthn_register_start(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<StartPasskeyRegistrationResponse>()
+        .await
+        .expect("Could not deserialize response body to StartPasskeyRegistrationResponse");
+    assert_eq!(options.public_key.rp.id, RP_ID);
+
+    let response = app
+        .post_webauthn_register_finish(
+            &authenticator.create(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let json_body = response
+        .json::<FinishPasskeyRegistrationResponse>()
+        .await
+        .expect("Could not deserialize response body to FinishPasskeyRegistrationResponse");
+    assert_eq!(json_body.credential_id, authenticator.credential_id);
+}
+
+async fn start_passwordless_login(app: &TestApp) -> StartPasskeyLoginResponse {
+    let response = app.post_webauthn_login_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<StartPasskeyLoginResponse>()
+        .await
+        .expect("Could not deserialize response body to StartPasskeyLoginResponse")
+}
+
+async fn error_message(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.post_webauthn_register_start(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_webauthn_credentials().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_401_if_password_incorrect() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let response = app
+        .post_webauthn_register_start(&serde_json::json!({ "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_webauthn_register_start(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[api_test]
+async fn should_log_in_with_passkey_alone() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_passwordless_login(&app).await;
+    assert!(options.public_key.allow_credentials.is_empty());
+    assert_eq!(options.public_key.user_verification, "required");
+
+    let response = app
+        .post_webauthn_login_finish(
+            &authenticator.get(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    // The signature counter is remembered to detect cloned authenticators
+    let credential = app
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credential(&authenticator.credential_id)
+        .await
+        .unwrap();
+    assert_eq!(credential.sign_count, 1);
+}
+
+#[api_test]
+async fn should_accept_passkey_as_second_factor() {
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+    let recovery_codes = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes;
+
+    let login_attempt_id = |response: reqwest::Response| async move {
+        assert_eq!(response.status().as_u16(), 206);
+        response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id
+    };
+
+    // Passkeys can only be registered once logged in
+    let attempt = login_attempt_id(app.post_login(&login_body(&random_email)).await).await;
+    let start_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": attempt,
+    });
+    let response = app.post_webauthn_login_start_with(&start_body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error_message(response).await, "No passkey registered");
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": attempt,
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    // Holding the passkey is enough for a second factor
+    authenticator.flags = USER_PRESENT;
+    register_passkey(&app, &authenticator).await;
+
+    let attempt = login_attempt_id(app.post_login(&login_body(&random_email)).await).await;
+    let start_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": attempt,
+    });
+    let response = app.post_webauthn_login_start_with(&start_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<StartPasskeyLoginResponse>()
+        .await
+        .expect("Could not deserialize response body to StartPasskeyLoginResponse");
+    assert_eq!(options.public_key.allow_credentials.len(), 1);
+    assert_eq!(
+        options.public_key.allow_credentials[0].id,
+        authenticator.credential_id
+    );
+
+    let response = app
+        .post_webauthn_login_finish(
+            &authenticator.get(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The login attempt is used up
+    let response = app.post_webauthn_login_start_with(&start_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Without user verification the passkey can't replace the password
+    let options = start_passwordless_login(&app).await;
+    let response = app
+        .post_webauthn_login_finish(
+            &authenticator.get(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_origin_is_wrong() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    authenticator.origin = "https://evil.example.com".to_owned();
+    let options = start_passwordless_login(&app).await;
+    let response = app
+        .post_webauthn_login_finish(
+            &authenticator.get(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(error_message(response).await, "Incorrect credentials");
+}
+
+#[api_test]
+async fn should_return_401_if_challenge_is_replayed() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_passwordless_login(&app).await;
+    let body = authenticator.get(&options.ceremony_id, &options.public_key.challenge);
+
+    let response = app.post_webauthn_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_webauthn_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let result = app
+        .webauthn_challenge_store
+        .write()
+        .await
+        .take_challenge(&options.ceremony_id)
+        .await;
+    assert_eq!(result, Err(WebAuthnChallengeStoreError::ChallengeNotFound));
+}
+
+#[api_test]
+async fn should_return_409_if_passkey_already_registered() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let response = app.post_webauthn_register_start(&password_body()).await;
+    let options = response
+        .json::<StartPasskeyRegistrationResponse>()
+        .await
+        .expect("Could not deserialize response body to StartPasskeyRegistrationResponse");
+    assert_eq!(options.public_key.exclude_credentials.len(), 1);
+
+    let response = app
+        .post_webauthn_register_finish(
+            &authenticator.create(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[api_test]
+async fn should_check_user_handle() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let credential = app
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credential(&authenticator.credential_id)
+        .await
+        .unwrap();
+
+    // Further passkeys are registered under the same handle
+    let response = app.post_webauthn_register_start(&password_body()).await;
+    let options = response
+        .json::<StartPasskeyRegistrationResponse>()
+        .await
+        .expect("Could not deserialize response body to StartPasskeyRegistrationResponse");
+    assert_eq!(options.public_key.user.id, credential.user_handle);
+
+    let options = start_passwordless_login(&app).await;
+    let mut body = authenticator.get(&options.ceremony_id, &options.public_key.challenge);
+    body["credential"]["response"]["userHandle"] =
+        URL_SAFE_NO_PAD.encode(uuid::Uuid::new_v4().as_bytes()).into();
+    let response = app.post_webauthn_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let options = start_passwordless_login(&app).await;
+    let mut body = authenticator.get(&options.ceremony_id, &options.public_key.challenge);
+    body["credential"]["response"]["userHandle"] = credential.user_handle.into();
+    let response = app.post_webauthn_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_403_if_email_not_verified() {
+    let random_email = get_random_email();
+    let body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    let credential = WebAuthnCredential {
+        id: authenticator.credential_id.clone(),
+        email: Email::parse(random_email).unwrap(),
+        user_handle: URL_SAFE_NO_PAD.encode(uuid::Uuid::new_v4().as_bytes()),
+        public_key: authenticator.key_pair.public_key().as_ref().to_vec(),
+        sign_count: 0,
+        created_at: 0,
+    };
+    app.webauthn_credential_store
+        .write()
+        .await
+        .add_credential(credential)
+        .await
+        .unwrap();
+
+    let options = start_passwordless_login(&app).await;
+    let response = app
+        .post_webauthn_login_finish(
+            &authenticator.get(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(error_message(response).await, "Email address not verified");
+}
+
+#[api_test]
+async fn should_list_and_delete_passkeys() {
+    let other_email = get_random_email();
+    signup_and_login(&app, &other_email).await;
+    let other_authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &other_authenticator).await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let response = app.get_webauthn_credentials().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<ListPasskeysResponse>()
+        .await
+        .expect("Could not deserialize response body to ListPasskeysResponse");
+    assert_eq!(json_body.passkeys.len(), 1);
+    assert_eq!(json_body.passkeys[0].id, authenticator.credential_id);
+
+    // Users may only remove their own passkeys
+    let response = app
+        .delete_webauthn_credential(&other_authenticator.credential_id)
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(error_message(response).await, "Passkey not found");
+
+    let response = app
+        .delete_webauthn_credential(&authenticator.credential_id)
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .delete_webauthn_credential(&authenticator.credential_id)
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.get_webauthn_credentials().await;
+    let json_body = response
+        .json::<ListPasskeysResponse>()
+        .await
+        .expect("Could not deserialize response body to ListPasskeysResponse");
+    assert!(json_body.passkeys.is_empty());
+}
+
+#[api_test]
+async fn should_remove_passkeys_when_2fa_disabled() {
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+    let recovery_codes = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes;
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({
+            "password": "password123",
+            "2FACode": recovery_codes[1],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = start_passwordless_login(&app).await;
+    let response = app
+        .post_webauthn_login_finish(
+            &authenticator.get(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_remove_passkeys_when_password_reset() {
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let token = app.password_reset_token(&random_email).await;
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": token,
+            "password": "new-password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = start_passwordless_login(&app).await;
+    let response = app
+        .post_webauthn_login_finish(
+            &authenticator.get(&options.ceremony_id, &options.public_key.challenge),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::{NotificationChannel, NotificationTarget, TwoFAMethod},
+    services::{WebhookNotificationChannel, WebhookSettings},
+};
+use wiremock::{
+    matchers::{body_json, header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const TOKEN: &str = "webhook-token";
+
+fn channel(server: &MockServer, timeout: Duration) -> WebhookNotificationChannel {
+    WebhookNotificationChannel::new(WebhookSettings {
+        url: format!("{}/sms", server.uri()),
+        token: TOKEN.to_owned(),
+        timeout,
+    })
+    .unwrap()
+}
+
+async fn send_notification(channel: &WebhookNotificationChannel) -> Result<(), String> {
+    let recipient = NotificationTarget::parse(TwoFAMethod::Sms, "+15551234567".to_owned()).unwrap();
+    channel
+        .send_notification(&recipient, "Your 2FA code is 123456")
+        .await
+}
+
+#[tokio::test]
+async fn should_post_notification_to_webhook() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/sms"))
+        .and(header(
+            "Authorization",
+            format!("Bearer {}", TOKEN).as_str(),
+        ))
+        .and(header("Content-Type", "application/json"))
+        .and(body_json(serde_json::json!({
+            "to": "+15551234567",
+            "message": "Your 2FA code is 123456",
+        })))
+        .respond_with(ResponseTemplate::new(202))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = send_notification(&channel(&server, Duration::from_secs(5))).await;
+    assert_eq!(result, Ok(()));
+}
+
+#[tokio::test]
+async fn should_fail_if_webhook_rejects_notification() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("Unknown number"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = send_notification(&channel(&server, Duration::from_secs(5))).await;
+    assert!(result.unwrap_err().contains("Unknown number"));
+}
+
+#[tokio::test]
+async fn should_time_out_slow_responses() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = send_notification(&channel(&server, Duration::from_millis(100))).await;
+    assert!(result.is_err());
+}